@@ -40,6 +40,21 @@ mod scissor_box;
 #[doc(inline)]
 pub use scissor_box::*;
 
+mod clip_plane;
+#[doc(inline)]
+pub use clip_plane::*;
+
+mod profiler;
+#[doc(inline)]
+pub use profiler::*;
+
+mod occlusion_query;
+#[doc(inline)]
+pub use occlusion_query::*;
+
+mod state_cache;
+use state_cache::*;
+
 pub mod prelude {
 
     //!
@@ -67,6 +82,8 @@ pub enum CoreError {
     ShaderCompilation(String, String, String),
     #[error("failed to link shader program: {0}")]
     ShaderLink(String),
+    #[error("failed to parse KTX2 container: {0}")]
+    InvalidKtx2(String),
 }
 
 mod data_type;
@@ -88,15 +105,38 @@ fn from_byte_slice<'a, T: DataType>(data: &'a [u8]) -> &'a [T] {
 }
 
 fn format_from_data_type<T: DataType>() -> u32 {
-    match T::size() {
-        1 => crate::context::RED,
-        2 => crate::context::RG,
-        3 => crate::context::RGB,
-        4 => crate::context::RGBA,
-        _ => unreachable!(),
+    if is_integer_data_type::<T>() {
+        match T::size() {
+            1 => crate::context::RED_INTEGER,
+            2 => crate::context::RG_INTEGER,
+            3 => crate::context::RGB_INTEGER,
+            4 => crate::context::RGBA_INTEGER,
+            _ => unreachable!(),
+        }
+    } else {
+        match T::size() {
+            1 => crate::context::RED,
+            2 => crate::context::RG,
+            3 => crate::context::RGB,
+            4 => crate::context::RGBA,
+            _ => unreachable!(),
+        }
     }
 }
 
+// Returns true if the data is not normalized when used in a texture, ie. the data is accessed as integers in the shader.
+fn is_integer_data_type<T: DataType>() -> bool {
+    is_integer_type(T::data_type())
+}
+
+fn is_integer_type(data_type: u32) -> bool {
+    data_type == crate::context::BYTE
+        || data_type == crate::context::SHORT
+        || data_type == crate::context::UNSIGNED_SHORT
+        || data_type == crate::context::INT
+        || data_type == crate::context::UNSIGNED_INT
+}
+
 fn flip_y<T: TextureDataType>(pixels: &mut [T], width: usize, height: usize) {
     for row in 0..height / 2 {
         for col in 0..width {
@@ -43,48 +43,324 @@ impl BufferDataType for Quat {}
 
 impl<T: BufferDataType + ?Sized> BufferDataType for &T {}
 
+///
+/// Hints how often the data in a buffer is changed, which the graphics driver can use to place the buffer in the most efficient memory.
+/// If no usage is specified using for example [VertexBuffer::set_usage], the usage is [BufferUsage::Static] until the buffer is filled a second time, after which it is [BufferUsage::Dynamic].
+///
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BufferUsage {
+    /// The data is specified once and used many times.
+    Static,
+    /// The data is changed repeatedly and used many times.
+    Dynamic,
+    /// The data is specified once and used at most a few times, for example data that changes every frame.
+    Stream,
+}
+
+impl BufferUsage {
+    fn to_const(&self) -> u32 {
+        match self {
+            Self::Static => crate::context::STATIC_DRAW,
+            Self::Dynamic => crate::context::DYNAMIC_DRAW,
+            Self::Stream => crate::context::STREAM_DRAW,
+        }
+    }
+}
+
+///
+/// Data which is being read back from a buffer on the GPU, see for example [VertexBuffer::read_async].
+/// The GPU executes commands asynchronously, so reading a buffer right away stalls until all previous commands have finished.
+/// Instead, the data is copied on the GPU and is available from [BufferReadback::result] when the copy has finished, typically one or more frames later.
+///
+pub struct BufferReadback<T: DataType> {
+    context: Context,
+    id: crate::context::Buffer,
+    fence: crate::context::Fence,
+    layout: ReadLayout,
+    _data_type: std::marker::PhantomData<T>,
+}
+
+impl<T: DataType> BufferReadback<T> {
+    ///
+    /// Returns whether the data is available without stalling.
+    ///
+    pub fn is_ready(&self) -> bool {
+        let status = unsafe { self.context.client_wait_sync(self.fence, 0, 0) };
+        status == crate::context::ALREADY_SIGNALED || status == crate::context::CONDITION_SATISFIED
+    }
+
+    ///
+    /// Returns the data, or `None` if the data is not available yet.
+    ///
+    pub fn result(&self) -> Option<Vec<T>> {
+        if self.is_ready() {
+            Some(self.read())
+        } else {
+            None
+        }
+    }
+
+    ///
+    /// Returns the data and stalls until the data is available if necessary.
+    ///
+    pub fn wait(self) -> Vec<T> {
+        self.read()
+    }
+
+    fn read(&self) -> Vec<T> {
+        self.layout
+            .extract(&read_bytes(&self.context, self.id, self.layout.byte_size()))
+    }
+}
+
+impl<T: DataType> Drop for BufferReadback<T> {
+    fn drop(&mut self) {
+        unsafe {
+            self.context.delete_sync(self.fence);
+            self.context.delete_buffer(self.id);
+        }
+    }
+}
+
+// Describes where to find the values to read in a buffer.
+#[derive(Clone, Copy, Debug)]
+struct ReadLayout {
+    count: usize,
+    element_size: usize,
+    stride: usize,
+    offset: usize,
+}
+
+impl ReadLayout {
+    fn byte_size(&self) -> usize {
+        if self.count == 0 {
+            0
+        } else {
+            (self.count - 1) * self.stride + self.offset + self.element_size
+        }
+    }
+
+    fn extract<T: DataType>(&self, bytes: &[u8]) -> Vec<T> {
+        let mut packed = Vec::with_capacity(self.count * self.element_size);
+        for i in 0..self.count {
+            let start = i * self.stride + self.offset;
+            packed.extend_from_slice(&bytes[start..start + self.element_size]);
+        }
+        from_byte_slice(&packed).to_vec()
+    }
+}
+
+fn read_bytes(context: &Context, id: crate::context::Buffer, byte_size: usize) -> Vec<u8> {
+    let mut bytes = vec![0u8; byte_size];
+    if byte_size > 0 {
+        unsafe {
+            context.bind_buffer(crate::context::COPY_READ_BUFFER, Some(id));
+            context.get_buffer_sub_data(crate::context::COPY_READ_BUFFER, 0, &mut bytes);
+            context.bind_buffer(crate::context::COPY_READ_BUFFER, None);
+        }
+    }
+    bytes
+}
+
 struct Buffer {
     context: Context,
     id: crate::context::Buffer,
+    target: u32,
+    usage: Option<BufferUsage>,
     attribute_count: u32,
+    attribute_byte_size: usize,
+    capacity: usize,
     data_type: u32,
     data_size: u32,
 }
 
 impl Buffer {
-    pub fn new(context: &Context) -> Self {
+    pub fn new(context: &Context, target: u32) -> Self {
         Self {
             context: context.clone(),
             id: unsafe { context.create_buffer().expect("Failed creating buffer") },
+            target,
+            usage: None,
             attribute_count: 0,
+            attribute_byte_size: 0,
+            capacity: 0,
             data_type: 0,
             data_size: 0,
         }
     }
 
-    pub fn new_with_data<T: BufferDataType>(context: &Context, data: &[T]) -> Self {
-        let mut buffer = Self::new(context);
+    pub fn new_with_data<T: DataType>(context: &Context, target: u32, data: &[T]) -> Self {
+        let mut buffer = Self::new(context, target);
         if data.len() > 0 {
             buffer.fill(data);
         }
         buffer
     }
 
-    pub fn fill<T: BufferDataType>(&mut self, data: &[T]) {
+    pub fn set_usage(&mut self, usage: BufferUsage) {
+        self.usage = Some(usage);
+    }
+
+    // Fills the buffer with the given data. The buffer is only reallocated if the data does not fit within the capacity of the buffer.
+    pub fn fill<T: DataType>(&mut self, data: &[T]) {
+        let bytes = to_byte_slice(data);
         self.bind();
         unsafe {
-            self.context.buffer_data_u8_slice(
-                crate::context::ARRAY_BUFFER,
-                to_byte_slice(data),
-                if self.attribute_count > 0 {
-                    crate::context::DYNAMIC_DRAW
+            if bytes.len() > 0 && bytes.len() <= self.capacity {
+                self.context.buffer_sub_data_u8_slice(self.target, 0, bytes);
+            } else {
+                let usage = self.usage.unwrap_or(if self.attribute_count > 0 {
+                    BufferUsage::Dynamic
                 } else {
-                    crate::context::STATIC_DRAW
-                },
-            );
-            self.context.bind_buffer(crate::context::ARRAY_BUFFER, None);
+                    BufferUsage::Static
+                });
+                self.context
+                    .buffer_data_u8_slice(self.target, bytes, usage.to_const());
+                self.capacity = bytes.len();
+            }
+            self.context.bind_buffer(self.target, None);
         }
         self.attribute_count = data.len() as u32;
+        self.set_data_type::<T>();
+    }
+
+    // Fills the buffer with the given data starting at the attribute with index `offset`, leaving the rest of the buffer unchanged.
+    // The data can extend the buffer beyond the current number of attributes, as long as it fits within the capacity.
+    pub fn fill_subset<T: DataType>(&mut self, offset: u32, data: &[T]) {
+        if self.attribute_count > 0 {
+            check_data_type::<T>(self.data_type, self.data_size);
+        }
+        let byte_offset = offset as usize * std::mem::size_of::<T>();
+        let bytes = to_byte_slice(data);
+        assert!(
+            offset <= self.attribute_count && byte_offset + bytes.len() <= self.capacity,
+            "cannot fill the buffer from offset {} with {} attributes, since there are only {} attributes in the buffer and room for {}",
+            offset,
+            data.len(),
+            self.attribute_count,
+            self.capacity / std::mem::size_of::<T>().max(1)
+        );
+        self.bind();
+        unsafe {
+            self.context
+                .buffer_sub_data_u8_slice(self.target, byte_offset as i32, bytes);
+            self.context.bind_buffer(self.target, None);
+        }
+        self.attribute_count = self.attribute_count.max(offset + data.len() as u32);
+        self.set_data_type::<T>();
+    }
+
+    // Makes room for at least the given number of attributes of type T without reallocating. The data already in the buffer is kept.
+    pub fn reserve<T: DataType>(&mut self, capacity: u32) {
+        if self.attribute_count > 0 {
+            check_data_type::<T>(self.data_type, self.data_size);
+        }
+        let byte_capacity = capacity as usize * std::mem::size_of::<T>();
+        if byte_capacity <= self.capacity {
+            return;
+        }
+        let usage = self.usage.unwrap_or(BufferUsage::Dynamic).to_const();
+        let used_bytes = self.attribute_count as usize * self.attribute_byte_size;
+        unsafe {
+            let id = self
+                .context
+                .create_buffer()
+                .expect("Failed creating buffer");
+            // The new buffer is allocated using its own target, since the first target a buffer is bound to
+            // determines whether it can be used as an element buffer on web.
+            self.context.bind_buffer(self.target, Some(id));
+            self.context
+                .buffer_data_size(self.target, byte_capacity as i32, usage);
+            self.context.bind_buffer(self.target, None);
+            if used_bytes > 0 {
+                self.context
+                    .bind_buffer(crate::context::COPY_WRITE_BUFFER, Some(id));
+                self.context
+                    .bind_buffer(crate::context::COPY_READ_BUFFER, Some(self.id));
+                self.context.copy_buffer_sub_data(
+                    crate::context::COPY_READ_BUFFER,
+                    crate::context::COPY_WRITE_BUFFER,
+                    0,
+                    0,
+                    used_bytes as i32,
+                );
+                self.context
+                    .bind_buffer(crate::context::COPY_READ_BUFFER, None);
+                self.context
+                    .bind_buffer(crate::context::COPY_WRITE_BUFFER, None);
+            }
+            self.context.delete_buffer(self.id);
+            self.id = id;
+        }
+        self.capacity = byte_capacity;
+        if self.attribute_count == 0 {
+            self.set_data_type::<T>();
+        }
+    }
+
+    // The layout for reading all attributes in the buffer as the type T.
+    pub fn read_layout<T: DataType>(&self) -> ReadLayout {
+        check_data_type::<T>(self.data_type, self.data_size);
+        ReadLayout {
+            count: self.attribute_count as usize,
+            element_size: std::mem::size_of::<T>(),
+            stride: std::mem::size_of::<T>(),
+            offset: 0,
+        }
+    }
+
+    // Reads the values described by the layout. Stalls until all previous commands have finished.
+    pub fn read<T: DataType>(&self, layout: ReadLayout) -> Vec<T> {
+        layout.extract(&read_bytes(&self.context, self.id, layout.byte_size()))
+    }
+
+    // Copies the values described by the layout to a new buffer on the GPU which can be read when the copy has finished.
+    pub fn read_async<T: DataType>(&self, layout: ReadLayout) -> BufferReadback<T> {
+        let byte_size = layout.byte_size();
+        unsafe {
+            let id = self
+                .context
+                .create_buffer()
+                .expect("Failed creating buffer");
+            self.context
+                .bind_buffer(crate::context::COPY_WRITE_BUFFER, Some(id));
+            self.context.buffer_data_size(
+                crate::context::COPY_WRITE_BUFFER,
+                byte_size as i32,
+                crate::context::STREAM_READ,
+            );
+            if byte_size > 0 {
+                self.context
+                    .bind_buffer(crate::context::COPY_READ_BUFFER, Some(self.id));
+                self.context.copy_buffer_sub_data(
+                    crate::context::COPY_READ_BUFFER,
+                    crate::context::COPY_WRITE_BUFFER,
+                    0,
+                    0,
+                    byte_size as i32,
+                );
+                self.context
+                    .bind_buffer(crate::context::COPY_READ_BUFFER, None);
+            }
+            self.context
+                .bind_buffer(crate::context::COPY_WRITE_BUFFER, None);
+            let fence = self
+                .context
+                .fence_sync(crate::context::SYNC_GPU_COMMANDS_COMPLETE, 0)
+                .expect("Failed creating fence");
+            self.context.flush();
+            BufferReadback {
+                context: self.context.clone(),
+                id,
+                fence,
+                layout,
+                _data_type: std::marker::PhantomData,
+            }
+        }
+    }
+
+    fn set_data_type<T: DataType>(&mut self) {
+        self.attribute_byte_size = std::mem::size_of::<T>();
         self.data_type = T::data_type();
         self.data_size = T::size();
     }
@@ -93,12 +369,32 @@ impl Buffer {
         self.attribute_count
     }
 
+    // The number of attributes that fit in the buffer without reallocating.
+    pub fn capacity(&self) -> u32 {
+        if self.attribute_byte_size == 0 {
+            0
+        } else {
+            (self.capacity / self.attribute_byte_size) as u32
+        }
+    }
+
     pub fn bind(&self) {
         unsafe {
-            self.context
-                .bind_buffer(crate::context::ARRAY_BUFFER, Some(self.id));
+            self.context.bind_buffer(self.target, Some(self.id));
+        }
+    }
+
+    // Binds the buffer to the given index of an indexed target, for example the transform feedback buffer.
+    pub fn bind_base(&self, target: u32, index: u32) {
+        unsafe {
+            self.context.bind_buffer_base(target, index, Some(self.id));
         }
     }
+
+    // Sets the number of attributes after the data has been written on the GPU, for example using transform feedback.
+    pub fn set_attribute_count(&mut self, count: u32) {
+        self.attribute_count = count;
+    }
 }
 
 impl Drop for Buffer {
@@ -108,3 +404,15 @@ impl Drop for Buffer {
         }
     }
 }
+
+fn check_data_type<T: DataType>(data_type: u32, data_size: u32) {
+    if T::data_type() != data_type || T::size() != data_size {
+        panic!(
+            "cannot use a buffer with {} components of data type {:#x} as {} components of data type {:#x}",
+            data_size,
+            data_type,
+            T::size(),
+            T::data_type()
+        );
+    }
+}
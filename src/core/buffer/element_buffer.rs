@@ -1,3 +1,4 @@
+use super::Buffer;
 use crate::core::*;
 
 /// The basic data type used for each index in an element buffer.
@@ -29,10 +30,7 @@ impl ElementBufferDataType for u32 {
 /// See for example [Program::draw_elements] to use this for drawing.
 ///
 pub struct ElementBuffer {
-    context: Context,
-    id: crate::context::Buffer,
-    count: usize,
-    data_type: u32,
+    buffer: Buffer,
 }
 
 impl ElementBuffer {
@@ -40,12 +38,8 @@ impl ElementBuffer {
     /// Creates a new empty element buffer.
     ///
     pub fn new(context: &Context) -> Self {
-        let id = unsafe { context.create_buffer().expect("Failed creating buffer") };
         Self {
-            context: context.clone(),
-            id,
-            count: 0,
-            data_type: 0,
+            buffer: Buffer::new(context, crate::context::ELEMENT_ARRAY_BUFFER),
         }
     }
 
@@ -53,61 +47,94 @@ impl ElementBuffer {
     /// Creates a new element buffer and fills it with the given indices which must be divisable by 3.
     ///
     pub fn new_with_data<T: ElementBufferDataType>(context: &Context, data: &[T]) -> Self {
-        let mut buffer = Self::new(context);
-        if data.len() > 0 {
-            buffer.fill(data);
+        Self {
+            buffer: Buffer::new_with_data(context, crate::context::ELEMENT_ARRAY_BUFFER, data),
         }
-        buffer
     }
 
     ///
     /// Fills the buffer with the given indices which must be divisable by 3.
+    /// The buffer is only reallocated if the indices do not fit within the capacity of the buffer (see [ElementBuffer::reserve]).
     ///
     pub fn fill<T: ElementBufferDataType>(&mut self, data: &[T]) {
-        self.bind();
-        unsafe {
-            self.context.buffer_data_u8_slice(
-                crate::context::ELEMENT_ARRAY_BUFFER,
-                to_byte_slice(data),
-                crate::context::STATIC_DRAW,
-            );
-            self.context
-                .bind_buffer(crate::context::ELEMENT_ARRAY_BUFFER, None);
-        }
-        self.count = data.len();
-        self.data_type = T::data_type();
+        self.buffer.fill(data);
+    }
+
+    ///
+    /// Fills the buffer with the given indices starting at the index with index `offset`, leaving the rest of the buffer unchanged.
+    /// The indices must be of the same type as the indices the buffer was last filled with.
+    /// The indices can extend beyond the current end of the buffer as long as they fit within the capacity (see [ElementBuffer::reserve]),
+    /// in which case the number of indices is increased.
+    ///
+    /// # Panic
+    /// Will panic if `offset` is larger than [ElementBuffer::count] or if the indices do not fit within [ElementBuffer::capacity].
+    ///
+    pub fn fill_subset<T: ElementBufferDataType>(&mut self, offset: u32, data: &[T]) {
+        self.buffer.fill_subset(offset, data);
+    }
+
+    ///
+    /// Makes room for at least `capacity` indices of type `T` without reallocating, so the buffer can grow using [ElementBuffer::fill_subset] or be refilled using [ElementBuffer::fill] without reallocating.
+    /// The indices already in the buffer are kept.
+    ///
+    pub fn reserve<T: ElementBufferDataType>(&mut self, capacity: u32) {
+        self.buffer.reserve::<T>(capacity)
+    }
+
+    ///
+    /// The number of indices that fit in the buffer without reallocating.
+    ///
+    pub fn capacity(&self) -> u32 {
+        self.buffer.capacity()
+    }
+
+    ///
+    /// Set the usage hint which is used the next time the buffer is allocated, see [BufferUsage].
+    ///
+    pub fn set_usage(&mut self, usage: BufferUsage) {
+        self.buffer.set_usage(usage)
     }
 
     ///
     /// The number of values in the buffer.
     ///
     pub fn count(&self) -> usize {
-        self.count
+        self.buffer.attribute_count() as usize
     }
 
     ///
     /// The number of triangles in the buffer.
     ///
     pub fn triangle_count(&self) -> usize {
-        self.count / 3
+        self.count() / 3
     }
 
-    pub(crate) fn bind(&self) {
-        unsafe {
-            self.context
-                .bind_buffer(crate::context::ELEMENT_ARRAY_BUFFER, Some(self.id));
-        }
+    ///
+    /// Reads the indices in this buffer back from the GPU.
+    /// This stalls until all previous commands have finished, so prefer [ElementBuffer::read_async] if performance matters.
+    ///
+    /// # Panic
+    /// Will panic if `T` is not the same type as the indices in the buffer.
+    ///
+    pub fn read<T: ElementBufferDataType>(&self) -> Vec<T> {
+        self.buffer.read(self.buffer.read_layout::<T>())
     }
 
-    pub(crate) fn data_type(&self) -> u32 {
-        self.data_type
+    ///
+    /// Starts reading the indices in this buffer back from the GPU without stalling, see [BufferReadback].
+    ///
+    /// # Panic
+    /// Will panic if `T` is not the same type as the indices in the buffer.
+    ///
+    pub fn read_async<T: ElementBufferDataType>(&self) -> BufferReadback<T> {
+        self.buffer.read_async(self.buffer.read_layout::<T>())
     }
-}
 
-impl Drop for ElementBuffer {
-    fn drop(&mut self) {
-        unsafe {
-            self.context.delete_buffer(self.id);
-        }
+    pub(crate) fn bind(&self) {
+        self.buffer.bind();
+    }
+
+    pub(crate) fn data_type(&self) -> u32 {
+        self.buffer.data_type
     }
 }
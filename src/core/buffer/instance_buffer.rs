@@ -15,7 +15,7 @@ impl InstanceBuffer {
     ///
     pub fn new(context: &Context) -> Self {
         Self {
-            buffer: Buffer::new(context),
+            buffer: Buffer::new(context, crate::context::ARRAY_BUFFER),
         }
     }
 
@@ -25,18 +25,58 @@ impl InstanceBuffer {
     ///
     pub fn new_with_data<T: BufferDataType>(context: &Context, data: &[T]) -> Self {
         Self {
-            buffer: Buffer::new_with_data(context, data),
+            buffer: Buffer::new_with_data(context, crate::context::ARRAY_BUFFER, data),
         }
     }
 
     ///
     /// Fills the instance buffer with the given data. The data should be in the same format as specified in the shader.
     /// As an example, if specified as `vec3` in the shader it needs to be specified as an array of `Vector3<T>` where `T` is a primitive type that implements [BufferDataType], for example can be f16 or f32.
+    /// The buffer is only reallocated if the data does not fit within the capacity of the buffer (see [InstanceBuffer::reserve]).
     ///
     pub fn fill<T: BufferDataType>(&mut self, data: &[T]) {
         self.buffer.fill(data)
     }
 
+    ///
+    /// Fills the instance buffer with the given data starting at the instance attribute with index `offset`, leaving the rest of the buffer unchanged.
+    /// The data must be of the same type as the data the buffer was last filled with.
+    /// The data can extend beyond the current end of the buffer as long as it fits within the capacity (see [InstanceBuffer::reserve]),
+    /// in which case the number of instance attributes is increased.
+    ///
+    /// # Panic
+    /// Will panic if `offset` is larger than [InstanceBuffer::instance_count], if the data does not fit within [InstanceBuffer::capacity]
+    /// or if the data is not of the same type as the data in the buffer.
+    ///
+    pub fn fill_subset<T: BufferDataType>(&mut self, offset: u32, data: &[T]) {
+        self.buffer.fill_subset(offset, data)
+    }
+
+    ///
+    /// Makes room for at least `capacity` instance attributes of type `T` without reallocating, so the buffer can grow using [InstanceBuffer::fill_subset] or be refilled using [InstanceBuffer::fill] without reallocating.
+    /// The data already in the buffer is kept.
+    ///
+    /// # Panic
+    /// Will panic if `T` is not the type of the data in the buffer.
+    ///
+    pub fn reserve<T: BufferDataType>(&mut self, capacity: u32) {
+        self.buffer.reserve::<T>(capacity)
+    }
+
+    ///
+    /// The number of instance attributes that fit in the buffer without reallocating.
+    ///
+    pub fn capacity(&self) -> u32 {
+        self.buffer.capacity()
+    }
+
+    ///
+    /// Set the usage hint which is used the next time the buffer is allocated, see [BufferUsage].
+    ///
+    pub fn set_usage(&mut self, usage: BufferUsage) {
+        self.buffer.set_usage(usage)
+    }
+
     ///
     /// The number of values in the buffer.
     ///
@@ -51,6 +91,27 @@ impl InstanceBuffer {
         self.buffer.attribute_count()
     }
 
+    ///
+    /// Reads the instance attributes in this buffer back from the GPU.
+    /// This stalls until all previous commands have finished, so prefer [InstanceBuffer::read_async] if performance matters.
+    ///
+    /// # Panic
+    /// Will panic if `T` does not have the same data type and number of components as the data in the buffer.
+    ///
+    pub fn read<T: BufferDataType>(&self) -> Vec<T> {
+        self.buffer.read(self.buffer.read_layout::<T>())
+    }
+
+    ///
+    /// Starts reading the instance attributes in this buffer back from the GPU without stalling, see [BufferReadback].
+    ///
+    /// # Panic
+    /// Will panic if `T` does not have the same data type and number of components as the data in the buffer.
+    ///
+    pub fn read_async<T: BufferDataType>(&self) -> BufferReadback<T> {
+        self.buffer.read_async(self.buffer.read_layout::<T>())
+    }
+
     pub(crate) fn bind(&self) {
         self.buffer.bind();
     }
@@ -1,14 +1,356 @@
 use crate::core::*;
 
+///
+/// The type of a variable in a uniform block, see [UniformBlockLayout].
+///
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum UniformBlockType {
+    /// A `float` in GLSL.
+    Float,
+    /// An `int` in GLSL.
+    Int,
+    /// A `uint` in GLSL.
+    UInt,
+    /// A `vec2` in GLSL.
+    Vec2,
+    /// A `vec3` in GLSL.
+    Vec3,
+    /// A `vec4` in GLSL.
+    Vec4,
+    /// An `ivec2` in GLSL.
+    IVec2,
+    /// An `ivec3` in GLSL.
+    IVec3,
+    /// An `ivec4` in GLSL.
+    IVec4,
+    /// A `mat2` in GLSL.
+    Mat2,
+    /// A `mat3` in GLSL.
+    Mat3,
+    /// A `mat4` in GLSL.
+    Mat4,
+}
+
+impl UniformBlockType {
+    // Returns the number of columns and the number of components in each column.
+    fn shape(&self) -> (usize, usize) {
+        match self {
+            Self::Float | Self::Int | Self::UInt => (1, 1),
+            Self::Vec2 | Self::IVec2 => (1, 2),
+            Self::Vec3 | Self::IVec3 => (1, 3),
+            Self::Vec4 | Self::IVec4 => (1, 4),
+            Self::Mat2 => (2, 2),
+            Self::Mat3 => (3, 3),
+            Self::Mat4 => (4, 4),
+        }
+    }
+
+    // The base alignment in bytes according to the std140 rules.
+    fn alignment(&self) -> usize {
+        match self.shape() {
+            (1, 1) => 4,
+            (1, 2) => 8,
+            _ => 16,
+        }
+    }
+
+    // The size in bytes according to the std140 rules, where each column of a matrix is aligned as a vec4.
+    fn size(&self) -> usize {
+        match self.shape() {
+            (1, rows) => 4 * rows,
+            (columns, _) => 16 * columns,
+        }
+    }
+}
+
+///
+/// A value that can be stored in a variable of a [UniformBuffer], see [UniformBuffer::set].
+///
+pub trait UniformBlockValue: Copy {
+    ///
+    /// Returns the type of the variable in the uniform block.
+    ///
+    fn uniform_block_type() -> UniformBlockType;
+
+    ///
+    /// Returns the bytes of each component of the value, where matrices are in column-major order.
+    ///
+    fn components(&self) -> Vec<[u8; 4]>;
+}
+
+impl UniformBlockValue for f32 {
+    fn uniform_block_type() -> UniformBlockType {
+        UniformBlockType::Float
+    }
+    fn components(&self) -> Vec<[u8; 4]> {
+        vec![self.to_ne_bytes()]
+    }
+}
+
+impl UniformBlockValue for i32 {
+    fn uniform_block_type() -> UniformBlockType {
+        UniformBlockType::Int
+    }
+    fn components(&self) -> Vec<[u8; 4]> {
+        vec![self.to_ne_bytes()]
+    }
+}
+
+impl UniformBlockValue for u32 {
+    fn uniform_block_type() -> UniformBlockType {
+        UniformBlockType::UInt
+    }
+    fn components(&self) -> Vec<[u8; 4]> {
+        vec![self.to_ne_bytes()]
+    }
+}
+
+impl UniformBlockValue for Vec2 {
+    fn uniform_block_type() -> UniformBlockType {
+        UniformBlockType::Vec2
+    }
+    fn components(&self) -> Vec<[u8; 4]> {
+        [self.x, self.y].iter().map(|v| v.to_ne_bytes()).collect()
+    }
+}
+
+impl UniformBlockValue for Vec3 {
+    fn uniform_block_type() -> UniformBlockType {
+        UniformBlockType::Vec3
+    }
+    fn components(&self) -> Vec<[u8; 4]> {
+        [self.x, self.y, self.z]
+            .iter()
+            .map(|v| v.to_ne_bytes())
+            .collect()
+    }
+}
+
+impl UniformBlockValue for Vec4 {
+    fn uniform_block_type() -> UniformBlockType {
+        UniformBlockType::Vec4
+    }
+    fn components(&self) -> Vec<[u8; 4]> {
+        [self.x, self.y, self.z, self.w]
+            .iter()
+            .map(|v| v.to_ne_bytes())
+            .collect()
+    }
+}
+
+impl UniformBlockValue for Vector2<i32> {
+    fn uniform_block_type() -> UniformBlockType {
+        UniformBlockType::IVec2
+    }
+    fn components(&self) -> Vec<[u8; 4]> {
+        [self.x, self.y].iter().map(|v| v.to_ne_bytes()).collect()
+    }
+}
+
+impl UniformBlockValue for Vector3<i32> {
+    fn uniform_block_type() -> UniformBlockType {
+        UniformBlockType::IVec3
+    }
+    fn components(&self) -> Vec<[u8; 4]> {
+        [self.x, self.y, self.z]
+            .iter()
+            .map(|v| v.to_ne_bytes())
+            .collect()
+    }
+}
+
+impl UniformBlockValue for Vector4<i32> {
+    fn uniform_block_type() -> UniformBlockType {
+        UniformBlockType::IVec4
+    }
+    fn components(&self) -> Vec<[u8; 4]> {
+        [self.x, self.y, self.z, self.w]
+            .iter()
+            .map(|v| v.to_ne_bytes())
+            .collect()
+    }
+}
+
+impl UniformBlockValue for Color {
+    fn uniform_block_type() -> UniformBlockType {
+        UniformBlockType::Vec4
+    }
+    fn components(&self) -> Vec<[u8; 4]> {
+        (vec4(self.r as f32, self.g as f32, self.b as f32, self.a as f32) / 255.0).components()
+    }
+}
+
+impl UniformBlockValue for Matrix2<f32> {
+    fn uniform_block_type() -> UniformBlockType {
+        UniformBlockType::Mat2
+    }
+    fn components(&self) -> Vec<[u8; 4]> {
+        [self.x.x, self.x.y, self.y.x, self.y.y]
+            .iter()
+            .map(|v| v.to_ne_bytes())
+            .collect()
+    }
+}
+
+impl UniformBlockValue for Mat3 {
+    fn uniform_block_type() -> UniformBlockType {
+        UniformBlockType::Mat3
+    }
+    fn components(&self) -> Vec<[u8; 4]> {
+        [
+            self.x.x, self.x.y, self.x.z, self.y.x, self.y.y, self.y.z, self.z.x, self.z.y,
+            self.z.z,
+        ]
+        .iter()
+        .map(|v| v.to_ne_bytes())
+        .collect()
+    }
+}
+
+impl UniformBlockValue for Mat4 {
+    fn uniform_block_type() -> UniformBlockType {
+        UniformBlockType::Mat4
+    }
+    fn components(&self) -> Vec<[u8; 4]> {
+        let m: &[f32; 16] = self.as_ref();
+        m.iter().map(|v| v.to_ne_bytes()).collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+struct UniformBlockVariable {
+    name: String,
+    type_: UniformBlockType,
+    length: Option<usize>,
+    offset: usize,
+    stride: usize,
+}
+
+impl UniformBlockVariable {
+    // Returns the byte offset of each component of each element in the order given by `UniformBlockValue::components`.
+    fn component_offsets(&self) -> Vec<usize> {
+        let (columns, rows) = self.type_.shape();
+        let mut offsets = Vec::new();
+        for element in 0..self.length.unwrap_or(1) {
+            for column in 0..columns {
+                for row in 0..rows {
+                    offsets.push(self.offset + element * self.stride + column * 16 + row * 4);
+                }
+            }
+        }
+        offsets
+    }
+}
+
+///
+/// Describes the variables in a uniform block using the std140 layout rules, which is the layout used by GLSL for a uniform block declared with `layout (std140)`.
+/// The variables must be added in the same order as they are declared in the shader, for example
+///
+/// ```glsl
+/// layout (std140) uniform Camera
+/// {
+///     mat4 viewProjection;
+///     vec3 position;
+///     float time;
+///     vec4 lightColors[4];
+/// };
+/// ```
+///
+/// is described by
+///
+/// ```no_run
+/// # use three_d::core::*;
+/// let layout = UniformBlockLayout::new()
+///     .with_variable("viewProjection", UniformBlockType::Mat4)
+///     .with_variable("position", UniformBlockType::Vec3)
+///     .with_variable("time", UniformBlockType::Float)
+///     .with_array("lightColors", UniformBlockType::Vec4, 4);
+/// ```
+///
+#[derive(Debug, Clone, Default)]
+pub struct UniformBlockLayout {
+    variables: Vec<UniformBlockVariable>,
+    size: usize,
+}
+
+impl UniformBlockLayout {
+    ///
+    /// Creates a new empty layout.
+    ///
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///
+    /// Adds a variable with the given name and type after the previously added variables.
+    ///
+    pub fn with_variable(self, name: &str, type_: UniformBlockType) -> Self {
+        self.add(name, type_, None)
+    }
+
+    ///
+    /// Adds an array with the given name, element type and length after the previously added variables.
+    /// Note that each element of an array is aligned to 16 bytes, so for example an array of `float` uses four times as much memory as the values.
+    ///
+    pub fn with_array(self, name: &str, type_: UniformBlockType, length: usize) -> Self {
+        self.add(name, type_, Some(length))
+    }
+
+    fn add(mut self, name: &str, type_: UniformBlockType, length: Option<usize>) -> Self {
+        let (alignment, stride, size) = if let Some(length) = length {
+            // Array elements are aligned as a vec4
+            let stride = round_up(type_.size(), 16);
+            (16, stride, stride * length)
+        } else {
+            (type_.alignment(), type_.size(), type_.size())
+        };
+        let offset = round_up(self.size, alignment);
+        self.variables.push(UniformBlockVariable {
+            name: name.to_string(),
+            type_,
+            length,
+            offset,
+            stride,
+        });
+        self.size = offset + size;
+        self
+    }
+
+    ///
+    /// Returns the size of the uniform block in bytes.
+    ///
+    pub fn size(&self) -> usize {
+        round_up(self.size, 16)
+    }
+
+    ///
+    /// Returns the byte offset of the variable with the given name, if it exists.
+    ///
+    pub fn offset(&self, name: &str) -> Option<usize> {
+        self.variable(name).map(|v| v.offset)
+    }
+
+    fn variable(&self, name: &str) -> Option<&UniformBlockVariable> {
+        self.variables.iter().find(|v| v.name == name)
+    }
+}
+
+fn round_up(value: usize, alignment: usize) -> usize {
+    (value + alignment - 1) / alignment * alignment
+}
+
 ///
 /// A buffer for transferring a set of uniform variables to the shader program
 /// (see also [use_uniform_block](crate::core::Program::use_uniform_block)).
+/// A buffer created with [UniformBuffer::new_with_layout] is laid out according to the std140 rules (see [UniformBlockLayout]),
+/// so it can be shared between several shader programs declaring the uniform block with `layout (std140)`, for example to share camera and light data.
 ///
 pub struct UniformBuffer {
     context: Context,
     id: crate::context::Buffer,
     offsets: Vec<usize>,
     data: Vec<f32>,
+    layout: Option<UniformBlockLayout>,
 }
 
 impl UniformBuffer {
@@ -32,11 +374,36 @@ impl UniformBuffer {
             id,
             offsets,
             data: vec![0.0; length as usize],
+            layout: None,
         };
         buffer.send();
         buffer
     }
 
+    ///
+    /// Creates a new uniform buffer with the variables described by the given std140 layout.
+    /// The variables are initialized to 0 and are set using [UniformBuffer::set] and [UniformBuffer::set_array].
+    ///
+    pub fn new_with_layout(context: &Context, layout: UniformBlockLayout) -> UniformBuffer {
+        let id = unsafe { context.create_buffer().expect("Failed creating buffer") };
+        let buffer = UniformBuffer {
+            context: context.clone(),
+            id,
+            offsets: layout.variables.iter().map(|v| v.offset / 4).collect(),
+            data: vec![0.0; layout.size() / 4],
+            layout: Some(layout),
+        };
+        buffer.send();
+        buffer
+    }
+
+    ///
+    /// Returns the std140 layout of the variables in this buffer, if it was created with [UniformBuffer::new_with_layout].
+    ///
+    pub fn layout(&self) -> Option<&UniformBlockLayout> {
+        self.layout.as_ref()
+    }
+
     pub(crate) fn bind(&self, id: u32) {
         unsafe {
             self.context
@@ -44,8 +411,59 @@ impl UniformBuffer {
         };
     }
 
+    ///
+    /// Set the value of the variable with the given name.
+    ///
+    /// # Panic
+    /// Will panic if this buffer was not created with [UniformBuffer::new_with_layout], if the variable does not exist, if it is an array or if the type of the value does not match the type of the variable.
+    ///
+    pub fn set<T: UniformBlockValue>(&mut self, name: &str, value: T) {
+        let variable = self.typed_variable::<T>(name);
+        if variable.length.is_some() {
+            panic!("the uniform block variable {} is an array", name);
+        }
+        self.write(&variable.component_offsets(), &value.components());
+    }
+
+    ///
+    /// Set the values of the array with the given name, starting at the element with the given index.
+    ///
+    /// # Panic
+    /// Will panic if this buffer was not created with [UniformBuffer::new_with_layout], if the array does not exist, if the type of the values does not match the type of the array elements or if the values do not fit in the array.
+    ///
+    pub fn set_array<T: UniformBlockValue>(
+        &mut self,
+        name: &str,
+        start_index: usize,
+        values: &[T],
+    ) {
+        let variable = self.typed_variable::<T>(name);
+        let length = variable
+            .length
+            .unwrap_or_else(|| panic!("the uniform block variable {} is not an array", name));
+        if start_index + values.len() > length {
+            panic!(
+                "cannot set {} values from index {} in the array {} of length {}",
+                values.len(),
+                start_index,
+                name,
+                length
+            );
+        }
+        let offsets = variable.component_offsets();
+        let components_per_element = offsets.len() / length;
+        let offsets = &offsets[start_index * components_per_element
+            ..(start_index + values.len()) * components_per_element];
+        let components = values
+            .iter()
+            .flat_map(|v| v.components())
+            .collect::<Vec<_>>();
+        self.write(offsets, &components);
+    }
+
     ///
     /// Update the values of the variable at the given index with the given data.
+    /// For a buffer created with [UniformBuffer::new_with_layout], the data is the memory of the variable in the std140 layout, ie. including the padding of matrix columns and array elements.
     ///
     /// # Panic
     /// Will panic if the index is not in the range `[0-max]` where `max` is the length of the `sizes` argument given at construction.
@@ -71,11 +489,11 @@ impl UniformBuffer {
                 self.offsets.len() - 1
             );
         }
-        //TODO: Send to GPU (contextBufferSubData)
     }
 
     ///
     /// Returns the values of the variable at the given index if inside the range of variables, otherwise `None`.
+    /// For a buffer created with [UniformBuffer::new_with_layout], the values are the memory of the variable in the std140 layout, see [UniformBuffer::update].
     ///
     pub fn get(&self, index: u32) -> Option<&[f32]> {
         self.offset_length(index as usize)
@@ -85,6 +503,13 @@ impl UniformBuffer {
     fn offset_length(&self, index: usize) -> Option<(usize, usize)> {
         if index >= self.offsets.len() {
             None
+        } else if let Some(ref layout) = self.layout {
+            let variable = &layout.variables[index];
+            let size = variable
+                .length
+                .map(|length| length * variable.stride)
+                .unwrap_or(variable.type_.size());
+            Some((variable.offset / 4, size / 4))
         } else {
             let offset = self.offsets[index];
             let length = if index + 1 == self.offsets.len() {
@@ -96,6 +521,44 @@ impl UniformBuffer {
         }
     }
 
+    fn typed_variable<T: UniformBlockValue>(&self, name: &str) -> UniformBlockVariable {
+        let variable = self
+            .layout
+            .as_ref()
+            .expect("the uniform buffer was not created with a layout")
+            .variable(name)
+            .unwrap_or_else(|| panic!("the uniform block variable {} does not exist", name));
+        if variable.type_ != T::uniform_block_type() {
+            panic!(
+                "the uniform block variable {} has type {:?}, but a value of type {:?} was given",
+                name,
+                variable.type_,
+                T::uniform_block_type()
+            );
+        }
+        variable.clone()
+    }
+
+    // Writes the components at the given byte offsets and only sends the part of the buffer that changed.
+    fn write(&mut self, offsets: &[usize], components: &[[u8; 4]]) {
+        for (offset, component) in offsets.iter().zip(components) {
+            self.data[*offset / 4] = f32::from_ne_bytes(*component);
+        }
+        if let (Some(start), Some(end)) = (offsets.iter().min(), offsets.iter().max()) {
+            unsafe {
+                self.context
+                    .bind_buffer(crate::context::UNIFORM_BUFFER, Some(self.id));
+                self.context.buffer_sub_data_u8_slice(
+                    crate::context::UNIFORM_BUFFER,
+                    *start as i32,
+                    to_byte_slice(&self.data[*start / 4..*end / 4 + 1]),
+                );
+                self.context
+                    .bind_buffer(crate::context::UNIFORM_BUFFER, None);
+            }
+        }
+    }
+
     fn send(&self) {
         unsafe {
             self.context
@@ -118,3 +581,64 @@ impl Drop for UniformBuffer {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn float_after_vec3_fills_the_padding() {
+        let layout = UniformBlockLayout::new()
+            .with_variable("position", UniformBlockType::Vec3)
+            .with_variable("time", UniformBlockType::Float)
+            .with_variable("color", UniformBlockType::Vec4);
+        assert_eq!(layout.offset("position"), Some(0));
+        assert_eq!(layout.offset("time"), Some(12));
+        assert_eq!(layout.offset("color"), Some(16));
+        assert_eq!(layout.size(), 32);
+    }
+
+    #[test]
+    fn vec2_is_aligned_to_8_bytes() {
+        let layout = UniformBlockLayout::new()
+            .with_variable("a", UniformBlockType::Float)
+            .with_variable("b", UniformBlockType::Vec2)
+            .with_variable("c", UniformBlockType::Vec3);
+        assert_eq!(layout.offset("b"), Some(8));
+        assert_eq!(layout.offset("c"), Some(16));
+    }
+
+    #[test]
+    fn mat3_columns_are_aligned_as_vec4() {
+        let layout = UniformBlockLayout::new()
+            .with_variable("a", UniformBlockType::Float)
+            .with_variable("m", UniformBlockType::Mat3)
+            .with_variable("b", UniformBlockType::Float);
+        assert_eq!(layout.offset("m"), Some(16));
+        assert_eq!(layout.offset("b"), Some(64));
+        assert_eq!(
+            layout.variable("m").unwrap().component_offsets(),
+            vec![16, 20, 24, 32, 36, 40, 48, 52, 56]
+        );
+    }
+
+    #[test]
+    fn array_elements_have_a_stride_of_16_bytes() {
+        let layout = UniformBlockLayout::new()
+            .with_array("values", UniformBlockType::Float, 3)
+            .with_variable("after", UniformBlockType::Float)
+            .with_array("colors", UniformBlockType::Vec3, 2);
+        assert_eq!(layout.offset("values"), Some(0));
+        assert_eq!(
+            layout.variable("values").unwrap().component_offsets(),
+            vec![0, 16, 32]
+        );
+        assert_eq!(layout.offset("after"), Some(48));
+        assert_eq!(layout.offset("colors"), Some(64));
+        assert_eq!(
+            layout.variable("colors").unwrap().component_offsets(),
+            vec![64, 68, 72, 80, 84, 88]
+        );
+        assert_eq!(layout.size(), 96);
+    }
+}
@@ -1,12 +1,70 @@
 use super::Buffer;
 use crate::core::*;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+///
+/// A named vertex attribute used to construct an interleaved vertex buffer, see [VertexBuffer::new_interleaved].
+///
+#[derive(Clone, Debug)]
+pub struct VertexAttribute<'a> {
+    name: String,
+    bytes: &'a [u8],
+    vertex_count: u32,
+    byte_size: usize,
+    data_type: u32,
+    data_size: u32,
+    normalized: bool,
+}
+
+impl<'a> VertexAttribute<'a> {
+    ///
+    /// Creates a new vertex attribute with the given name and one value per vertex.
+    /// The data should be in the same format as specified in the shader, see [VertexBuffer::new_with_data].
+    ///
+    pub fn new<T: BufferDataType>(name: &str, data: &'a [T]) -> Self {
+        Self {
+            name: name.to_string(),
+            bytes: to_byte_slice(data),
+            vertex_count: data.len() as u32,
+            byte_size: std::mem::size_of::<T>(),
+            data_type: T::data_type(),
+            data_size: T::size(),
+            normalized: false,
+        }
+    }
+
+    ///
+    /// Creates a new vertex attribute with the given name and one value per vertex, where integer values are normalized when accessed in the shader.
+    /// Unsigned values are mapped to the range `[0..1]` and signed values to the range `[-1..1]`,
+    /// so for example a normal can be stored as `[i8; 3]` and still be specified as `vec3` in the shader.
+    ///
+    pub fn new_normalized<T: BufferDataType>(name: &str, data: &'a [T]) -> Self {
+        Self {
+            normalized: true,
+            ..Self::new(name, data)
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct InterleavedLayout {
+    vertex_count: u32,
+    data_type: u32,
+    data_size: u32,
+    normalized: bool,
+    stride: u32,
+    offset: u32,
+}
 
 ///
 /// A buffer containing per vertex data, for example positions, normals, uv coordinates or colors.
 /// To send this data to a shader, use the [Program::use_vertex_attribute] method.
+/// Several attributes can share one buffer by interleaving the data of each vertex, see [VertexBuffer::new_interleaved].
 ///
 pub struct VertexBuffer {
-    buffer: Buffer,
+    buffer: Arc<Buffer>,
+    layout: Option<InterleavedLayout>,
 }
 
 impl VertexBuffer {
@@ -15,7 +73,8 @@ impl VertexBuffer {
     ///
     pub fn new(context: &Context) -> Self {
         Self {
-            buffer: Buffer::new(context),
+            buffer: Arc::new(Buffer::new(context, crate::context::ARRAY_BUFFER)),
+            layout: None,
         }
     }
 
@@ -25,41 +84,241 @@ impl VertexBuffer {
     ///
     pub fn new_with_data<T: BufferDataType>(context: &Context, data: &[T]) -> Self {
         Self {
-            buffer: Buffer::new_with_data(context, data),
+            buffer: Arc::new(Buffer::new_with_data(
+                context,
+                crate::context::ARRAY_BUFFER,
+                data,
+            )),
+            layout: None,
         }
     }
 
+    ///
+    /// Creates one vertex buffer for each of the given attributes, where the data of all attributes is interleaved in a single buffer on the GPU,
+    /// ie. all attributes of the first vertex are followed by all attributes of the second vertex and so on.
+    /// This uses less memory and improves the cache behaviour compared to one buffer per attribute,
+    /// especially when combined with small data types, for example normals stored as normalized `[i8; 3]` (see [VertexAttribute::new_normalized]).
+    /// The returned vertex buffers are used in the same way as any other vertex buffer and are indexed by the name of the attribute.
+    ///
+    /// Each attribute is aligned to 4 bytes within a vertex. The returned vertex buffers cannot be filled afterwards, instead create new interleaved vertex buffers.
+    ///
+    /// # Panic
+    /// Will panic if the attributes do not contain the same number of vertices.
+    ///
+    pub fn new_interleaved(
+        context: &Context,
+        attributes: &[VertexAttribute],
+    ) -> HashMap<String, VertexBuffer> {
+        let vertex_count = attributes.first().map(|a| a.vertex_count).unwrap_or(0);
+        let mut offsets = Vec::with_capacity(attributes.len());
+        let mut stride = 0;
+        for attribute in attributes {
+            if attribute.vertex_count != vertex_count {
+                panic!(
+                    "the vertex attribute {} has {} values, but the vertex attribute {} has {} values",
+                    attribute.name, attribute.vertex_count, attributes[0].name, vertex_count
+                );
+            }
+            stride = (stride + 3) / 4 * 4;
+            offsets.push(stride);
+            stride += attribute.byte_size;
+        }
+        let stride = (stride + 3) / 4 * 4;
+
+        let mut data = vec![0u8; stride * vertex_count as usize];
+        for (attribute, offset) in attributes.iter().zip(offsets.iter()) {
+            for (vertex, bytes) in attribute
+                .bytes
+                .chunks_exact(attribute.byte_size)
+                .enumerate()
+            {
+                let start = vertex * stride + offset;
+                data[start..start + attribute.byte_size].copy_from_slice(bytes);
+            }
+        }
+        let buffer = Arc::new(Buffer::new_with_data(
+            context,
+            crate::context::ARRAY_BUFFER,
+            &data,
+        ));
+        attributes
+            .iter()
+            .zip(offsets)
+            .map(|(attribute, offset)| {
+                (
+                    attribute.name.clone(),
+                    VertexBuffer {
+                        buffer: buffer.clone(),
+                        layout: Some(InterleavedLayout {
+                            vertex_count,
+                            data_type: attribute.data_type,
+                            data_size: attribute.data_size,
+                            normalized: attribute.normalized,
+                            stride: stride as u32,
+                            offset: offset as u32,
+                        }),
+                    },
+                )
+            })
+            .collect()
+    }
+
     ///
     /// Fills the vertex buffer with the given data. The data should be in the same format as specified in the shader.
     /// As an example, if specified as `vec3` in the shader it needs to be specified as an array of `Vector3<T>` where `T` is a primitive type that implements [BufferDataType], for example can be f16 or f32.
+    /// The buffer is only reallocated if the data does not fit within the capacity of the buffer (see [VertexBuffer::reserve]).
+    ///
+    /// # Panic
+    /// Will panic if the vertex buffer is part of an interleaved vertex buffer, see [VertexBuffer::new_interleaved].
     ///
     pub fn fill<T: BufferDataType>(&mut self, data: &[T]) {
-        self.buffer.fill(data);
+        self.buffer_mut().fill(data);
+    }
+
+    ///
+    /// Fills the vertex buffer with the given data starting at the vertex attribute with index `offset`, leaving the rest of the buffer unchanged.
+    /// The data must be of the same type as the data the buffer was last filled with.
+    /// The data can extend beyond the current end of the buffer as long as it fits within the capacity (see [VertexBuffer::reserve]),
+    /// in which case the number of vertex attributes is increased.
+    ///
+    /// # Panic
+    /// Will panic if `offset` is larger than [VertexBuffer::vertex_count], if the data does not fit within [VertexBuffer::capacity],
+    /// if the data is not of the same type as the data in the buffer or if the vertex buffer is part of an interleaved vertex buffer.
+    ///
+    pub fn fill_subset<T: BufferDataType>(&mut self, offset: u32, data: &[T]) {
+        self.buffer_mut().fill_subset(offset, data)
+    }
+
+    ///
+    /// Makes room for at least `capacity` vertex attributes of type `T` without reallocating, so the buffer can grow using [VertexBuffer::fill_subset] or be refilled using [VertexBuffer::fill] without reallocating.
+    /// The data already in the buffer is kept.
+    ///
+    /// # Panic
+    /// Will panic if `T` is not the type of the data in the buffer or if the vertex buffer is part of an interleaved vertex buffer.
+    ///
+    pub fn reserve<T: BufferDataType>(&mut self, capacity: u32) {
+        self.buffer_mut().reserve::<T>(capacity)
+    }
+
+    ///
+    /// The number of vertex attributes that fit in the buffer without reallocating.
+    ///
+    pub fn capacity(&self) -> u32 {
+        if let Some(layout) = self.layout {
+            layout.vertex_count
+        } else {
+            self.buffer.capacity()
+        }
+    }
+
+    ///
+    /// Set the usage hint which is used the next time the buffer is allocated, see [BufferUsage].
+    ///
+    /// # Panic
+    /// Will panic if the vertex buffer is part of an interleaved vertex buffer.
+    ///
+    pub fn set_usage(&mut self, usage: BufferUsage) {
+        self.buffer_mut().set_usage(usage)
     }
 
     ///
     /// The number of values in the buffer.
     ///
     pub fn count(&self) -> u32 {
-        self.buffer.attribute_count() * self.buffer.data_size
+        self.vertex_count() * self.data_size()
     }
 
     ///
     /// The number of vertex attributes in the buffer.
     ///
     pub fn vertex_count(&self) -> u32 {
-        self.buffer.attribute_count()
+        if let Some(layout) = self.layout {
+            layout.vertex_count
+        } else {
+            self.buffer.attribute_count()
+        }
+    }
+
+    ///
+    /// Reads the vertex attributes in this buffer back from the GPU.
+    /// This stalls until all previous commands have finished, so prefer [VertexBuffer::read_async] if performance matters.
+    ///
+    /// # Panic
+    /// Will panic if `T` does not have the same data type and number of components as the data in the buffer.
+    ///
+    pub fn read<T: BufferDataType>(&self) -> Vec<T> {
+        self.buffer.read(self.read_layout::<T>())
+    }
+
+    ///
+    /// Starts reading the vertex attributes in this buffer back from the GPU without stalling, see [BufferReadback].
+    ///
+    /// # Panic
+    /// Will panic if `T` does not have the same data type and number of components as the data in the buffer.
+    ///
+    pub fn read_async<T: BufferDataType>(&self) -> BufferReadback<T> {
+        self.buffer.read_async(self.read_layout::<T>())
+    }
+
+    fn read_layout<T: BufferDataType>(&self) -> super::ReadLayout {
+        if let Some(layout) = self.layout {
+            super::check_data_type::<T>(layout.data_type, layout.data_size);
+            super::ReadLayout {
+                count: layout.vertex_count as usize,
+                element_size: std::mem::size_of::<T>(),
+                stride: layout.stride as usize,
+                offset: layout.offset as usize,
+            }
+        } else {
+            self.buffer.read_layout::<T>()
+        }
     }
 
     pub(crate) fn bind(&self) {
         self.buffer.bind();
     }
 
+    pub(crate) fn bind_transform_feedback(&mut self, index: u32, count: u32) {
+        let buffer = self.buffer_mut();
+        if buffer.capacity() < count {
+            panic!(
+                "cannot capture {} values into a vertex buffer with room for {} values",
+                count,
+                buffer.capacity()
+            );
+        }
+        buffer.bind_base(crate::context::TRANSFORM_FEEDBACK_BUFFER, index);
+        buffer.set_attribute_count(count);
+    }
+
     pub(crate) fn data_type(&self) -> u32 {
-        self.buffer.data_type
+        self.layout
+            .map(|layout| layout.data_type)
+            .unwrap_or(self.buffer.data_type)
     }
 
     pub(crate) fn data_size(&self) -> u32 {
-        self.buffer.data_size
+        self.layout
+            .map(|layout| layout.data_size)
+            .unwrap_or(self.buffer.data_size)
+    }
+
+    pub(crate) fn normalized(&self) -> bool {
+        self.layout.map(|layout| layout.normalized).unwrap_or(false)
+    }
+
+    pub(crate) fn stride(&self) -> u32 {
+        self.layout.map(|layout| layout.stride).unwrap_or(0)
+    }
+
+    pub(crate) fn offset(&self) -> u32 {
+        self.layout.map(|layout| layout.offset).unwrap_or(0)
+    }
+
+    fn buffer_mut(&mut self) -> &mut Buffer {
+        if self.layout.is_some() {
+            panic!("cannot change a vertex buffer which is part of an interleaved vertex buffer");
+        }
+        Arc::get_mut(&mut self.buffer).unwrap()
     }
 }
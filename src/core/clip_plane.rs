@@ -0,0 +1,54 @@
+use crate::core::*;
+
+///
+/// The maximum number of clip planes that can be active at the same time, see [RenderTarget::render_with_clip_planes](crate::renderer::RenderTarget::render_with_clip_planes).
+///
+pub const MAX_CLIP_PLANES: usize = 8;
+
+///
+/// A plane in world space used for cutting geometry open, see [RenderTarget::render_with_clip_planes](crate::renderer::RenderTarget::render_with_clip_planes).
+/// A fragment at the world position `p` is kept if `normal.dot(p) + distance >= 0`, ie. if it is on the side of the plane the normal points to, otherwise it is discarded.
+///
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ClipPlane {
+    /// The normal of the plane which points towards the side that is kept.
+    pub normal: Vec3,
+    /// The signed distance from the plane to origo along the normal.
+    pub distance: f32,
+}
+
+impl ClipPlane {
+    ///
+    /// Creates a new clip plane that goes through the given point and keeps everything on the side the given normal points to.
+    ///
+    pub fn new(point: Vec3, normal: Vec3) -> Self {
+        let normal = normal.normalize();
+        Self {
+            normal,
+            distance: -normal.dot(point),
+        }
+    }
+
+    ///
+    /// Returns the signed distance from the plane to the given point, which is negative if the point is clipped.
+    ///
+    pub fn signed_distance(&self, point: Vec3) -> f32 {
+        self.normal.dot(point) + self.distance
+    }
+}
+
+impl From<ClipPlane> for Vec4 {
+    fn from(plane: ClipPlane) -> Self {
+        plane.normal.extend(plane.distance)
+    }
+}
+
+// The stencil operations used for capping geometry that is cut by clip planes.
+pub(crate) enum StencilMode {
+    // No stencil test.
+    Disabled,
+    // Clears the stencil buffer and then inverts the lowest bit for each fragment, ie. counts the number of surfaces behind each pixel modulo 2.
+    Invert,
+    // Only keeps fragments where the lowest bit is set and clears it, so each pixel is written at most once.
+    NotZero,
+}
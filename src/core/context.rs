@@ -17,6 +17,9 @@ pub struct Context {
     pub(super) vao: crate::context::VertexArray,
     programs: Arc<RwLock<HashMap<String, Program>>>,
     effects: Arc<RwLock<HashMap<String, ImageEffect>>>,
+    pub(super) clip_planes: Arc<RwLock<Vec<ClipPlane>>>,
+    pub(super) profiler: Arc<RwLock<Profiler>>,
+    state: Arc<RwLock<StateCache>>,
 }
 
 impl Context {
@@ -39,11 +42,15 @@ impl Context {
             let vao = context
                 .create_vertex_array()
                 .map_err(|e| CoreError::ContextCreation(e))?;
+            let profiler = Profiler::new(&context);
             Self {
                 context,
                 vao,
                 programs: Arc::new(RwLock::new(HashMap::new())),
                 effects: Arc::new(RwLock::new(HashMap::new())),
+                clip_planes: Arc::new(RwLock::new(Vec::new())),
+                profiler: Arc::new(RwLock::new(profiler)),
+                state: Arc::new(RwLock::new(StateCache::default())),
             }
         };
         Ok(c)
@@ -52,6 +59,8 @@ impl Context {
     ///
     /// Compiles a [Program] with the given vertex and fragment shader source and stores it for later use.
     /// If it has already been created, then it is just returned.
+    /// When rendering with clip planes (see [RenderTarget::render_with_clip_planes](crate::renderer::RenderTarget::render_with_clip_planes))
+    /// and the vertex shader outputs the world position as `out vec3 pos;`, then `USE_CLIP_PLANES` is defined in the fragment shader.
     ///
     pub fn program(
         &self,
@@ -59,11 +68,19 @@ impl Context {
         fragment_shader_source: &str,
         callback: impl FnOnce(&Program),
     ) -> Result<(), CoreError> {
+        // Clip planes are only enabled in the fragment shader if the vertex shader outputs the world position
+        let fragment_shader_source = if !self.clip_planes.read().unwrap().is_empty()
+            && vertex_shader_source.contains("out vec3 pos;")
+        {
+            format!("#define USE_CLIP_PLANES\n{}", fragment_shader_source)
+        } else {
+            fragment_shader_source.to_string()
+        };
         let key = format!("{}{}", vertex_shader_source, fragment_shader_source);
         if !self.programs.read().unwrap().contains_key(&key) {
             self.programs.write().unwrap().insert(
                 key.clone(),
-                Program::from_source(self, vertex_shader_source, fragment_shader_source)?,
+                Program::from_source(self, vertex_shader_source, &fragment_shader_source)?,
             );
         };
         callback(self.programs.read().unwrap().get(&key).unwrap());
@@ -100,10 +117,122 @@ impl Context {
         Ok(())
     }
 
+    // Uses the given clip planes in all draw calls issued inside the callback, see RenderTarget::render_with_clip_planes.
+    // The previous clip planes are restored afterwards, so the clip planes never leak into other render calls.
+    pub(crate) fn with_clip_planes<T>(
+        &self,
+        clip_planes: &[ClipPlane],
+        callback: impl FnOnce() -> T,
+    ) -> T {
+        if clip_planes.len() > MAX_CLIP_PLANES {
+            panic!(
+                "cannot use {} clip planes, the maximum number of clip planes is {}",
+                clip_planes.len(),
+                MAX_CLIP_PLANES
+            )
+        }
+        let previous = std::mem::replace(
+            &mut *self.clip_planes.write().unwrap(),
+            clip_planes.to_vec(),
+        );
+        let result = callback();
+        *self.clip_planes.write().unwrap() = previous;
+        result
+    }
+
+    // Clears the stencil buffer of the bound framebuffer and sets the stencil test, see StencilMode.
+    pub(crate) fn set_stencil_mode(&self, stencil_mode: StencilMode) {
+        unsafe {
+            match stencil_mode {
+                StencilMode::Disabled => self.disable(crate::context::STENCIL_TEST),
+                StencilMode::Invert => {
+                    self.enable(crate::context::STENCIL_TEST);
+                    self.stencil_mask(0xFF);
+                    self.clear_stencil(0);
+                    self.clear(crate::context::STENCIL_BUFFER_BIT);
+                    self.stencil_func(crate::context::ALWAYS, 0, 1);
+                    self.stencil_op(
+                        crate::context::KEEP,
+                        crate::context::KEEP,
+                        crate::context::INVERT,
+                    );
+                }
+                StencilMode::NotZero => {
+                    self.enable(crate::context::STENCIL_TEST);
+                    self.stencil_func(crate::context::NOTEQUAL, 0, 1);
+                    self.stencil_op(
+                        crate::context::KEEP,
+                        crate::context::KEEP,
+                        crate::context::ZERO,
+                    );
+                }
+            }
+        }
+    }
+
+    ///
+    /// Enables or disables profiling, see [Context::profile_scope]. Profiling is disabled by default.
+    ///
+    pub fn set_profiling_enabled(&self, enabled: bool) {
+        self.profiler.write().unwrap().set_enabled(enabled);
+    }
+
+    ///
+    /// Returns whether profiling is enabled, see [Context::set_profiling_enabled].
+    ///
+    pub fn is_profiling_enabled(&self) -> bool {
+        self.profiler.read().unwrap().is_enabled()
+    }
+
+    ///
+    /// Returns whether the GPU time spent inside a profiling scope can be measured.
+    /// This requires support for timer queries, which on web requires the `EXT_disjoint_timer_query_webgl2` extension.
+    ///
+    pub fn is_gpu_timing_supported(&self) -> bool {
+        self.profiler.read().unwrap().is_gpu_timing_supported()
+    }
+
+    ///
+    /// Measures the work done in the given callback, for example a shadow pass or a post effect, and identifies it by the given name.
+    /// The result contains the CPU time, the GPU time and the number of draw calls, triangles and state changes issued with [Program]s inside the callback.
+    /// Scopes can be nested, but only the GPU time of the outermost scope is measured.
+    /// Does nothing except calling the callback if profiling is not enabled, see [Context::set_profiling_enabled].
+    ///
+    /// The GPU executes the commands asynchronously, so the results are not available immediately, instead they are returned by
+    /// [Context::profiling_results] when the GPU has finished, typically a few frames later.
+    ///
+    pub fn profile_scope<T>(&self, name: &str, callback: impl FnOnce() -> T) -> T {
+        if !self.is_profiling_enabled() {
+            return callback();
+        }
+        self.profiler.write().unwrap().begin_scope(self, name);
+        let result = callback();
+        self.profiler.write().unwrap().end_scope(self);
+        result
+    }
+
+    ///
+    /// Returns the results of the profiling scopes (see [Context::profile_scope]) which the GPU has finished since the last call to this method,
+    /// in the order the scopes ended. Call this once each frame to collect the results.
+    ///
+    pub fn profiling_results(&self) -> Vec<ProfileResult> {
+        self.profiler.write().unwrap().results(self)
+    }
+
+    pub(crate) fn register_queued_state_changes(&self, state_changes: u32) {
+        self.profiler
+            .write()
+            .unwrap()
+            .register_queued_state_changes(state_changes);
+    }
+
     ///
     /// Set the scissor test for this context (see [ScissorBox]).
     ///
     pub fn set_scissor(&self, scissor_box: ScissorBox) {
+        if !StateCache::update(&mut self.state.write().unwrap().scissor_box, scissor_box) {
+            return;
+        }
         unsafe {
             if scissor_box.width > 0 && scissor_box.height > 0 {
                 self.enable(crate::context::SCISSOR_TEST);
@@ -123,6 +252,9 @@ impl Context {
     /// Set the viewport for this context (See [Viewport]).
     ///
     pub fn set_viewport(&self, viewport: Viewport) {
+        if !StateCache::update(&mut self.state.write().unwrap().viewport, viewport) {
+            return;
+        }
         unsafe {
             self.viewport(
                 viewport.x,
@@ -137,6 +269,9 @@ impl Context {
     /// Set the face culling for this context (see [Cull]).
     ///
     pub fn set_cull(&self, cull: Cull) {
+        if !StateCache::update(&mut self.state.write().unwrap().cull, cull) {
+            return;
+        }
         unsafe {
             match cull {
                 Cull::None => {
@@ -162,6 +297,9 @@ impl Context {
     /// Set the write mask for this context (see [WriteMask]).
     ///
     pub fn set_write_mask(&self, write_mask: WriteMask) {
+        if !StateCache::update(&mut self.state.write().unwrap().write_mask, write_mask) {
+            return;
+        }
         unsafe {
             self.color_mask(
                 write_mask.red,
@@ -177,8 +315,11 @@ impl Context {
     /// Set the depth test for this context (see [DepthTest]).
     ///
     pub fn set_depth_test(&self, depth_test: DepthTest) {
+        self.set_depth_test_enabled(true);
+        if !StateCache::update(&mut self.state.write().unwrap().depth_test, depth_test) {
+            return;
+        }
         unsafe {
-            self.enable(crate::context::DEPTH_TEST);
             match depth_test {
                 DepthTest::Never => {
                     self.depth_func(crate::context::NEVER);
@@ -212,6 +353,9 @@ impl Context {
     /// Set the blend state for this context (see [Blend]).
     ///
     pub fn set_blend(&self, blend: Blend) {
+        if !StateCache::update(&mut self.state.write().unwrap().blend, blend) {
+            return;
+        }
         unsafe {
             if let Blend::Enabled {
                 source_rgb_multiplier,
@@ -271,13 +415,125 @@ impl Context {
         self.set_cull(render_states.cull);
         self.set_write_mask(render_states.write_mask);
         if !render_states.write_mask.depth && render_states.depth_test == DepthTest::Always {
-            unsafe { self.disable(crate::context::DEPTH_TEST) }
+            self.set_depth_test_enabled(false);
         } else {
             self.set_depth_test(render_states.depth_test);
         }
         self.set_blend(render_states.blend);
     }
 
+    ///
+    /// Invalidates the shadow copy of the graphics state which is used to skip state changes that have no effect.
+    /// Must be called after changing the graphics state, for example the render states, viewport, bound program, textures or framebuffers,
+    /// by calling functions on the low-level graphics context directly, otherwise subsequent render calls might use the wrong state.
+    ///
+    pub fn invalidate_state(&self) {
+        *self.state.write().unwrap() = StateCache::default();
+    }
+
+    fn set_depth_test_enabled(&self, enabled: bool) {
+        if !StateCache::update(&mut self.state.write().unwrap().depth_test_enabled, enabled) {
+            return;
+        }
+        unsafe {
+            if enabled {
+                self.enable(crate::context::DEPTH_TEST);
+            } else {
+                self.disable(crate::context::DEPTH_TEST);
+            }
+        }
+    }
+
+    pub(in crate::core) fn set_program(&self, program: Option<crate::context::Program>) {
+        if StateCache::update(&mut self.state.write().unwrap().program, program) {
+            unsafe {
+                self.use_program(program);
+            }
+        }
+    }
+
+    pub(in crate::core) fn set_active_texture(&self, unit: u32) {
+        if StateCache::update(&mut self.state.write().unwrap().active_texture, unit) {
+            unsafe {
+                self.active_texture(crate::context::TEXTURE0 + unit);
+            }
+        }
+    }
+
+    pub(in crate::core) fn set_texture(&self, target: u32, texture: crate::context::Texture) {
+        let mut state = self.state.write().unwrap();
+        if let Some(unit) = state.active_texture {
+            if state.textures.insert((unit, target), texture) == Some(texture) {
+                return;
+            }
+        }
+        drop(state);
+        unsafe {
+            self.bind_texture(target, Some(texture));
+        }
+    }
+
+    pub(in crate::core) fn set_framebuffer(
+        &self,
+        target: u32,
+        framebuffer: Option<crate::context::Framebuffer>,
+    ) {
+        let mut state = self.state.write().unwrap();
+        let changed = match target {
+            crate::context::DRAW_FRAMEBUFFER => {
+                StateCache::update(&mut state.draw_framebuffer, framebuffer)
+            }
+            crate::context::READ_FRAMEBUFFER => {
+                StateCache::update(&mut state.read_framebuffer, framebuffer)
+            }
+            _ => {
+                StateCache::update(&mut state.draw_framebuffer, framebuffer)
+                    | StateCache::update(&mut state.read_framebuffer, framebuffer)
+            }
+        };
+        if changed {
+            unsafe {
+                self.bind_framebuffer(target, framebuffer);
+            }
+        }
+    }
+
+    pub(in crate::core) fn destroy_program(&self, program: crate::context::Program) {
+        if self.state.read().unwrap().program == Some(Some(program)) {
+            self.set_program(None);
+        }
+        unsafe {
+            self.delete_program(program);
+        }
+    }
+
+    pub(in crate::core) fn destroy_texture(&self, texture: crate::context::Texture) {
+        // Deleting a texture reverts all bindings of that texture to the default texture
+        self.state
+            .write()
+            .unwrap()
+            .textures
+            .retain(|_, bound| *bound != texture);
+        unsafe {
+            self.delete_texture(texture);
+        }
+    }
+
+    pub(in crate::core) fn destroy_framebuffer(&self, framebuffer: crate::context::Framebuffer) {
+        // Deleting a framebuffer reverts the bindings of that framebuffer to the default framebuffer
+        let mut state = self.state.write().unwrap();
+        if state.draw_framebuffer == Some(Some(framebuffer)) {
+            state.draw_framebuffer = Some(None);
+        }
+        if state.read_framebuffer == Some(Some(framebuffer)) {
+            state.read_framebuffer = Some(None);
+        }
+        drop(state);
+        unsafe {
+            self.delete_framebuffer(framebuffer);
+        }
+    }
+
     ///
     /// Returns an error if an GPU-side error has happened while rendering which can be used to check for errors while developing.
     /// Can also be used in production to handle unexpected rendering errors, but do not call it too often to avoid performance problems.
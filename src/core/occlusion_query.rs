@@ -0,0 +1,75 @@
+use crate::core::*;
+
+///
+/// A query which tests whether any samples pass the depth test while rendering, for example to find out whether an object is hidden behind other objects.
+/// The GPU executes the render calls asynchronously, so the result is typically available one or more frames after the query was run.
+///
+pub struct OcclusionQuery {
+    context: Context,
+    id: crate::context::Query,
+    target: u32,
+}
+
+impl OcclusionQuery {
+    ///
+    /// Creates a new occlusion query.
+    ///
+    pub fn new(context: &Context) -> Result<Self, CoreError> {
+        let id = unsafe { context.create_query() }.map_err(CoreError::ContextError)?;
+        // The conservative version is faster, but is not available on desktop OpenGL 3.3
+        let target = if context.version().is_embedded {
+            crate::context::ANY_SAMPLES_PASSED_CONSERVATIVE
+        } else {
+            crate::context::ANY_SAMPLES_PASSED
+        };
+        Ok(Self {
+            context: context.clone(),
+            id,
+            target,
+        })
+    }
+
+    ///
+    /// Runs the query while calling the render calls in the given callback. Only one occlusion query can run at a time.
+    /// Must be called in the callback given as input to a [RenderTarget], [ColorTarget] or [DepthTarget] write method.
+    /// A query can be run any number of times, so prefer reusing a query over creating a new one each time.
+    ///
+    pub fn run(&self, render: impl FnOnce()) {
+        unsafe {
+            self.context.begin_query(self.target, self.id);
+        }
+        render();
+        unsafe {
+            self.context.end_query(self.target);
+        }
+    }
+
+    ///
+    /// Returns whether any samples passed the depth test the last time the query was run, or `None` if the result is not available yet.
+    ///
+    pub fn result(&self) -> Option<bool> {
+        unsafe {
+            if self
+                .context
+                .get_query_parameter_u32(self.id, crate::context::QUERY_RESULT_AVAILABLE)
+                != 0
+            {
+                Some(
+                    self.context
+                        .get_query_parameter_u32(self.id, crate::context::QUERY_RESULT)
+                        != 0,
+                )
+            } else {
+                None
+            }
+        }
+    }
+}
+
+impl Drop for OcclusionQuery {
+    fn drop(&mut self) {
+        unsafe {
+            self.context.delete_query(self.id);
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use crate::context::HasContext;
+use crate::core::*;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+// Not defined by the low-level context since it is only part of the disjoint timer query extensions.
+const GPU_DISJOINT: u32 = 0x8FBB;
+
+///
+/// The timings and counters measured inside a named scope, see [Context::profile_scope].
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProfileResult {
+    /// The name of the scope.
+    pub name: String,
+    /// The number of scopes this scope is nested inside, ie. zero for an outermost scope.
+    pub depth: usize,
+    /// The time the GPU has spent executing the commands issued inside the scope.
+    /// Is `None` if timer queries are not supported (see [Context::is_gpu_timing_supported]), if the scope is nested inside another scope
+    /// or if the GPU timing was disrupted, for example by a change in GPU frequency.
+    pub gpu_time: Option<Duration>,
+    /// The time the CPU has spent inside the scope.
+    pub cpu_time: Duration,
+    /// The number of draw calls issued inside the scope.
+    pub draw_calls: u32,
+    /// The number of triangles drawn inside the scope, including all instances.
+    pub triangles: u64,
+    /// The number of draw calls inside the scope which used another program or other render states than the previous draw call.
+    pub state_changes: u32,
+    /// The number of state changes between the objects rendered inside the scope as predicted by the [RenderQueue](crate::renderer::RenderQueue)s
+    /// used by the render methods on [RenderTarget], see [RenderQueue::state_changes](crate::renderer::RenderQueue::state_changes).
+    pub queued_state_changes: u32,
+}
+
+struct ActiveScope {
+    result: ProfileResult,
+    start: instant::Instant,
+    query: Option<crate::context::Query>,
+}
+
+struct PendingScope {
+    result: ProfileResult,
+    query: Option<crate::context::Query>,
+}
+
+pub(super) struct Profiler {
+    enabled: bool,
+    timer_queries_supported: bool,
+    disjoint_supported: bool,
+    active: Vec<ActiveScope>,
+    pending: VecDeque<PendingScope>,
+    last_program: Option<crate::context::Program>,
+    last_render_states: Option<RenderStates>,
+}
+
+impl Profiler {
+    pub(super) fn new(context: &crate::context::Context) -> Self {
+        let extensions = context.supported_extensions();
+        let disjoint_supported = extensions.contains("EXT_disjoint_timer_query_webgl2")
+            || extensions.contains("GL_EXT_disjoint_timer_query");
+        Self {
+            enabled: false,
+            timer_queries_supported: disjoint_supported
+                || extensions.contains("GL_ARB_timer_query")
+                || extensions.contains("GL_EXT_timer_query"),
+            disjoint_supported,
+            active: Vec::new(),
+            pending: VecDeque::new(),
+            last_program: None,
+            last_render_states: None,
+        }
+    }
+
+    pub(super) fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub(super) fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    pub(super) fn is_gpu_timing_supported(&self) -> bool {
+        self.timer_queries_supported
+    }
+
+    pub(super) fn begin_scope(&mut self, context: &crate::context::Context, name: &str) {
+        // Timer queries cannot be nested, so only the outermost scope is timed on the GPU
+        let query = if self.timer_queries_supported && self.active.is_empty() {
+            unsafe {
+                context.create_query().ok().map(|query| {
+                    context.begin_query(crate::context::TIME_ELAPSED, query);
+                    query
+                })
+            }
+        } else {
+            None
+        };
+        self.active.push(ActiveScope {
+            result: ProfileResult {
+                name: name.to_string(),
+                depth: self.active.len(),
+                gpu_time: None,
+                cpu_time: Duration::ZERO,
+                draw_calls: 0,
+                triangles: 0,
+                state_changes: 0,
+                queued_state_changes: 0,
+            },
+            start: instant::Instant::now(),
+            query,
+        });
+    }
+
+    pub(super) fn end_scope(&mut self, context: &crate::context::Context) {
+        let mut scope = self
+            .active
+            .pop()
+            .expect("ending a profiling scope which has not begun");
+        scope.result.cpu_time = scope.start.elapsed();
+        if scope.query.is_some() {
+            unsafe {
+                context.end_query(crate::context::TIME_ELAPSED);
+            }
+        }
+        self.pending.push_back(PendingScope {
+            result: scope.result,
+            query: scope.query,
+        });
+    }
+
+    pub(super) fn register_draw_call(
+        &mut self,
+        program: crate::context::Program,
+        render_states: RenderStates,
+        triangles: u64,
+    ) {
+        if self.active.is_empty() {
+            return;
+        }
+        let state_change =
+            self.last_program != Some(program) || self.last_render_states != Some(render_states);
+        self.last_program = Some(program);
+        self.last_render_states = Some(render_states);
+        for scope in self.active.iter_mut() {
+            scope.result.draw_calls += 1;
+            scope.result.triangles += triangles;
+            if state_change {
+                scope.result.state_changes += 1;
+            }
+        }
+    }
+
+    pub(super) fn register_queued_state_changes(&mut self, state_changes: u32) {
+        for scope in self.active.iter_mut() {
+            scope.result.queued_state_changes += state_changes;
+        }
+    }
+
+    pub(super) fn results(&mut self, context: &crate::context::Context) -> Vec<ProfileResult> {
+        let disjoint =
+            self.disjoint_supported && unsafe { context.get_parameter_i32(GPU_DISJOINT) } != 0;
+        let mut results = Vec::new();
+        while let Some(scope) = self.pending.front() {
+            if let Some(query) = scope.query {
+                let available = unsafe {
+                    context.get_query_parameter_u32(query, crate::context::QUERY_RESULT_AVAILABLE)
+                } != 0;
+                if !available {
+                    break;
+                }
+            }
+            let mut scope = self.pending.pop_front().unwrap();
+            if let Some(query) = scope.query {
+                unsafe {
+                    let nanoseconds =
+                        context.get_query_parameter_u32(query, crate::context::QUERY_RESULT);
+                    if !disjoint {
+                        scope.result.gpu_time = Some(Duration::from_nanos(nanoseconds as u64));
+                    }
+                    context.delete_query(query);
+                }
+            }
+            results.push(scope.result);
+        }
+        results
+    }
+}
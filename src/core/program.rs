@@ -15,6 +15,7 @@ pub struct Program {
     textures: RwLock<HashMap<String, u32>>,
     uniforms: HashMap<String, crate::context::UniformLocation>,
     uniform_blocks: RwLock<HashMap<String, (u32, u32)>>,
+    transform_feedback_varyings: Vec<String>,
 }
 
 impl Program {
@@ -25,6 +26,35 @@ impl Program {
         context: &Context,
         vertex_shader_source: &str,
         fragment_shader_source: &str,
+    ) -> Result<Self, CoreError> {
+        Self::new(context, vertex_shader_source, fragment_shader_source, &[])
+    }
+
+    ///
+    /// Creates a new shader program from the given vertex and fragment glsl shader source where the given vertex shader outputs (varyings) are captured using transform feedback,
+    /// see [Program::draw_arrays_transform_feedback].
+    /// Each varying must be declared as an `out` variable in the vertex shader and is captured into its own [VertexBuffer].
+    /// The fragment shader is never run when capturing, but is still required, so a fragment shader which does nothing is sufficient.
+    ///
+    pub fn from_source_with_transform_feedback(
+        context: &Context,
+        vertex_shader_source: &str,
+        fragment_shader_source: &str,
+        varyings: &[&str],
+    ) -> Result<Self, CoreError> {
+        Self::new(
+            context,
+            vertex_shader_source,
+            fragment_shader_source,
+            varyings,
+        )
+    }
+
+    fn new(
+        context: &Context,
+        vertex_shader_source: &str,
+        fragment_shader_source: &str,
+        varyings: &[&str],
     ) -> Result<Self, CoreError> {
         unsafe {
             let vert_shader = context
@@ -41,11 +71,27 @@ impl Program {
                         precision highp int;
                         precision highp sampler2DArray;
                         precision highp sampler3D;
+                        precision highp isampler2D;
+                        precision highp usampler2D;
+                        precision highp isampler2DArray;
+                        precision highp usampler2DArray;
+                        precision highp isampler3D;
+                        precision highp usampler3D;
+                        precision highp isamplerCube;
+                        precision highp usamplerCube;
                     #else
                         precision mediump float;
                         precision mediump int;
                         precision mediump sampler2DArray;
                         precision mediump sampler3D;
+                        precision mediump isampler2D;
+                        precision mediump usampler2D;
+                        precision mediump isampler2DArray;
+                        precision mediump usampler2DArray;
+                        precision mediump isampler3D;
+                        precision mediump usampler3D;
+                        precision mediump isamplerCube;
+                        precision mediump usamplerCube;
                     #endif\n"
             } else {
                 "#version 330 core\n"
@@ -61,6 +107,9 @@ impl Program {
             let id = context.create_program().expect("Failed creating program");
             context.attach_shader(id, vert_shader);
             context.attach_shader(id, frag_shader);
+            if varyings.len() > 0 {
+                context.transform_feedback_varyings(id, varyings, crate::context::SEPARATE_ATTRIBS);
+            }
             context.link_program(id);
 
             if !context.get_program_link_status(id) {
@@ -135,6 +184,7 @@ impl Program {
                 uniforms,
                 uniform_blocks: RwLock::new(HashMap::new()),
                 textures: RwLock::new(HashMap::new()),
+                transform_feedback_varyings: varyings.iter().map(|v| v.to_string()).collect(),
             })
         }
     }
@@ -151,7 +201,6 @@ impl Program {
     pub fn use_uniform<T: UniformDataType>(&self, name: &str, data: T) {
         let location = self.get_uniform_location(name);
         T::send_uniform(&self.context, location, &[data]);
-        self.unuse_program();
     }
 
     ///
@@ -175,7 +224,6 @@ impl Program {
     pub fn use_uniform_array<T: UniformDataType>(&self, name: &str, data: &[T]) {
         let location = self.get_uniform_location(name);
         T::send_uniform(&self.context, location, data);
-        self.unuse_program();
     }
 
     fn get_uniform_location(&self, name: &str) -> &crate::context::UniformLocation {
@@ -188,7 +236,7 @@ impl Program {
 
     ///
     /// Use the given [Texture2D] in this shader program and associate it with the given named variable.
-    /// The glsl shader variable must be of type `uniform sampler2D` and can only be accessed in the fragment shader.
+    /// The glsl shader variable must be of type `uniform sampler2D`, or `uniform usampler2D` and `uniform isampler2D` if the texture contains unsigned and signed integers respectively (see [TextureDataType]), and can only be accessed in the fragment shader.
     ///
     /// # Panic
     /// Will panic if the texture is not defined in the shader code or not used.
@@ -214,7 +262,7 @@ impl Program {
 
     ///
     /// Use the given texture array in this shader program and associate it with the given named variable.
-    /// The glsl shader variable must be of type `uniform sampler2DArray` and can only be accessed in the fragment shader.
+    /// The glsl shader variable must be of type `uniform sampler2DArray`, or `uniform usampler2DArray` and `uniform isampler2DArray` if the texture contains unsigned and signed integers respectively (see [TextureDataType]), and can only be accessed in the fragment shader.
     ///
     /// # Panic
     /// Will panic if the texture is not defined in the shader code or not used.
@@ -240,7 +288,7 @@ impl Program {
 
     ///
     /// Use the given texture cube map in this shader program and associate it with the given named variable.
-    /// The glsl shader variable must be of type `uniform samplerCube` and can only be accessed in the fragment shader.
+    /// The glsl shader variable must be of type `uniform samplerCube`, or `uniform usamplerCube` and `uniform isamplerCube` if the texture contains unsigned and signed integers respectively (see [TextureDataType]), and can only be accessed in the fragment shader.
     ///
     /// # Panic
     /// Will panic if the texture is not defined in the shader code or not used.
@@ -266,7 +314,7 @@ impl Program {
 
     ///
     /// Use the given 3D texture in this shader program and associate it with the given named variable.
-    /// The glsl shader variable must be of type `uniform sampler3D` and can only be accessed in the fragment shader.
+    /// The glsl shader variable must be of type `uniform sampler3D`, or `uniform usampler3D` and `uniform isampler3D` if the texture contains unsigned and signed integers respectively (see [TextureDataType]), and can only be accessed in the fragment shader.
     ///
     /// # Panic
     /// Will panic if the texture is not defined in the shader code or not used.
@@ -285,10 +333,7 @@ impl Program {
         };
         let index = self.textures.read().unwrap().get(name).unwrap().clone();
         self.use_uniform(name, index as i32);
-        unsafe {
-            self.context
-                .active_texture(crate::context::TEXTURE0 + index);
-        }
+        self.context.set_active_texture(index);
         index
     }
 
@@ -334,6 +379,22 @@ impl Program {
     /// In the latter case the variable is removed by the shader compiler.
     ///
     pub fn use_vertex_attribute(&self, name: &str, buffer: &VertexBuffer) {
+        self.use_vertex_buffer(name, buffer, 0);
+    }
+
+    ///
+    /// Same as [Program::use_instance_attribute] except that the data is taken from a [VertexBuffer],
+    /// which makes it possible to render instances using data computed on the GPU, see [Program::draw_arrays_transform_feedback].
+    ///
+    /// # Panic
+    /// Will panic if the attribute is not defined in the shader code or not used.
+    /// In the latter case the variable is removed by the shader compiler.
+    ///
+    pub fn use_vertex_buffer_as_instance_attribute(&self, name: &str, buffer: &VertexBuffer) {
+        self.use_vertex_buffer(name, buffer, 1);
+    }
+
+    fn use_vertex_buffer(&self, name: &str, buffer: &VertexBuffer, divisor: u32) {
         if buffer.count() > 0 {
             buffer.bind();
             let loc = self.location(name);
@@ -344,14 +405,13 @@ impl Program {
                     loc,
                     buffer.data_size() as i32,
                     buffer.data_type(),
-                    false,
-                    0,
-                    0,
+                    buffer.normalized(),
+                    buffer.stride() as i32,
+                    buffer.offset() as i32,
                 );
-                self.context.vertex_attrib_divisor(loc, 0);
+                self.context.vertex_attrib_divisor(loc, divisor);
                 self.context.bind_buffer(crate::context::ARRAY_BUFFER, None);
             }
-            self.unuse_program();
         }
     }
 
@@ -382,7 +442,6 @@ impl Program {
                 self.context.vertex_attrib_divisor(loc, 1);
                 self.context.bind_buffer(crate::context::ARRAY_BUFFER, None);
             }
-            self.unuse_program();
         }
     }
 
@@ -393,8 +452,10 @@ impl Program {
     /// If you want to use an [ElementBuffer], see [Program::draw_elements].
     ///
     pub fn draw_arrays(&self, render_states: RenderStates, viewport: Viewport, count: u32) {
+        self.register_draw_call(render_states, count as u64 / 3);
         self.context.set_viewport(viewport);
         self.context.set_render_states(render_states);
+        self.use_clip_planes();
         self.use_program();
         unsafe {
             self.context
@@ -404,7 +465,6 @@ impl Program {
             }
             self.context.bind_vertex_array(None);
         }
-        self.unuse_program();
 
         #[cfg(debug_assertions)]
         self.context
@@ -423,8 +483,10 @@ impl Program {
         count: u32,
         instance_count: u32,
     ) {
+        self.register_draw_call(render_states, count as u64 / 3 * instance_count as u64);
         self.context.set_viewport(viewport);
         self.context.set_render_states(render_states);
+        self.use_clip_planes();
         self.use_program();
         unsafe {
             self.context.draw_arrays_instanced(
@@ -440,7 +502,6 @@ impl Program {
             }
             self.context.bind_vertex_array(None);
         }
-        self.unuse_program();
 
         #[cfg(debug_assertions)]
         self.context
@@ -481,8 +542,10 @@ impl Program {
         first: u32,
         count: u32,
     ) {
+        self.register_draw_call(render_states, count as u64 / 3);
         self.context.set_viewport(viewport);
         self.context.set_render_states(render_states);
+        self.use_clip_planes();
         self.use_program();
         element_buffer.bind();
         unsafe {
@@ -500,7 +563,6 @@ impl Program {
             }
             self.context.bind_vertex_array(None);
         }
-        self.unuse_program();
 
         #[cfg(debug_assertions)]
         self.context
@@ -542,8 +604,10 @@ impl Program {
         count: u32,
         instance_count: u32,
     ) {
+        self.register_draw_call(render_states, count as u64 / 3 * instance_count as u64);
         self.context.set_viewport(viewport);
         self.context.set_render_states(render_states);
+        self.use_clip_planes();
         self.use_program();
         element_buffer.bind();
         unsafe {
@@ -561,7 +625,6 @@ impl Program {
             }
             self.context.bind_vertex_array(None);
         }
-        self.unuse_program();
 
         #[cfg(debug_assertions)]
         self.context
@@ -569,6 +632,69 @@ impl Program {
             .expect("Unexpected rendering error occured")
     }
 
+    ///
+    /// Runs the vertex shader on `count` vertices and captures the varyings given when creating the program (see [Program::from_source_with_transform_feedback])
+    /// into the given vertex buffers, one buffer for each varying in the same order. Nothing is rasterized, so the fragment shader is not run.
+    /// Requires that all attributes and uniforms have been defined using the use_attribute and use_uniform methods.
+    /// This makes it possible to do general computations on the GPU, for example simulating particles or deforming meshes, without transfering data to and from the CPU.
+    ///
+    /// Each output buffer needs room for `count` values of the same type as the varying, for example by calling [VertexBuffer::reserve] with `Vec3` if the varying is a `vec3`.
+    /// Afterwards, the buffers contain `count` values and can be used as input to another program or read back using [VertexBuffer::read].
+    /// An output buffer cannot be used as input in the same call.
+    ///
+    /// # Panic
+    /// Will panic if the number of output buffers is not the same as the number of varyings or if an output buffer does not have room for `count` values.
+    ///
+    pub fn draw_arrays_transform_feedback(&self, count: u32, outputs: &mut [&mut VertexBuffer]) {
+        if outputs.len() != self.transform_feedback_varyings.len() {
+            panic!(
+                "the program captures {} varyings ({}), but {} output buffers are given",
+                self.transform_feedback_varyings.len(),
+                self.transform_feedback_varyings.join(", "),
+                outputs.len()
+            );
+        }
+        self.register_draw_call(RenderStates::default(), 0);
+        self.use_clip_planes();
+        self.use_program();
+        unsafe {
+            self.context.bind_vertex_array(Some(self.context.vao));
+            for (index, output) in outputs.iter_mut().enumerate() {
+                output.bind_transform_feedback(index as u32, count);
+            }
+            self.context.enable(crate::context::RASTERIZER_DISCARD);
+            self.context
+                .begin_transform_feedback(crate::context::POINTS);
+            self.context
+                .draw_arrays(crate::context::POINTS, 0, count as i32);
+            self.context.end_transform_feedback();
+            self.context.disable(crate::context::RASTERIZER_DISCARD);
+            for index in 0..outputs.len() {
+                self.context.bind_buffer_base(
+                    crate::context::TRANSFORM_FEEDBACK_BUFFER,
+                    index as u32,
+                    None,
+                );
+            }
+            for location in self.attributes.values() {
+                self.context.disable_vertex_attrib_array(*location);
+            }
+            self.context.bind_vertex_array(None);
+        }
+
+        #[cfg(debug_assertions)]
+        self.context
+            .error_check()
+            .expect("Unexpected transform feedback error occured")
+    }
+
+    ///
+    /// Returns the names of the varyings captured using transform feedback, see [Program::from_source_with_transform_feedback].
+    ///
+    pub fn transform_feedback_varyings(&self) -> &[String] {
+        &self.transform_feedback_varyings
+    }
+
     ///
     /// Returns true if this program uses the uniform with the given name.
     ///
@@ -583,6 +709,30 @@ impl Program {
         self.attributes.contains_key(name)
     }
 
+    fn register_draw_call(&self, render_states: RenderStates, triangles: u64) {
+        self.context.profiler.write().unwrap().register_draw_call(
+            self.id,
+            render_states,
+            triangles,
+        );
+    }
+
+    fn use_clip_planes(&self) {
+        if self.requires_uniform("clipPlaneCount") {
+            let clip_planes = self.context.clip_planes.read().unwrap();
+            self.use_uniform("clipPlaneCount", clip_planes.len() as i32);
+            if clip_planes.len() > 0 {
+                self.use_uniform_array(
+                    "clipPlanes",
+                    &clip_planes
+                        .iter()
+                        .map(|p| Vec4::from(*p))
+                        .collect::<Vec<_>>(),
+                );
+            }
+        }
+    }
+
     fn location(&self, name: &str) -> u32 {
         self.use_program();
         *self.attributes.get(name).expect(&format!(
@@ -592,22 +742,12 @@ impl Program {
     }
 
     fn use_program(&self) {
-        unsafe {
-            self.context.use_program(Some(self.id));
-        }
-    }
-
-    fn unuse_program(&self) {
-        unsafe {
-            self.context.use_program(None);
-        }
+        self.context.set_program(Some(self.id));
     }
 }
 
 impl Drop for Program {
     fn drop(&mut self) {
-        unsafe {
-            self.context.delete_program(self.id);
-        }
+        self.context.destroy_program(self.id);
     }
 }
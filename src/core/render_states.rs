@@ -5,7 +5,7 @@
 ///
 /// A set of render specific states that has to be specified at each render call.
 ///
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub struct RenderStates {
     ///
     /// Defines which channels (red, green, blue, alpha and depth) to write to in a render call.
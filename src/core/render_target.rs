@@ -77,7 +77,13 @@ impl<'a> RenderTarget<'a> {
     pub fn clear_partially(&self, scissor_box: ScissorBox, clear_state: ClearState) -> &Self {
         self.context.set_scissor(scissor_box);
         self.bind(crate::context::DRAW_FRAMEBUFFER);
-        clear_state.apply(&self.context);
+        if let Some((data_type, draw_buffer_count)) =
+            self.color.as_ref().and_then(|color| color.integer_format())
+        {
+            clear_state.apply_integer(&self.context, data_type, draw_buffer_count);
+        } else {
+            clear_state.apply(&self.context);
+        }
         self
     }
 
@@ -123,6 +129,9 @@ impl<'a> RenderTarget<'a> {
         }
         self.bind(crate::context::DRAW_FRAMEBUFFER);
         self.bind(crate::context::READ_FRAMEBUFFER);
+        if is_integer_data_type::<T>() {
+            return self.read_integer_color_partially(scissor_box);
+        }
         let mut data_size = std::mem::size_of::<T>();
         // On web, the format needs to be RGBA if the data type is byte.
         if data_size / T::size() as usize == 1 {
@@ -150,6 +159,51 @@ impl<'a> RenderTarget<'a> {
         pixels
     }
 
+    // Integer data can only be read as four channels of 32 bit integers on all platforms, so the data is converted to the requested format afterwards.
+    fn read_integer_color_partially<T: TextureDataType>(&self, scissor_box: ScissorBox) -> Vec<T> {
+        let data_type = if T::data_type() == crate::context::SHORT
+            || T::data_type() == crate::context::INT
+            || T::data_type() == crate::context::BYTE
+        {
+            crate::context::INT
+        } else {
+            crate::context::UNSIGNED_INT
+        };
+        let pixel_count = scissor_box.width as usize * scissor_box.height as usize;
+        let mut bytes = vec![0u8; pixel_count * 16];
+        unsafe {
+            self.context.read_pixels(
+                scissor_box.x as i32,
+                scissor_box.y as i32,
+                scissor_box.width as i32,
+                scissor_box.height as i32,
+                crate::context::RGBA_INTEGER,
+                data_type,
+                crate::context::PixelPackData::Slice(&mut bytes),
+            );
+        }
+        let channel_count = T::size() as usize;
+        let channel_size = std::mem::size_of::<T>() / channel_count;
+        let mut converted = Vec::with_capacity(pixel_count * std::mem::size_of::<T>());
+        for pixel in bytes.chunks_exact(16) {
+            for channel in pixel.chunks_exact(4).take(channel_count) {
+                let value = u32::from_ne_bytes([channel[0], channel[1], channel[2], channel[3]]);
+                match channel_size {
+                    1 => converted.push(value as u8),
+                    2 => converted.extend_from_slice(&(value as u16).to_ne_bytes()),
+                    _ => converted.extend_from_slice(&value.to_ne_bytes()),
+                }
+            }
+        }
+        let mut pixels = from_byte_slice(&converted).to_vec();
+        flip_y(
+            &mut pixels,
+            scissor_box.width as usize,
+            scissor_box.height as usize,
+        );
+        pixels
+    }
+
     ///
     /// Returns the depth values in this render target.
     ///
@@ -288,10 +342,23 @@ impl<'a> RenderTarget<'a> {
         }
     }
 
-    fn bind(&self, target: u32) {
+    // Returns whether this render target has a stencil buffer, which is only the case for the screen if it was created with one,
+    // since none of the depth formats used for depth textures contain a stencil buffer.
+    pub(crate) fn has_stencil_buffer(&self) -> bool {
+        if self.id.is_some() {
+            return false;
+        }
+        self.bind(crate::context::DRAW_FRAMEBUFFER);
         unsafe {
-            self.context.bind_framebuffer(target, self.id);
+            let stencil_bits = self.context.get_parameter_i32(crate::context::STENCIL_BITS);
+            // The query is not supported by core profile contexts, in which case the error is cleared and the stencil buffer is not used
+            self.context.get_error();
+            stencil_bits > 0
         }
+    }
+
+    fn bind(&self, target: u32) {
+        self.context.set_framebuffer(target, self.id);
         if let Some(ref color) = self.color {
             color.bind(&self.context);
         }
@@ -303,17 +370,15 @@ impl<'a> RenderTarget<'a> {
 
 impl Drop for RenderTarget<'_> {
     fn drop(&mut self) {
-        unsafe {
-            if let Some(id) = self.id {
-                self.context.delete_framebuffer(id);
-            }
+        if let Some(id) = self.id {
+            self.context.destroy_framebuffer(id);
         }
     }
 }
 
 fn size_with_mip(size: u32, mip: Option<u32>) -> u32 {
     if let Some(mip) = mip {
-        size / 2u32.pow(mip)
+        size.checked_shr(mip).unwrap_or(0).max(1)
     } else {
         size
     }
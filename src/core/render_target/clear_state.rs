@@ -3,6 +3,7 @@ use crate::core::*;
 ///
 /// Defines which channels (red, green, blue, alpha and depth) to clear when starting to write to a [RenderTarget].
 /// If `None` then the channel is not cleared and if `Some(value)` the channel is cleared to that value (the value must be between 0 and 1).
+/// When clearing a color texture with an integer format (see [TextureDataType]), the color values are instead converted to integers, for example a red value of `5.0` clears the red channel to `5`.
 ///
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct ClearState {
@@ -106,6 +107,57 @@ impl ClearState {
             });
         }
     }
+
+    // Integer color buffers cannot be cleared using the clear color, so the color values are converted to integers and each draw buffer is cleared separately.
+    pub(in crate::core) fn apply_integer(
+        &self,
+        context: &Context,
+        data_type: u32,
+        draw_buffer_count: u32,
+    ) {
+        context.set_write_mask(WriteMask {
+            red: self.red.is_some(),
+            green: self.green.is_some(),
+            blue: self.blue.is_some(),
+            alpha: self.alpha.is_some(),
+            depth: self.depth.is_some(),
+        });
+        unsafe {
+            if self.red.is_some()
+                || self.green.is_some()
+                || self.blue.is_some()
+                || self.alpha.is_some()
+            {
+                let color = [
+                    self.red.unwrap_or(0.0),
+                    self.green.unwrap_or(0.0),
+                    self.blue.unwrap_or(0.0),
+                    self.alpha.unwrap_or(1.0),
+                ];
+                for draw_buffer in 0..draw_buffer_count {
+                    if data_type == crate::context::UNSIGNED_SHORT
+                        || data_type == crate::context::UNSIGNED_INT
+                    {
+                        context.clear_buffer_u32_slice(
+                            crate::context::COLOR,
+                            draw_buffer,
+                            &color.map(|c| c as u32),
+                        );
+                    } else {
+                        context.clear_buffer_i32_slice(
+                            crate::context::COLOR,
+                            draw_buffer,
+                            &color.map(|c| c as i32),
+                        );
+                    }
+                }
+            }
+            if let Some(depth) = self.depth {
+                context.clear_depth_f32(depth);
+                context.clear(crate::context::DEPTH_BUFFER_BIT);
+            }
+        }
+    }
 }
 
 impl Default for ClearState {
@@ -175,6 +175,22 @@ impl<'a> ColorTarget<'a> {
         RenderTarget::new_color(self.clone())
     }
 
+    // Returns the data type of the texture and the number of draw buffers if the texture contains integers.
+    pub(super) fn integer_format(&self) -> Option<(u32, u32)> {
+        let (data_type, draw_buffer_count) = match self.target {
+            CT::Texture2D { texture, .. } => (texture.data_type(), 1),
+            CT::Texture2DArray {
+                texture, layers, ..
+            } => (texture.data_type(), layers.len() as u32),
+            CT::TextureCubeMap { texture, .. } => (texture.data_type(), 1),
+        };
+        if is_integer_type(data_type) {
+            Some((data_type, draw_buffer_count))
+        } else {
+            None
+        }
+    }
+
     pub(super) fn generate_mip_maps(&self) {
         match self.target {
             CT::Texture2D { texture, mip_level } => {
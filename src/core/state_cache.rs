@@ -0,0 +1,36 @@
+use crate::core::*;
+use std::collections::HashMap;
+
+///
+/// A shadow copy of the graphics state set through [Context], used to skip state changes which would have no effect.
+/// `None` means that the state is unknown, ie. it has never been set or it has been invalidated.
+///
+#[derive(Default)]
+pub(super) struct StateCache {
+    pub viewport: Option<Viewport>,
+    pub scissor_box: Option<ScissorBox>,
+    pub cull: Option<Cull>,
+    pub write_mask: Option<WriteMask>,
+    pub depth_test_enabled: Option<bool>,
+    pub depth_test: Option<DepthTest>,
+    pub blend: Option<Blend>,
+    pub program: Option<Option<crate::context::Program>>,
+    pub active_texture: Option<u32>,
+    pub textures: HashMap<(u32, u32), crate::context::Texture>,
+    pub draw_framebuffer: Option<Option<crate::context::Framebuffer>>,
+    pub read_framebuffer: Option<Option<crate::context::Framebuffer>>,
+}
+
+impl StateCache {
+    ///
+    /// Updates the cached value to the new value and returns true if the new value is different from the cached value.
+    ///
+    pub fn update<T: PartialEq>(cached: &mut Option<T>, value: T) -> bool {
+        if cached.as_ref() == Some(&value) {
+            false
+        } else {
+            *cached = Some(value);
+            true
+        }
+    }
+}
@@ -29,14 +29,28 @@ mod depth_target_texture_cube_map;
 #[doc(inline)]
 pub use depth_target_texture_cube_map::*;
 
+mod compressed_texture;
+#[doc(inline)]
+pub use compressed_texture::*;
+
 use data_type::*;
 pub use three_d_asset::texture::{
     Interpolation, Texture2D as CpuTexture, Texture3D as CpuTexture3D, TextureData, Wrapping,
 };
 
+///
 /// The basic data type used for each channel of each pixel in a texture.
+/// The `u8`, `f16` and `f32` data types are accessed as floating point values in the shader (using for example `sampler2D`),
+/// where `u8` is normalized to the range `[0..1]`.
+/// The `u16`, `u32`, `i16` and `i32` data types are accessed as integers in the shader (using for example `usampler2D` and `isampler2D`)
+/// and are therefore never interpolated, ie. the texture always uses nearest neighbour filtering without mip maps.
+///
 pub trait TextureDataType: DataType {}
 impl TextureDataType for u8 {}
+impl TextureDataType for u16 {}
+impl TextureDataType for u32 {}
+impl TextureDataType for i16 {}
+impl TextureDataType for i32 {}
 impl TextureDataType for f16 {}
 impl TextureDataType for f32 {}
 
@@ -129,6 +143,19 @@ fn set_parameters(
     }
 }
 
+// Integer textures cannot be filtered, so nearest neighbour filtering without mip maps is used for those regardless of the given filters.
+fn filters_from_data_type<T: TextureDataType>(
+    min_filter: Interpolation,
+    mag_filter: Interpolation,
+    mip_map_filter: Option<Interpolation>,
+) -> (Interpolation, Interpolation, Option<Interpolation>) {
+    if is_integer_data_type::<T>() {
+        (Interpolation::Nearest, Interpolation::Nearest, None)
+    } else {
+        (min_filter, mag_filter, mip_map_filter)
+    }
+}
+
 fn calculate_number_of_mip_maps(
     mip_map_filter: Option<Interpolation>,
     width: u32,
@@ -169,6 +196,35 @@ fn interpolation_from(interpolation: Interpolation) -> i32 {
     }) as i32
 }
 
+fn mip_level_size(width: u32, height: u32, mip_level: u32, number_of_mip_maps: u32) -> (u32, u32) {
+    if mip_level >= number_of_mip_maps {
+        panic!(
+            "cannot access mip level {}, since there are only {} mip levels in the texture",
+            mip_level, number_of_mip_maps
+        )
+    }
+    ((width >> mip_level).max(1), (height >> mip_level).max(1))
+}
+
+fn check_region(offset: (u32, u32, u32), size: (u32, u32, u32), texture_size: (u32, u32, u32)) {
+    // Overflowing regions are also outside the texture
+    let inside = |offset: u32, size: u32, texture_size: u32| {
+        offset
+            .checked_add(size)
+            .map(|end| end <= texture_size)
+            .unwrap_or(false)
+    };
+    if !inside(offset.0, size.0, texture_size.0)
+        || !inside(offset.1, size.1, texture_size.1)
+        || !inside(offset.2, size.2, texture_size.2)
+    {
+        panic!(
+            "the region with offset {:?} and size {:?} is outside the texture with size {:?}",
+            offset, size, texture_size
+        )
+    }
+}
+
 fn check_data_length<T: TextureDataType>(
     width: u32,
     height: u32,
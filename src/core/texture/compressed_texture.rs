@@ -0,0 +1,253 @@
+use crate::core::texture::*;
+
+// Not defined by the low-level context since they are only part of the texture compression extensions.
+const COMPRESSED_RGB_S3TC_DXT1: u32 = 0x83F0;
+const COMPRESSED_RGBA_S3TC_DXT1: u32 = 0x83F1;
+const COMPRESSED_RGBA_S3TC_DXT3: u32 = 0x83F2;
+const COMPRESSED_RGBA_S3TC_DXT5: u32 = 0x83F3;
+const COMPRESSED_RED_RGTC1: u32 = 0x8DBB;
+const COMPRESSED_RG_RGTC2: u32 = 0x8DBD;
+const COMPRESSED_RGBA_BPTC_UNORM: u32 = 0x8E8C;
+const COMPRESSED_RGB_BPTC_UNSIGNED_FLOAT: u32 = 0x8E8F;
+const COMPRESSED_RGB8_ETC2: u32 = 0x9274;
+const COMPRESSED_RGBA8_ETC2_EAC: u32 = 0x9278;
+const COMPRESSED_RGBA_ASTC_4X4: u32 = 0x93B0;
+
+const KTX2_IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+
+///
+/// A block-compressed texture format, where each block of 4x4 pixels is compressed into a fixed number of bytes.
+/// Compressed textures use 4 to 8 times less GPU memory than uncompressed textures and are typically also faster to sample.
+/// Which formats are available depends on the device, so use [CompressedTextureFormat::is_supported] to choose a format,
+/// typically one of the BC formats on desktop and ETC2 or ASTC on mobile.
+///
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum CompressedTextureFormat {
+    /// BC1 (also known as DXT1) with RGB channels, 8 bytes per block.
+    Bc1Rgb,
+    /// BC1 (also known as DXT1) with RGB channels and a 1 bit alpha channel, 8 bytes per block.
+    Bc1Rgba,
+    /// BC2 (also known as DXT3) with RGBA channels where the alpha channel is stored explicitly with 4 bits, 16 bytes per block.
+    Bc2,
+    /// BC3 (also known as DXT5) with RGBA channels, 16 bytes per block.
+    Bc3,
+    /// BC4 with a single red channel, 8 bytes per block.
+    Bc4,
+    /// BC5 with red and green channels, typically used for normal maps, 16 bytes per block.
+    Bc5,
+    /// BC6H with unsigned floating point RGB channels, typically used for HDR images, 16 bytes per block.
+    Bc6h,
+    /// BC7 with RGBA channels, 16 bytes per block.
+    Bc7,
+    /// ETC2 with RGB channels, 8 bytes per block.
+    Etc2Rgb,
+    /// ETC2 with RGBA channels, 16 bytes per block.
+    Etc2Rgba,
+    /// ASTC with RGBA channels and a block size of 4x4 pixels, 16 bytes per block.
+    Astc4x4,
+}
+
+impl CompressedTextureFormat {
+    ///
+    /// Returns whether this format can be used on the device the given context belongs to.
+    ///
+    pub fn is_supported(&self, context: &Context) -> bool {
+        let extensions = context.supported_extensions();
+        let has = |names: &[&str]| names.iter().any(|name| extensions.contains(*name));
+        match self {
+            Self::Bc1Rgb | Self::Bc1Rgba | Self::Bc2 | Self::Bc3 => has(&[
+                "GL_EXT_texture_compression_s3tc",
+                "WEBGL_compressed_texture_s3tc",
+            ]),
+            // RGTC is part of core desktop OpenGL
+            Self::Bc4 | Self::Bc5 => {
+                !context.version().is_embedded
+                    || has(&[
+                        "GL_EXT_texture_compression_rgtc",
+                        "EXT_texture_compression_rgtc",
+                    ])
+            }
+            Self::Bc6h | Self::Bc7 => has(&[
+                "GL_ARB_texture_compression_bptc",
+                "GL_EXT_texture_compression_bptc",
+                "EXT_texture_compression_bptc",
+            ]),
+            // ETC2 is part of core OpenGL ES 3, but not WebGL 2
+            Self::Etc2Rgb | Self::Etc2Rgba => {
+                (context.version().is_embedded && cfg!(not(target_arch = "wasm32")))
+                    || has(&["GL_ARB_ES3_compatibility", "WEBGL_compressed_texture_etc"])
+            }
+            Self::Astc4x4 => has(&[
+                "GL_KHR_texture_compression_astc_ldr",
+                "WEBGL_compressed_texture_astc",
+            ]),
+        }
+    }
+
+    ///
+    /// Returns the number of bytes of compressed data for an image with the given width and height in pixels.
+    ///
+    pub fn data_byte_size(&self, width: u32, height: u32) -> usize {
+        let blocks = ((width as usize + 3) / 4) * ((height as usize + 3) / 4);
+        blocks * self.block_byte_size()
+    }
+
+    fn block_byte_size(&self) -> usize {
+        match self {
+            Self::Bc1Rgb | Self::Bc1Rgba | Self::Bc4 | Self::Etc2Rgb => 8,
+            _ => 16,
+        }
+    }
+
+    pub(in crate::core) fn internal_format(&self) -> u32 {
+        match self {
+            Self::Bc1Rgb => COMPRESSED_RGB_S3TC_DXT1,
+            Self::Bc1Rgba => COMPRESSED_RGBA_S3TC_DXT1,
+            Self::Bc2 => COMPRESSED_RGBA_S3TC_DXT3,
+            Self::Bc3 => COMPRESSED_RGBA_S3TC_DXT5,
+            Self::Bc4 => COMPRESSED_RED_RGTC1,
+            Self::Bc5 => COMPRESSED_RG_RGTC2,
+            Self::Bc6h => COMPRESSED_RGB_BPTC_UNSIGNED_FLOAT,
+            Self::Bc7 => COMPRESSED_RGBA_BPTC_UNORM,
+            Self::Etc2Rgb => COMPRESSED_RGB8_ETC2,
+            Self::Etc2Rgba => COMPRESSED_RGBA8_ETC2_EAC,
+            Self::Astc4x4 => COMPRESSED_RGBA_ASTC_4X4,
+        }
+    }
+
+    // Maps the Vulkan format used in KTX2 containers to a compressed format.
+    // The sRGB formats map to the same format as the UNORM formats, since the data is the same and the materials convert from sRGB in the shader.
+    fn from_vk_format(vk_format: u32) -> Option<Self> {
+        Some(match vk_format {
+            131 | 132 => Self::Bc1Rgb,
+            133 | 134 => Self::Bc1Rgba,
+            135 | 136 => Self::Bc2,
+            137 | 138 => Self::Bc3,
+            139 => Self::Bc4,
+            141 => Self::Bc5,
+            143 => Self::Bc6h,
+            145 | 146 => Self::Bc7,
+            147 | 148 => Self::Etc2Rgb,
+            151 | 152 => Self::Etc2Rgba,
+            157 | 158 => Self::Astc4x4,
+            _ => return None,
+        })
+    }
+}
+
+///
+/// A compressed texture stored in CPU memory, which can be transferred to the GPU using [Texture2D::new_compressed].
+///
+/// The compressed data is uploaded as is, so in contrast to uncompressed textures, the image is not flipped vertically,
+/// which means that the first row of pixels is at the bottom of the texture.
+/// Compressed images should therefore be created with the origin in the lower left corner, for example using `toktx --lower_left_maps_to_s0t0`.
+///
+#[derive(Clone, Debug)]
+pub struct CpuCompressedTexture {
+    /// The compression format.
+    pub format: CompressedTextureFormat,
+    /// The width of the texture in pixels.
+    pub width: u32,
+    /// The height of the texture in pixels.
+    pub height: u32,
+    /// The compressed data of each mip level starting with the full size image, where each level is half the size of the previous level (rounded down, but at least 1 pixel).
+    /// If more than one level is given, the texture uses these mip levels instead of generating them.
+    pub mip_levels: Vec<Vec<u8>>,
+    /// The way the pixel data is interpolated when the texture is far away.
+    pub min_filter: Interpolation,
+    /// The way the pixel data is interpolated when the texture is close.
+    pub mag_filter: Interpolation,
+    /// Specifies whether mipmaps should be used and how they are interpolated. Only used if more than one mip level is given.
+    pub mip_map_filter: Option<Interpolation>,
+    /// Determines how the texture is sampled outside the [0..1] s coordinate range (the first value of the uv coordinates).
+    pub wrap_s: Wrapping,
+    /// Determines how the texture is sampled outside the [0..1] t coordinate range (the second value of the uv coordinates).
+    pub wrap_t: Wrapping,
+}
+
+impl CpuCompressedTexture {
+    ///
+    /// Parses a [KTX2](https://registry.khronos.org/KTX/specs/2.0/ktxspec.v2.html) container with a 2D texture in one of the [CompressedTextureFormat]s, including all mip levels in the container.
+    /// Supercompressed containers (for example Basis Universal) are not supported.
+    ///
+    /// Both the UNORM and the sRGB variants of the formats are accepted. The data of an sRGB variant is used as is, in the same way as uncompressed color textures,
+    /// since the materials in this crate convert color textures from sRGB to linear color space in the shader.
+    ///
+    pub fn from_ktx2(bytes: &[u8]) -> Result<Self, CoreError> {
+        if bytes.len() < 80 || bytes[..12] != KTX2_IDENTIFIER {
+            Err(CoreError::InvalidKtx2("not a KTX2 container".to_string()))?;
+        }
+        let vk_format = read_u32(bytes, 12)?;
+        let width = read_u32(bytes, 20)?;
+        let height = read_u32(bytes, 24)?;
+        let depth = read_u32(bytes, 28)?;
+        let layer_count = read_u32(bytes, 32)?;
+        let face_count = read_u32(bytes, 36)?;
+        let level_count = read_u32(bytes, 40)?.max(1);
+        if level_count > 32 {
+            Err(CoreError::InvalidKtx2(format!(
+                "{} mip levels is more than the maximum of 32",
+                level_count
+            )))?;
+        }
+        let supercompression = read_u32(bytes, 44)?;
+
+        let format = CompressedTextureFormat::from_vk_format(vk_format).ok_or_else(|| {
+            CoreError::InvalidKtx2(format!("unsupported format (VkFormat {})", vk_format))
+        })?;
+        if depth > 1 || layer_count > 1 || face_count != 1 {
+            Err(CoreError::InvalidKtx2(
+                "only 2D textures are supported, not 3D textures, texture arrays or cube maps"
+                    .to_string(),
+            ))?;
+        }
+        if supercompression != 0 {
+            Err(CoreError::InvalidKtx2(format!(
+                "supercompression scheme {} is not supported",
+                supercompression
+            )))?;
+        }
+
+        // The level index follows the header and the data format, key/value and supercompression indices
+        let mut mip_levels = Vec::with_capacity(level_count as usize);
+        for level in 0..level_count {
+            let index = 80 + level as usize * 24;
+            let missing =
+                || CoreError::InvalidKtx2(format!("the data of mip level {} is missing", level));
+            // The offset and length are untrusted 64 bit values, which might not fit in the address space
+            let offset = usize::try_from(read_u64(bytes, index)?).map_err(|_| missing())?;
+            let length = usize::try_from(read_u64(bytes, index + 8)?).map_err(|_| missing())?;
+            let end = offset.checked_add(length).ok_or_else(missing)?;
+            let data = bytes.get(offset..end).ok_or_else(missing)?;
+            mip_levels.push(data.to_vec());
+        }
+        Ok(Self {
+            format,
+            width,
+            height,
+            mip_levels,
+            min_filter: Interpolation::Linear,
+            mag_filter: Interpolation::Linear,
+            mip_map_filter: if level_count > 1 {
+                Some(Interpolation::Linear)
+            } else {
+                None
+            },
+            wrap_s: Wrapping::Repeat,
+            wrap_t: Wrapping::Repeat,
+        })
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, CoreError> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| CoreError::InvalidKtx2("unexpected end of data".to_string()))
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Result<u64, CoreError> {
+    Ok(read_u32(bytes, offset)? as u64 | (read_u32(bytes, offset + 4)? as u64) << 32)
+}
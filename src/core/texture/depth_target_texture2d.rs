@@ -97,17 +97,13 @@ impl DepthTargetTexture2D {
     }
 
     pub(in crate::core) fn bind(&self) {
-        unsafe {
-            self.context
-                .bind_texture(crate::context::TEXTURE_2D, Some(self.id));
-        }
+        self.context
+            .set_texture(crate::context::TEXTURE_2D, self.id);
     }
 }
 
 impl Drop for DepthTargetTexture2D {
     fn drop(&mut self) {
-        unsafe {
-            self.context.delete_texture(self.id);
-        }
+        self.context.destroy_texture(self.id);
     }
 }
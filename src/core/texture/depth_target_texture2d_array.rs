@@ -92,17 +92,13 @@ impl DepthTargetTexture2DArray {
     }
 
     pub(in crate::core) fn bind(&self) {
-        unsafe {
-            self.context
-                .bind_texture(crate::context::TEXTURE_2D_ARRAY, Some(self.id));
-        }
+        self.context
+            .set_texture(crate::context::TEXTURE_2D_ARRAY, self.id);
     }
 }
 
 impl Drop for DepthTargetTexture2DArray {
     fn drop(&mut self) {
-        unsafe {
-            self.context.delete_texture(self.id);
-        }
+        self.context.destroy_texture(self.id);
     }
 }
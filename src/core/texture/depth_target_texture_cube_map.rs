@@ -84,17 +84,13 @@ impl DepthTargetTextureCubeMap {
     }
 
     pub(in crate::core) fn bind(&self) {
-        unsafe {
-            self.context
-                .bind_texture(crate::context::TEXTURE_CUBE_MAP, Some(self.id));
-        }
+        self.context
+            .set_texture(crate::context::TEXTURE_CUBE_MAP, self.id);
     }
 }
 
 impl Drop for DepthTargetTextureCubeMap {
     fn drop(&mut self) {
-        unsafe {
-            self.context.delete_texture(self.id);
-        }
+        self.context.destroy_texture(self.id);
     }
 }
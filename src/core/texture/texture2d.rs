@@ -10,6 +10,8 @@ pub struct Texture2D {
     height: u32,
     number_of_mip_maps: u32,
     data_byte_size: usize,
+    data_type: u32,
+    compressed: bool,
 }
 
 impl Texture2D {
@@ -67,6 +69,8 @@ impl Texture2D {
         wrap_s: Wrapping,
         wrap_t: Wrapping,
     ) -> Self {
+        let (min_filter, mag_filter, mip_map_filter) =
+            filters_from_data_type::<T>(min_filter, mag_filter, mip_map_filter);
         let id = generate(context);
         let number_of_mip_maps = calculate_number_of_mip_maps(mip_map_filter, width, height, None);
         let texture = Self {
@@ -76,6 +80,8 @@ impl Texture2D {
             height,
             number_of_mip_maps,
             data_byte_size: std::mem::size_of::<T>(),
+            data_type: T::data_type(),
+            compressed: false,
         };
         texture.bind();
         set_parameters(
@@ -105,14 +111,115 @@ impl Texture2D {
         texture
     }
 
+    ///
+    /// Constructs a new texture from the given compressed texture.
+    /// If the compressed texture contains more than one mip level, those levels are used as mip maps, otherwise no mip maps are used
+    /// since mip maps cannot be generated for compressed textures.
+    /// Note that the compressed data is not flipped vertically, see [CpuCompressedTexture].
+    ///
+    /// # Panic
+    /// Will panic if the compression format is not supported on this device (see [CompressedTextureFormat::is_supported])
+    /// or if the size of the data of a mip level does not correspond to the width, height and format.
+    ///
+    pub fn new_compressed(context: &Context, cpu_texture: &CpuCompressedTexture) -> Self {
+        let format = cpu_texture.format;
+        if !format.is_supported(context) {
+            panic!(
+                "the compressed texture format {:?} is not supported on this device",
+                format
+            );
+        }
+        let max_mip_maps = 32
+            - cpu_texture
+                .width
+                .max(cpu_texture.height)
+                .max(1)
+                .leading_zeros();
+        let number_of_mip_maps = (cpu_texture.mip_levels.len() as u32).clamp(1, max_mip_maps);
+        let texture = Self {
+            context: context.clone(),
+            id: generate(context),
+            width: cpu_texture.width,
+            height: cpu_texture.height,
+            number_of_mip_maps,
+            data_byte_size: 0,
+            data_type: 0,
+            compressed: true,
+        };
+        texture.bind();
+        set_parameters(
+            context,
+            crate::context::TEXTURE_2D,
+            cpu_texture.min_filter,
+            cpu_texture.mag_filter,
+            if number_of_mip_maps == 1 {
+                None
+            } else {
+                cpu_texture.mip_map_filter
+            },
+            cpu_texture.wrap_s,
+            cpu_texture.wrap_t,
+            None,
+        );
+        unsafe {
+            context.tex_parameter_i32(
+                crate::context::TEXTURE_2D,
+                crate::context::TEXTURE_MAX_LEVEL,
+                number_of_mip_maps as i32 - 1,
+            );
+            context.tex_storage_2d(
+                crate::context::TEXTURE_2D,
+                number_of_mip_maps as i32,
+                format.internal_format(),
+                cpu_texture.width as i32,
+                cpu_texture.height as i32,
+            );
+        }
+        for (level, data) in cpu_texture
+            .mip_levels
+            .iter()
+            .take(number_of_mip_maps as usize)
+            .enumerate()
+        {
+            let width = (cpu_texture.width >> level).max(1);
+            let height = (cpu_texture.height >> level).max(1);
+            let expected_bytes = format.data_byte_size(width, height);
+            if data.len() != expected_bytes {
+                panic!(
+                    "invalid size of compressed texture data for mip level {} (got {} bytes but expected {} bytes)",
+                    level,
+                    data.len(),
+                    expected_bytes
+                );
+            }
+            unsafe {
+                context.compressed_tex_sub_image_2d(
+                    crate::context::TEXTURE_2D,
+                    level as i32,
+                    0,
+                    0,
+                    width as i32,
+                    height as i32,
+                    format.internal_format(),
+                    crate::context::CompressedPixelUnpackData::Slice(data),
+                );
+            }
+        }
+        texture
+    }
+
     ///
     /// Fills this texture with the given data.
     ///
     /// # Panic
     /// Will panic if the length of the data does not correspond to the width, height and format specified at construction.
     /// It is therefore necessary to create a new texture if the texture size or format has changed.
+    /// Will also panic if this is a compressed texture.
     ///
     pub fn fill<T: TextureDataType>(&mut self, data: &[T]) {
+        if self.compressed {
+            panic!("cannot fill a compressed texture with uncompressed data");
+        }
         check_data_length::<T>(self.width, self.height, 1, self.data_byte_size, data.len());
         self.bind();
         let mut data = data.to_owned();
@@ -133,6 +240,98 @@ impl Texture2D {
         self.generate_mip_maps();
     }
 
+    ///
+    /// Fills the region of this texture with the given offset and size with the given data, leaving the rest of the texture unchanged.
+    /// The offset `(x, y)` is the position of the top left corner of the region in the same layout as the data given to [Texture2D::fill],
+    /// ie. `y` is the number of rows from the first row of the data.
+    ///
+    /// # Panic
+    /// Will panic if the region is outside the texture or if the length of the data does not correspond to the size of the region and the format specified at construction.
+    /// Will also panic if this is a compressed texture.
+    ///
+    pub fn fill_partially<T: TextureDataType>(
+        &mut self,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        data: &[T],
+    ) {
+        if self.compressed {
+            panic!("cannot fill a compressed texture with uncompressed data");
+        }
+        check_region((x, y, 0), (width, height, 1), (self.width, self.height, 1));
+        check_data_length::<T>(width, height, 1, self.data_byte_size, data.len());
+        self.bind();
+        let mut data = data.to_owned();
+        flip_y(&mut data, width as usize, height as usize);
+        unsafe {
+            self.context.tex_sub_image_2d(
+                crate::context::TEXTURE_2D,
+                0,
+                x as i32,
+                (self.height - y - height) as i32,
+                width as i32,
+                height as i32,
+                format_from_data_type::<T>(),
+                T::data_type(),
+                crate::context::PixelUnpackData::Slice(to_byte_slice(&data)),
+            );
+        }
+        self.generate_mip_maps();
+    }
+
+    ///
+    /// Fills the given mip level of this texture with the given data, where the width and height of the mip level is the width and height of this texture divided by 2 to the power of the mip level.
+    /// In contrast to [Texture2D::fill], mip maps are not generated afterwards, so this can be used to upload pre-filtered mip levels.
+    /// Note however that all mip levels are regenerated if the texture is filled using [Texture2D::fill] or written to using [Texture2D::as_color_target] with `None` as the mip level.
+    ///
+    /// # Panic
+    /// Will panic if the mip level does not exist or if the length of the data does not correspond to the size of the mip level and the format specified at construction.
+    /// Will also panic if this is a compressed texture.
+    ///
+    pub fn fill_mip_level<T: TextureDataType>(&mut self, mip_level: u32, data: &[T]) {
+        if self.compressed {
+            panic!("cannot fill a compressed texture with uncompressed data");
+        }
+        let (width, height) =
+            mip_level_size(self.width, self.height, mip_level, self.number_of_mip_maps);
+        check_data_length::<T>(width, height, 1, self.data_byte_size, data.len());
+        self.bind();
+        let mut data = data.to_owned();
+        flip_y(&mut data, width as usize, height as usize);
+        unsafe {
+            self.context.tex_sub_image_2d(
+                crate::context::TEXTURE_2D,
+                mip_level as i32,
+                0,
+                0,
+                width as i32,
+                height as i32,
+                format_from_data_type::<T>(),
+                T::data_type(),
+                crate::context::PixelUnpackData::Slice(to_byte_slice(&data)),
+            );
+        }
+    }
+
+    ///
+    /// Returns the pixel data of the given mip level of this texture.
+    /// The number of channels per pixel and the data format for each channel is specified by the generic parameter.
+    ///
+    /// **Note:** On web, the data format needs to match the data format of the texture.
+    ///
+    /// # Panic
+    /// Will panic if the mip level does not exist or if this is a compressed texture.
+    ///
+    pub fn read_mip_level<T: TextureDataType>(&self, mip_level: u32) -> Vec<T> {
+        if self.compressed {
+            panic!("cannot read the data of a compressed texture");
+        }
+        mip_level_size(self.width, self.height, mip_level, self.number_of_mip_maps);
+        ColorTarget::new_texture2d(&self.context, self, Some(mip_level)).read()
+    }
+
     ///
     /// Returns a [ColorTarget] which can be used to clear, write to and read from the given mip level of this texture.
     /// Combine this together with a [DepthTarget] with [RenderTarget::new] to be able to write to both a depth and color target at the same time.
@@ -155,8 +354,13 @@ impl Texture2D {
         self.height
     }
 
+    /// The number of mip levels in this texture, including the full size level.
+    pub fn number_of_mip_maps(&self) -> u32 {
+        self.number_of_mip_maps
+    }
+
     pub(crate) fn generate_mip_maps(&self) {
-        if self.number_of_mip_maps > 1 {
+        if self.number_of_mip_maps > 1 && !self.compressed {
             self.bind();
             unsafe {
                 self.context.generate_mipmap(crate::context::TEXTURE_2D);
@@ -175,18 +379,18 @@ impl Texture2D {
             );
         }
     }
+    pub(in crate::core) fn data_type(&self) -> u32 {
+        self.data_type
+    }
+
     pub(in crate::core) fn bind(&self) {
-        unsafe {
-            self.context
-                .bind_texture(crate::context::TEXTURE_2D, Some(self.id));
-        }
+        self.context
+            .set_texture(crate::context::TEXTURE_2D, self.id);
     }
 }
 
 impl Drop for Texture2D {
     fn drop(&mut self) {
-        unsafe {
-            self.context.delete_texture(self.id);
-        }
+        self.context.destroy_texture(self.id);
     }
 }
@@ -14,6 +14,7 @@ pub struct Texture2DArray {
     depth: u32,
     number_of_mip_maps: u32,
     data_byte_size: usize,
+    data_type: u32,
 }
 
 impl Texture2DArray {
@@ -156,6 +157,8 @@ impl Texture2DArray {
         wrap_s: Wrapping,
         wrap_t: Wrapping,
     ) -> Self {
+        let (min_filter, mag_filter, mip_map_filter) =
+            filters_from_data_type::<T>(min_filter, mag_filter, mip_map_filter);
         let id = generate(context);
         let number_of_mip_maps = calculate_number_of_mip_maps(mip_map_filter, width, height, None);
         let texture = Self {
@@ -166,6 +169,7 @@ impl Texture2DArray {
             depth,
             number_of_mip_maps,
             data_byte_size: std::mem::size_of::<T>(),
+            data_type: T::data_type(),
         };
         texture.bind();
         set_parameters(
@@ -217,12 +221,7 @@ impl Texture2DArray {
     /// It is therefore necessary to create a new texture if the texture size or format has changed.
     ///
     pub fn fill_layer<T: TextureDataType>(&mut self, layer: u32, data: &[T]) {
-        if layer >= self.depth {
-            panic!(
-                "cannot fill the layer {} with data, since there are only {} layers in the texture array",
-                layer, self.depth
-            )
-        }
+        self.check_layer(layer);
         check_data_length::<T>(self.width, self.height, 1, self.data_byte_size, data.len());
         self.bind();
         let mut data = (*data).to_owned();
@@ -245,6 +244,96 @@ impl Texture2DArray {
         self.generate_mip_maps();
     }
 
+    ///
+    /// Fills the region with the given offset and size of the given layer in the texture array with the given pixel data, leaving the rest of the layer unchanged.
+    /// The offset `(x, y)` is the position of the top left corner of the region in the same layout as the data given to [Texture2DArray::fill_layer],
+    /// ie. `y` is the number of rows from the first row of the data.
+    ///
+    /// # Panic
+    /// Will panic if the layer does not exist, if the region is outside the texture or if the length of the data does not correspond to the size of the region and the format specified at construction.
+    ///
+    pub fn fill_layer_partially<T: TextureDataType>(
+        &mut self,
+        layer: u32,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        data: &[T],
+    ) {
+        self.check_layer(layer);
+        check_region((x, y, 0), (width, height, 1), (self.width, self.height, 1));
+        check_data_length::<T>(width, height, 1, self.data_byte_size, data.len());
+        self.bind();
+        let mut data = data.to_owned();
+        flip_y(&mut data, width as usize, height as usize);
+        unsafe {
+            self.context.tex_sub_image_3d(
+                crate::context::TEXTURE_2D_ARRAY,
+                0,
+                x as i32,
+                (self.height - y - height) as i32,
+                layer as i32,
+                width as i32,
+                height as i32,
+                1,
+                format_from_data_type::<T>(),
+                T::data_type(),
+                crate::context::PixelUnpackData::Slice(to_byte_slice(&data)),
+            );
+        }
+        self.generate_mip_maps();
+    }
+
+    ///
+    /// Fills the given mip level of the given layer in the texture array with the given pixel data, where the width and height of the mip level is the width and height of this texture divided by 2 to the power of the mip level.
+    /// In contrast to [Texture2DArray::fill_layer], mip maps are not generated afterwards, so this can be used to upload pre-filtered mip levels.
+    /// Note however that all mip levels are regenerated if a layer is filled using [Texture2DArray::fill_layer] or written to using [Texture2DArray::as_color_target] with `None` as the mip level.
+    ///
+    /// # Panic
+    /// Will panic if the layer or mip level does not exist or if the length of the data does not correspond to the size of the mip level and the format specified at construction.
+    ///
+    pub fn fill_mip_level<T: TextureDataType>(&mut self, layer: u32, mip_level: u32, data: &[T]) {
+        self.check_layer(layer);
+        let (width, height) =
+            mip_level_size(self.width, self.height, mip_level, self.number_of_mip_maps);
+        check_data_length::<T>(width, height, 1, self.data_byte_size, data.len());
+        self.bind();
+        let mut data = data.to_owned();
+        flip_y(&mut data, width as usize, height as usize);
+        unsafe {
+            self.context.tex_sub_image_3d(
+                crate::context::TEXTURE_2D_ARRAY,
+                mip_level as i32,
+                0,
+                0,
+                layer as i32,
+                width as i32,
+                height as i32,
+                1,
+                format_from_data_type::<T>(),
+                T::data_type(),
+                crate::context::PixelUnpackData::Slice(to_byte_slice(&data)),
+            );
+        }
+    }
+
+    ///
+    /// Returns the pixel data of the given mip level of the given layer in the texture array.
+    /// The number of channels per pixel and the data format for each channel is specified by the generic parameter.
+    ///
+    /// **Note:** On web, the data format needs to match the data format of the texture.
+    ///
+    /// # Panic
+    /// Will panic if the layer or mip level does not exist.
+    ///
+    pub fn read_mip_level<T: TextureDataType>(&self, layer: u32, mip_level: u32) -> Vec<T> {
+        self.check_layer(layer);
+        mip_level_size(self.width, self.height, mip_level, self.number_of_mip_maps);
+        let layers = [layer];
+        ColorTarget::new_texture_2d_array(&self.context, self, &layers, Some(mip_level)).read()
+    }
+
     ///
     /// Returns a [ColorTarget] which can be used to clear, write to and read from the given layers and mip level of this texture.
     /// Combine this together with a [DepthTarget] with [RenderTarget::new] to be able to write to both a depth and color target at the same time.
@@ -276,6 +365,20 @@ impl Texture2DArray {
         self.depth
     }
 
+    /// The number of mip levels in this texture, including the full size level.
+    pub fn number_of_mip_maps(&self) -> u32 {
+        self.number_of_mip_maps
+    }
+
+    fn check_layer(&self, layer: u32) {
+        if layer >= self.depth {
+            panic!(
+                "cannot access the layer {}, since there are only {} layers in the texture array",
+                layer, self.depth
+            )
+        }
+    }
+
     pub(in crate::core) fn generate_mip_maps(&self) {
         if self.number_of_mip_maps > 1 {
             self.bind();
@@ -298,18 +401,18 @@ impl Texture2DArray {
         }
     }
 
+    pub(in crate::core) fn data_type(&self) -> u32 {
+        self.data_type
+    }
+
     pub(in crate::core) fn bind(&self) {
-        unsafe {
-            self.context
-                .bind_texture(crate::context::TEXTURE_2D_ARRAY, Some(self.id));
-        }
+        self.context
+            .set_texture(crate::context::TEXTURE_2D_ARRAY, self.id);
     }
 }
 
 impl Drop for Texture2DArray {
     fn drop(&mut self) {
-        unsafe {
-            self.context.delete_texture(self.id);
-        }
+        self.context.destroy_texture(self.id);
     }
 }
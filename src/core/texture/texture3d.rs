@@ -69,6 +69,8 @@ impl Texture3D {
         wrap_t: Wrapping,
         wrap_r: Wrapping,
     ) -> Self {
+        let (min_filter, mag_filter, mip_map_filter) =
+            filters_from_data_type::<T>(min_filter, mag_filter, mip_map_filter);
         let id = generate(context);
         let number_of_mip_maps =
             calculate_number_of_mip_maps(mip_map_filter, width, height, Some(depth));
@@ -144,6 +146,48 @@ impl Texture3D {
         self.generate_mip_maps();
     }
 
+    ///
+    /// Fills the region of this texture with the given offset and size with the given data, leaving the rest of the texture unchanged.
+    /// The offset `(x, y, z)` is the position of the first pixel of the region in the same layout as the data given to [Texture3D::fill].
+    ///
+    /// # Panic
+    /// Will panic if the region is outside the texture or if the length of the data does not correspond to the size of the region and the format specified at construction.
+    ///
+    pub fn fill_partially<T: TextureDataType>(
+        &mut self,
+        x: u32,
+        y: u32,
+        z: u32,
+        width: u32,
+        height: u32,
+        depth: u32,
+        data: &[T],
+    ) {
+        check_region(
+            (x, y, z),
+            (width, height, depth),
+            (self.width, self.height, self.depth),
+        );
+        check_data_length::<T>(width, height, depth, self.data_byte_size, data.len());
+        self.bind();
+        unsafe {
+            self.context.tex_sub_image_3d(
+                crate::context::TEXTURE_3D,
+                0,
+                x as i32,
+                y as i32,
+                z as i32,
+                width as i32,
+                height as i32,
+                depth as i32,
+                format_from_data_type::<T>(),
+                T::data_type(),
+                crate::context::PixelUnpackData::Slice(to_byte_slice(data)),
+            );
+        }
+        self.generate_mip_maps();
+    }
+
     /// The width of this texture.
     pub fn width(&self) -> u32 {
         self.width
@@ -168,17 +212,13 @@ impl Texture3D {
         }
     }
     pub(in crate::core) fn bind(&self) {
-        unsafe {
-            self.context
-                .bind_texture(crate::context::TEXTURE_3D, Some(self.id));
-        }
+        self.context
+            .set_texture(crate::context::TEXTURE_3D, self.id);
     }
 }
 
 impl Drop for Texture3D {
     fn drop(&mut self) {
-        unsafe {
-            self.context.delete_texture(self.id);
-        }
+        self.context.destroy_texture(self.id);
     }
 }
@@ -117,6 +117,7 @@ pub struct TextureCubeMap {
     number_of_mip_maps: u32,
     is_hdr: bool,
     data_byte_size: usize,
+    data_type: u32,
 }
 
 impl TextureCubeMap {
@@ -316,6 +317,8 @@ impl TextureCubeMap {
         wrap_t: Wrapping,
         wrap_r: Wrapping,
     ) -> Self {
+        let (min_filter, mag_filter, mip_map_filter) =
+            filters_from_data_type::<T>(min_filter, mag_filter, mip_map_filter);
         let id = generate(context);
         let number_of_mip_maps = calculate_number_of_mip_maps(mip_map_filter, width, height, None);
         let texture = Self {
@@ -326,6 +329,7 @@ impl TextureCubeMap {
             number_of_mip_maps,
             is_hdr: std::mem::size_of::<T>() as u32 / T::size() > 1,
             data_byte_size: std::mem::size_of::<T>(),
+            data_type: T::data_type(),
         };
         texture.bind();
         set_parameters(
@@ -441,6 +445,94 @@ impl TextureCubeMap {
         self.generate_mip_maps();
     }
 
+    ///
+    /// Fills the region with the given offset and size of the given side of the cube map texture with the given pixel data, leaving the rest of the texture unchanged.
+    /// The offset `(x, y)` is the position of the first pixel of the region in the same layout as the data given to [TextureCubeMap::fill].
+    ///
+    /// # Panic
+    /// Will panic if the region is outside the texture or if the length of the data does not correspond to the size of the region and the format specified at construction.
+    ///
+    pub fn fill_partially<T: TextureDataType>(
+        &mut self,
+        side: CubeMapSide,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        data: &[T],
+    ) {
+        check_region((x, y, 0), (width, height, 1), (self.width, self.height, 1));
+        check_data_length::<T>(width, height, 1, self.data_byte_size, data.len());
+        self.bind();
+        unsafe {
+            self.context.tex_sub_image_2d(
+                side.to_const(),
+                0,
+                x as i32,
+                y as i32,
+                width as i32,
+                height as i32,
+                format_from_data_type::<T>(),
+                T::data_type(),
+                crate::context::PixelUnpackData::Slice(to_byte_slice(data)),
+            );
+        }
+        self.generate_mip_maps();
+    }
+
+    ///
+    /// Fills the given mip level of the given side of the cube map texture with the given pixel data, where the width and height of the mip level is the width and height of this texture divided by 2 to the power of the mip level.
+    /// In contrast to [TextureCubeMap::fill], mip maps are not generated afterwards, so this can be used to upload pre-filtered mip levels,
+    /// for example the [prefilter map](crate::renderer::Environment::prefilter_map) of an environment.
+    /// Note however that all mip levels are regenerated if the texture is filled using [TextureCubeMap::fill] or written to using [TextureCubeMap::as_color_target] with `None` as the mip level.
+    ///
+    /// # Panic
+    /// Will panic if the mip level does not exist or if the length of the data does not correspond to the size of the mip level and the format specified at construction.
+    ///
+    pub fn fill_mip_level<T: TextureDataType>(
+        &mut self,
+        side: CubeMapSide,
+        mip_level: u32,
+        data: &[T],
+    ) {
+        let (width, height) =
+            mip_level_size(self.width, self.height, mip_level, self.number_of_mip_maps);
+        check_data_length::<T>(width, height, 1, self.data_byte_size, data.len());
+        self.bind();
+        unsafe {
+            self.context.tex_sub_image_2d(
+                side.to_const(),
+                mip_level as i32,
+                0,
+                0,
+                width as i32,
+                height as i32,
+                format_from_data_type::<T>(),
+                T::data_type(),
+                crate::context::PixelUnpackData::Slice(to_byte_slice(data)),
+            );
+        }
+    }
+
+    ///
+    /// Returns the pixel data of the given mip level of the given side of the cube map texture, in the same layout as given to [TextureCubeMap::fill_mip_level].
+    /// The number of channels per pixel and the data format for each channel is specified by the generic parameter.
+    ///
+    /// **Note:** On web, the data format needs to match the data format of the texture.
+    ///
+    /// # Panic
+    /// Will panic if the mip level does not exist.
+    ///
+    pub fn read_mip_level<T: TextureDataType>(&self, side: CubeMapSide, mip_level: u32) -> Vec<T> {
+        let (width, height) =
+            mip_level_size(self.width, self.height, mip_level, self.number_of_mip_maps);
+        let mut data =
+            ColorTarget::new_texture_cube_map(&self.context, self, side, Some(mip_level)).read();
+        // The sides of a cube map are not flipped when filled, so the flip when reading is reverted
+        flip_y(&mut data, width as usize, height as usize);
+        data
+    }
+
     ///
     /// Creates a new cube texture generated from the equirectangular texture given as input.
     ///
@@ -524,6 +616,11 @@ impl TextureCubeMap {
         self.height
     }
 
+    /// The number of mip levels in this texture, including the full size level.
+    pub fn number_of_mip_maps(&self) -> u32 {
+        self.number_of_mip_maps
+    }
+
     /// Whether this cube map contain HDR (high dynamic range) data.
     pub fn is_hdr(&self) -> bool {
         self.is_hdr
@@ -556,18 +653,18 @@ impl TextureCubeMap {
         }
     }
 
+    pub(in crate::core) fn data_type(&self) -> u32 {
+        self.data_type
+    }
+
     pub(in crate::core) fn bind(&self) {
-        unsafe {
-            self.context
-                .bind_texture(crate::context::TEXTURE_CUBE_MAP, Some(self.id));
-        }
+        self.context
+            .set_texture(crate::context::TEXTURE_CUBE_MAP, self.id);
     }
 }
 
 impl Drop for TextureCubeMap {
     fn drop(&mut self) {
-        unsafe {
-            self.context.delete_texture(self.id);
-        }
+        self.context.destroy_texture(self.id);
     }
 }
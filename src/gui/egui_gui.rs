@@ -14,6 +14,7 @@ pub struct GUI {
     egui_context: egui::Context,
     output: RefCell<Option<egui::FullOutput>>,
     modifiers: Modifiers,
+    context: Option<Context>,
 }
 
 impl GUI {
@@ -22,11 +23,15 @@ impl GUI {
     ///
     pub fn new(context: &Context) -> Self {
         use std::ops::Deref;
-        Self::from_gl_context(context.deref().clone())
+        Self {
+            context: Some(context.clone()),
+            ..Self::from_gl_context(context.deref().clone())
+        }
     }
 
     ///
     /// Creates a new GUI from a low-level graphics [Context](crate::context::Context).
+    /// If the low-level graphics context is also used by a mid-level [Context], [Context::invalidate_state] must be called after each call to [GUI::render].
     ///
     pub fn from_gl_context(context: std::sync::Arc<crate::context::Context>) -> Self {
         #[allow(unsafe_code)] // Temporary until egui takes Arc
@@ -36,6 +41,7 @@ impl GUI {
             painter: RefCell::new(Painter::new(context, None, "").unwrap()),
             output: RefCell::new(None),
             modifiers: Modifiers::default(),
+            context: None,
         }
     }
 
@@ -235,6 +241,9 @@ impl GUI {
             use glow::HasContext as _;
             self.painter.borrow().gl().disable(glow::FRAMEBUFFER_SRGB);
         }
+        if let Some(ref context) = self.context {
+            context.invalidate_state();
+        }
     }
 }
 
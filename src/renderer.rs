@@ -55,11 +55,23 @@ pub use object::*;
 pub mod control;
 pub use control::*;
 
+mod render_queue;
+#[doc(inline)]
+pub use render_queue::*;
+
+mod occlusion_culling;
+#[doc(inline)]
+pub use occlusion_culling::*;
+
+mod object_bvh;
+#[doc(inline)]
+pub use object_bvh::*;
+
 impl DepthTarget<'_> {
     ///
     /// Render the objects using the given camera and lights into this depth target.
     /// Use an empty array for the `lights` argument, if the objects does not require lights to be rendered.
-    /// Also, objects outside the camera frustum are not rendered and the objects are rendered in the order given by [cmp_render_order].
+    /// Also, objects outside the camera frustum are not rendered and the objects are rendered in the order given by [RenderQueue].
     ///
     pub fn render(
         &self,
@@ -73,7 +85,7 @@ impl DepthTarget<'_> {
     ///
     /// Render the objects using the given camera and lights into the part of this depth target defined by the scissor box.
     /// Use an empty array for the `lights` argument, if the objects does not require lights to be rendered.
-    /// Also, objects outside the camera frustum are not rendered and the objects are rendered in the order given by [cmp_render_order].
+    /// Also, objects outside the camera frustum are not rendered and the objects are rendered in the order given by [RenderQueue].
     ///
     pub fn render_partially(
         &self,
@@ -137,7 +149,7 @@ impl ColorTarget<'_> {
     ///
     /// Render the objects using the given camera and lights into this color target.
     /// Use an empty array for the `lights` argument, if the objects does not require lights to be rendered.
-    /// Also, objects outside the camera frustum are not rendered and the objects are rendered in the order given by [cmp_render_order].
+    /// Also, objects outside the camera frustum are not rendered and the objects are rendered in the order given by [RenderQueue].
     ///
     pub fn render(
         &self,
@@ -151,7 +163,7 @@ impl ColorTarget<'_> {
     ///
     /// Render the objects using the given camera and lights into the part of this color target defined by the scissor box.
     /// Use an empty array for the `lights` argument, if the objects does not require lights to be rendered.
-    /// Also, objects outside the camera frustum are not rendered and the objects are rendered in the order given by [cmp_render_order].
+    /// Also, objects outside the camera frustum are not rendered and the objects are rendered in the order given by [RenderQueue].
     ///
     pub fn render_partially(
         &self,
@@ -165,6 +177,35 @@ impl ColorTarget<'_> {
         self
     }
 
+    ///
+    /// Render the objects using the given camera and lights into this color target.
+    /// Same as [ColorTarget::render], except that transparent objects are rendered using weighted blended order-independent transparency, see [RenderTarget::render_with_oit].
+    ///
+    pub fn render_with_oit(
+        &self,
+        camera: &Camera,
+        objects: impl IntoIterator<Item = impl Object>,
+        lights: &[&dyn Light],
+    ) -> &Self {
+        self.render_partially_with_oit(self.scissor_box(), camera, objects, lights)
+    }
+
+    ///
+    /// Render the objects using the given camera and lights into the part of this color target defined by the scissor box.
+    /// Same as [ColorTarget::render_partially], except that transparent objects are rendered using weighted blended order-independent transparency, see [RenderTarget::render_with_oit].
+    ///
+    pub fn render_partially_with_oit(
+        &self,
+        scissor_box: ScissorBox,
+        camera: &Camera,
+        objects: impl IntoIterator<Item = impl Object>,
+        lights: &[&dyn Light],
+    ) -> &Self {
+        self.as_render_target()
+            .render_partially_with_oit(scissor_box, camera, objects, lights);
+        self
+    }
+
     ///
     /// Render the geometries with the given material using the given camera and lights into this color target.
     /// Use an empty array for the `lights` argument, if the material does not require lights to be rendered.
@@ -215,7 +256,7 @@ impl RenderTarget<'_> {
     ///
     /// Render the objects using the given camera and lights into this render target.
     /// Use an empty array for the `lights` argument, if the objects does not require lights to be rendered.
-    /// Also, objects outside the camera frustum are not rendered and the objects are rendered in the order given by [cmp_render_order].
+    /// Also, objects outside the camera frustum are not rendered and the objects are rendered in the order given by [RenderQueue].
     ///
     pub fn render(
         &self,
@@ -229,7 +270,7 @@ impl RenderTarget<'_> {
     ///
     /// Render the objects using the given camera and lights into the part of this render target defined by the scissor box.
     /// Use an empty array for the `lights` argument, if the objects does not require lights to be rendered.
-    /// Also, objects outside the camera frustum are not rendered and the objects are rendered in the order given by [cmp_render_order].
+    /// Also, objects outside the camera frustum are not rendered and the objects are rendered in the order given by [RenderQueue].
     ///
     pub fn render_partially(
         &self,
@@ -238,19 +279,201 @@ impl RenderTarget<'_> {
         objects: impl IntoIterator<Item = impl Object>,
         lights: &[&dyn Light],
     ) -> &Self {
-        let (mut deferred_objects, mut forward_objects): (Vec<_>, Vec<_>) = objects
+        self.render_objects(scissor_box, camera, objects, lights, false)
+    }
+
+    ///
+    /// Render the objects using the given camera and lights into this render target.
+    /// Same as [RenderTarget::render], except that objects with a [MaterialType::Transparent] material type are rendered using weighted blended order-independent transparency (see [Object::render_with_oit])
+    /// instead of being sorted back to front. This gives a plausible result for intersecting and nested transparent objects, at the cost of an additional depth pass of the opaque objects.
+    /// Requires support for blending into half float textures which on web requires the `EXT_color_buffer_float` and `EXT_float_blend` extensions.
+    /// If these are not supported, or if an object does not support it (see [Object::supports_oit]), the transparent objects are sorted back to front as in [RenderTarget::render].
+    ///
+    pub fn render_with_oit(
+        &self,
+        camera: &Camera,
+        objects: impl IntoIterator<Item = impl Object>,
+        lights: &[&dyn Light],
+    ) -> &Self {
+        self.render_partially_with_oit(self.scissor_box(), camera, objects, lights)
+    }
+
+    ///
+    /// Render the objects using the given camera and lights into the part of this render target defined by the scissor box.
+    /// Same as [RenderTarget::render_partially], except that objects with a [MaterialType::Transparent] material type are rendered using weighted blended order-independent transparency (see [RenderTarget::render_with_oit]).
+    ///
+    pub fn render_partially_with_oit(
+        &self,
+        scissor_box: ScissorBox,
+        camera: &Camera,
+        objects: impl IntoIterator<Item = impl Object>,
+        lights: &[&dyn Light],
+    ) -> &Self {
+        self.render_objects(scissor_box, camera, objects, lights, true)
+    }
+
+    ///
+    /// Render the objects using the given camera and lights into this render target, where the parts of the objects on the clipped side of any of the given clip planes are cut away, see [ClipPlane].
+    /// Use [RenderTarget::render_clip_caps] afterwards to fill the holes that appear where closed objects are cut.
+    ///
+    /// All materials in this crate, except the material of the [Skybox], support clip planes, but only when used on a geometry whose vertex shader outputs the world position (`out vec3 pos;`).
+    /// A custom material can support clip planes by including `in vec3 pos;`, `uniform int clipPlaneCount;` and `uniform vec4 clipPlanes[8];` inside `#ifdef USE_CLIP_PLANES` in the fragment shader,
+    /// the uniforms are then automatically set in every draw call.
+    ///
+    /// # Panic
+    /// Will panic if more than [MAX_CLIP_PLANES] planes are given.
+    ///
+    pub fn render_with_clip_planes(
+        &self,
+        camera: &Camera,
+        objects: impl IntoIterator<Item = impl Object>,
+        lights: &[&dyn Light],
+        clip_planes: &[ClipPlane],
+    ) -> &Self {
+        self.context
+            .with_clip_planes(clip_planes, || self.render(camera, objects, lights))
+    }
+
+    ///
+    /// Render the geometries with the given material using the given camera and lights into this render target, where the parts of the geometries on the clipped side of any of the given clip planes are cut away.
+    /// Same as [RenderTarget::render_with_material], except for the clip planes, see [RenderTarget::render_with_clip_planes].
+    ///
+    /// # Panic
+    /// Will panic if more than [MAX_CLIP_PLANES] planes are given.
+    ///
+    pub fn render_with_material_and_clip_planes(
+        &self,
+        material: &dyn Material,
+        camera: &Camera,
+        geometries: impl IntoIterator<Item = impl Geometry>,
+        lights: &[&dyn Light],
+        clip_planes: &[ClipPlane],
+    ) -> &Self {
+        self.context.with_clip_planes(clip_planes, || {
+            self.render_with_material(material, camera, geometries, lights)
+        })
+    }
+
+    ///
+    /// Fills the holes that appear where the given closed geometries are cut by the given clip planes with the color of the given material,
+    /// which makes the geometries look solid instead of hollow.
+    /// Call this after rendering the geometries with the same clip planes using [RenderTarget::render_with_clip_planes], since the caps are depth tested against the rendered geometries.
+    ///
+    /// If this render target has a stencil buffer, which is only the case for the screen, the cut is found by counting the surfaces behind each pixel in the stencil buffer.
+    /// This works for any closed geometry and for all clip planes, also when the camera is on the kept side of a plane.
+    /// Otherwise the back faces visible through the cut are rendered with the [ClipCapMaterial], see its documentation for the limitations.
+    ///
+    /// # Panic
+    /// Will panic if more than [MAX_CLIP_PLANES] planes are given.
+    ///
+    pub fn render_clip_caps(
+        &self,
+        material: &ClipCapMaterial,
+        camera: &Camera,
+        geometries: impl IntoIterator<Item = impl Geometry>,
+        clip_planes: &[ClipPlane],
+    ) -> &Self {
+        let geometries = geometries
+            .into_iter()
+            .filter(|g| camera.in_frustum(&g.aabb()))
+            .collect::<Vec<_>>();
+        self.context.with_clip_planes(clip_planes, || {
+            if self.has_stencil_buffer() {
+                // Only writes to the stencil buffer
+                let stencil_material = ColorMaterial {
+                    render_states: RenderStates {
+                        write_mask: WriteMask::NONE,
+                        depth_test: DepthTest::Always,
+                        cull: Cull::None,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                };
+                self.write(|| {
+                    for index in 0..clip_planes.len() {
+                        self.context.set_stencil_mode(StencilMode::Invert);
+                        for geometry in geometries.iter() {
+                            geometry.render_with_material(&stencil_material, camera, &[]);
+                        }
+                        self.context.set_stencil_mode(StencilMode::NotZero);
+                        material.render_stencil_cap(&self.context, camera, index);
+                    }
+                    self.context.set_stencil_mode(StencilMode::Disabled);
+                });
+                self
+            } else {
+                self.render_with_material(material, camera, geometries, &[])
+            }
+        })
+    }
+
+    ///
+    /// Render the objects using the given camera and lights into this render target.
+    /// Same as [RenderTarget::render], except that objects which were hidden behind other objects in a previous frame are not rendered, see [OcclusionCulling].
+    /// The render target must have a depth target and the same objects must be given in the same order every frame.
+    ///
+    pub fn render_with_occlusion_culling(
+        &self,
+        occlusion_culling: &OcclusionCulling,
+        camera: &Camera,
+        objects: impl IntoIterator<Item = impl Object>,
+        lights: &[&dyn Light],
+    ) -> &Self {
+        let objects = objects.into_iter().collect::<Vec<_>>();
+        let visibility = occlusion_culling.visibility(objects.len());
+        self.render(
+            camera,
+            objects
+                .iter()
+                .zip(visibility)
+                .filter(|(_, visible)| *visible)
+                .map(|(object, _)| object),
+            lights,
+        );
+        let aabbs = objects.iter().map(|o| o.aabb()).collect::<Vec<_>>();
+        self.write(|| occlusion_culling.render_queries(camera, &aabbs))
+    }
+
+    ///
+    /// Render the objects using the given camera and lights into this render target.
+    /// Same as [RenderTarget::render], except that the objects which share both geometry and material are rendered with a single instanced draw call, see [MeshBatching].
+    /// Shear in the transformations of the objects is not supported.
+    ///
+    pub fn render_with_batching<'a, M: Material + 'a>(
+        &self,
+        batching: &MeshBatching<M>,
+        camera: &Camera,
+        objects: impl IntoIterator<Item = &'a Gm<Mesh, std::sync::Arc<M>>>,
+        lights: &[&dyn Light],
+    ) -> &Self {
+        batching.batch(objects, |batched_objects| {
+            self.render(camera, batched_objects, lights)
+        })
+    }
+
+    fn render_objects(
+        &self,
+        scissor_box: ScissorBox,
+        camera: &Camera,
+        objects: impl IntoIterator<Item = impl Object>,
+        lights: &[&dyn Light],
+        use_oit: bool,
+    ) -> &Self {
+        let (deferred_objects, mut forward_objects): (Vec<_>, Vec<_>) = objects
             .into_iter()
             .filter(|o| camera.in_frustum(&o.aabb()))
             .partition(|o| o.material_type() == MaterialType::Deferred);
 
         // Deferred
-        if deferred_objects.len() > 0 {
+        let deferred_objects = RenderQueue::new(camera, deferred_objects, lights);
+        if deferred_objects.objects().len() > 0 {
+            self.context
+                .register_queued_state_changes(deferred_objects.state_changes() as u32);
             // Geometry pass
             let mut geometry_pass_camera = camera.clone();
             let viewport =
                 Viewport::new_at_origo(camera.viewport().width, camera.viewport().height);
             geometry_pass_camera.set_viewport(viewport);
-            deferred_objects.sort_by(|a, b| cmp_render_order(&geometry_pass_camera, a, b));
             let mut geometry_pass_texture = Texture2DArray::new_empty::<[u8; 4]>(
                 &self.context,
                 viewport.width,
@@ -275,11 +498,7 @@ impl RenderTarget<'_> {
                 geometry_pass_depth_texture.as_depth_target(),
             )
             .clear(ClearState::default())
-            .write(|| {
-                for object in deferred_objects {
-                    object.render(&geometry_pass_camera, lights);
-                }
-            });
+            .write(|| deferred_objects.render(&geometry_pass_camera, lights));
 
             // Lighting pass
             self.write_partially(scissor_box, || {
@@ -294,12 +513,72 @@ impl RenderTarget<'_> {
         }
 
         // Forward
-        forward_objects.sort_by(|a, b| cmp_render_order(camera, a, b));
-        self.write_partially(scissor_box, || {
-            for object in forward_objects {
-                object.render(camera, lights);
-            }
-        });
+        let oit_objects = if use_oit && OitMaterial::is_available(&self.context) {
+            let (oit_objects, other_objects): (Vec<_>, Vec<_>) =
+                forward_objects.into_iter().partition(|o| {
+                    o.material_type() == MaterialType::Transparent && o.supports_oit(lights)
+                });
+            forward_objects = other_objects;
+            oit_objects
+        } else {
+            Vec::new()
+        };
+        let forward_objects = RenderQueue::new(camera, forward_objects, lights);
+        self.context
+            .register_queued_state_changes(forward_objects.state_changes() as u32);
+        self.write_partially(scissor_box, || forward_objects.render(camera, lights));
+
+        // Order-independent transparency
+        if oit_objects.len() > 0 {
+            // Accumulation pass
+            let mut oit_camera = camera.clone();
+            let viewport =
+                Viewport::new_at_origo(camera.viewport().width, camera.viewport().height);
+            oit_camera.set_viewport(viewport);
+            let mut accumulation_texture = OitMaterial::new_accumulation_texture(
+                &self.context,
+                viewport.width,
+                viewport.height,
+            );
+            let mut depth_texture = DepthTargetTexture2D::new(
+                &self.context,
+                viewport.width,
+                viewport.height,
+                Wrapping::ClampToEdge,
+                Wrapping::ClampToEdge,
+                DepthFormat::Depth32F,
+            );
+            let depth_material = DepthMaterial {
+                render_states: RenderStates {
+                    write_mask: WriteMask::DEPTH,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            RenderTarget::new(
+                accumulation_texture.as_color_target(&[0, 1], None),
+                depth_texture.as_depth_target(),
+            )
+            .clear(ClearState::color_and_depth(0.0, 0.0, 0.0, 1.0, 1.0))
+            .write(|| {
+                // Transparent objects that are sorted instead must not hide the order-independent transparent objects behind them
+                for object in deferred_objects
+                    .into_iter()
+                    .chain(&forward_objects)
+                    .filter(|o| o.material_type() != MaterialType::Transparent)
+                {
+                    object.render_with_material(&depth_material, &oit_camera, &[]);
+                }
+                for object in oit_objects {
+                    object.render_with_oit(&oit_camera, lights);
+                }
+            });
+
+            // Composite pass
+            self.write_partially(scissor_box, || {
+                OitMaterial::composite_pass(&self.context, camera.viewport(), &accumulation_texture)
+            });
+        }
         self
     }
 
@@ -406,6 +685,7 @@ pub fn pick(
 ///
 /// Finds the closest intersection between a ray starting at the given position in the given direction and the given geometries.
 /// Returns ```None``` if no geometry was hit before the given maximum depth.
+/// This renders to a small texture and reads it back, which stalls the GPU pipeline, see [MeshBvh] for a CPU alternative.
 ///
 pub fn ray_intersect(
     context: &Context,
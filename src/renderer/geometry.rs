@@ -20,6 +20,22 @@ mod particles;
 #[doc(inline)]
 pub use particles::*;
 
+mod gpu_particles;
+#[doc(inline)]
+pub use gpu_particles::*;
+
+mod bvh;
+#[doc(inline)]
+pub use bvh::*;
+
+mod lod_mesh;
+#[doc(inline)]
+pub use lod_mesh::*;
+
+mod simplification;
+#[doc(inline)]
+pub use simplification::*;
+
 use crate::core::*;
 use crate::renderer::*;
 
@@ -215,6 +231,99 @@ impl<T: Geometry2D> Geometry2D for std::sync::RwLock<T> {
 }
 
 use std::collections::HashMap;
+use std::sync::Arc;
+
+// Generates the vertex shader source which passes the custom attributes, given by the attribute name, the name used in the fragment shader and the number of components,
+// on to the fragment shader if they are used there.
+fn custom_attributes_source(
+    fragment_shader_source: &str,
+    attributes: impl IntoIterator<Item = (String, String, u32)>,
+) -> String {
+    // The attributes are sorted to generate the same source, and thereby reuse the same program, every time
+    let mut attributes = attributes.into_iter().collect::<Vec<_>>();
+    attributes.sort();
+    let mut declarations = String::new();
+    let mut assignments = String::new();
+    for (attribute_name, name, component_count) in attributes {
+        let glsl_type = match component_count {
+            1 => "float",
+            2 => "vec2",
+            3 => "vec3",
+            _ => "vec4",
+        };
+        if let Some((declared_type, flat)) = find_input_declaration(fragment_shader_source, &name) {
+            if declared_type != glsl_type {
+                panic!(
+                    "the custom attribute {} has {} components, so it must be declared as {} in the fragment shader, but it is declared as {}",
+                    name, component_count, glsl_type, declared_type
+                );
+            }
+            let interpolation = if flat { "flat " } else { "" };
+            declarations.push_str(&format!(
+                "in {} {};\n{}out {} {};\n",
+                glsl_type, attribute_name, interpolation, glsl_type, name
+            ));
+            assignments.push_str(&format!("    {} = {};\n", name, attribute_name));
+        }
+    }
+    if declarations.is_empty() {
+        String::new()
+    } else {
+        format!(
+            "#define USE_CUSTOM_ATTRIBUTES\n{}void custom_attributes()\n{{\n{}}}\n",
+            declarations, assignments
+        )
+    }
+}
+
+// Finds the declaration of the input with the given name in the fragment shader source and returns the type and whether it is declared flat.
+// The declaration is split into tokens, so any qualifiers, for example precision qualifiers, and any whitespace are allowed.
+fn find_input_declaration<'a>(
+    fragment_shader_source: &'a str,
+    name: &str,
+) -> Option<(&'a str, bool)> {
+    fragment_shader_source.split(';').find_map(|statement| {
+        let tokens = statement
+            .lines()
+            .map(|line| line.split("//").next().unwrap())
+            .filter(|line| !line.trim_start().starts_with('#'))
+            .flat_map(|line| line.split_whitespace())
+            .collect::<Vec<_>>();
+        let start = tokens
+            .iter()
+            .rposition(|token| token.contains('{') || token.contains('}'))
+            .map_or(0, |i| i + 1);
+        match tokens[start..] {
+            [ref qualifiers @ .., glsl_type, last]
+                if last == name && qualifiers.contains(&"in") =>
+            {
+                Some((glsl_type, qualifiers.contains(&"flat")))
+            }
+            _ => None,
+        }
+    })
+}
+
+fn custom_vertex_attributes(
+    custom_vertex_buffers: &HashMap<String, Arc<VertexBuffer>>,
+) -> impl Iterator<Item = (String, String, u32)> + '_ {
+    custom_vertex_buffers
+        .iter()
+        .map(|(name, buffer)| (format!("custom_{}", name), name.clone(), buffer.data_size()))
+}
+
+fn use_custom_attributes(
+    program: &Program,
+    custom_vertex_buffers: &HashMap<String, Arc<VertexBuffer>>,
+) {
+    for (name, buffer) in custom_vertex_buffers.iter() {
+        let attribute_name = format!("custom_{}", name);
+        if program.requires_attribute(&attribute_name) {
+            program.use_vertex_attribute(&attribute_name, buffer);
+        }
+    }
+}
+
 fn vertex_buffers_from_mesh(
     context: &Context,
     cpu_mesh: &CpuMesh,
@@ -259,6 +368,49 @@ fn vertex_buffers_from_mesh(
     buffers
 }
 
+fn interleaved_vertex_buffers_from_mesh(
+    context: &Context,
+    cpu_mesh: &CpuMesh,
+) -> HashMap<String, VertexBuffer> {
+    #[cfg(debug_assertions)]
+    cpu_mesh.validate().expect("invalid cpu mesh");
+
+    let to_i8 = |v: f32| (v.clamp(-1.0, 1.0) * 127.0).round() as i8;
+    let positions = cpu_mesh.positions.to_f32();
+    let normals = cpu_mesh.normals.as_ref().map(|normals| {
+        normals
+            .iter()
+            .map(|n| [to_i8(n.x), to_i8(n.y), to_i8(n.z)])
+            .collect::<Vec<_>>()
+    });
+    let tangents = cpu_mesh.tangents.as_ref().map(|tangents| {
+        tangents
+            .iter()
+            .map(|t| [to_i8(t.x), to_i8(t.y), to_i8(t.z), to_i8(t.w)])
+            .collect::<Vec<_>>()
+    });
+    let uvs = cpu_mesh.uvs.as_ref().map(|uvs| {
+        uvs.iter()
+            .map(|uv| vec2(uv.x, 1.0 - uv.y))
+            .collect::<Vec<_>>()
+    });
+
+    let mut attributes = vec![VertexAttribute::new("position", &positions)];
+    if let Some(ref normals) = normals {
+        attributes.push(VertexAttribute::new_normalized("normal", normals));
+    }
+    if let Some(ref tangents) = tangents {
+        attributes.push(VertexAttribute::new_normalized("tangent", tangents));
+    }
+    if let Some(ref uvs) = uvs {
+        attributes.push(VertexAttribute::new("uv_coordinates", uvs));
+    }
+    if let Some(ref colors) = cpu_mesh.colors {
+        attributes.push(VertexAttribute::new("color", colors));
+    }
+    VertexBuffer::new_interleaved(context, &attributes)
+}
+
 fn index_buffer_from_mesh(context: &Context, cpu_mesh: &CpuMesh) -> Option<ElementBuffer> {
     match &cpu_mesh.indices {
         Indices::U8(ind) => Some(ElementBuffer::new_with_data(context, ind)),
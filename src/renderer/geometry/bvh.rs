@@ -0,0 +1,473 @@
+use crate::core::*;
+use crate::renderer::*;
+
+const MAX_TRIANGLES_IN_LEAF: usize = 4;
+
+///
+/// The result of a ray or segment query against a [MeshBvh].
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+    /// The distance from the start of the ray to the hit point in world space.
+    pub distance: f32,
+    /// The position of the hit point in world space.
+    pub position: Vec3,
+    /// The index of the triangle that was hit, ie. the triangle defined by the indices at `3 * triangle_index`, `3 * triangle_index + 1` and `3 * triangle_index + 2`.
+    pub triangle_index: usize,
+    /// The barycentric coordinates of the hit point with respect to the three vertices of the triangle.
+    pub barycentric: Vec3,
+    /// The normal of the triangle that was hit in world space.
+    /// The normal faces the same way as the triangle winding, ie. it is not flipped towards the ray.
+    pub normal: Vec3,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct BvhNode {
+    min: Vec3,
+    max: Vec3,
+    // Index of the left child if this is an interior node (the right child is at left + 1),
+    // otherwise the index of the first triangle in the triangle order.
+    first: u32,
+    // The number of triangles in this node if it is a leaf, zero otherwise.
+    count: u32,
+}
+
+///
+/// A bounding volume hierarchy built on the CPU from the triangles of a [CpuMesh].
+/// Can be used to find intersections between rays or line segments and the mesh without touching the GPU,
+/// for example for picking, camera collision or gameplay queries.
+/// See [ray_intersect] for the GPU alternative which works for any [Geometry].
+///
+/// The hierarchy is built in the local coordinate system of the mesh, so the transformation can be changed using [MeshBvh::set_transformation] without rebuilding it.
+/// A mesh with a transformation that cannot be inverted, for example a scale of zero, is flattened and cannot be hit by any query.
+///
+pub struct MeshBvh {
+    positions: Vec<Vec3>,
+    triangles: Vec<[u32; 3]>,
+    triangle_order: Vec<u32>,
+    nodes: Vec<BvhNode>,
+    transformation: Mat4,
+    inverse_transformation: Option<Mat4>,
+    normal_transformation: Mat3,
+}
+
+impl MeshBvh {
+    ///
+    /// Builds a bounding volume hierarchy from the triangles in the given [CpuMesh].
+    /// If the mesh has no indices, every three consecutive positions are treated as a triangle.
+    ///
+    pub fn new(cpu_mesh: &CpuMesh) -> Self {
+        let positions = cpu_mesh.positions.to_f32();
+        let indices = cpu_mesh
+            .indices
+            .to_u32()
+            .unwrap_or_else(|| (0..positions.len() as u32).collect());
+        let triangles = indices
+            .chunks_exact(3)
+            .map(|t| [t[0], t[1], t[2]])
+            .collect::<Vec<_>>();
+
+        let mut bvh = Self {
+            triangle_order: (0..triangles.len() as u32).collect(),
+            positions,
+            triangles,
+            nodes: Vec::new(),
+            transformation: Mat4::identity(),
+            inverse_transformation: Some(Mat4::identity()),
+            normal_transformation: Mat3::identity(),
+        };
+        bvh.build();
+        bvh
+    }
+
+    ///
+    /// Builds a bounding volume hierarchy from the triangles in the given [CpuMesh] and sets the local to world transformation,
+    /// for example the transformation of the [Mesh] that was created from the same [CpuMesh].
+    ///
+    pub fn new_with_transformation(cpu_mesh: &CpuMesh, transformation: Mat4) -> Self {
+        let mut bvh = Self::new(cpu_mesh);
+        bvh.set_transformation(transformation);
+        bvh
+    }
+
+    ///
+    /// Returns the local to world transformation applied to the mesh.
+    ///
+    pub fn transformation(&self) -> Mat4 {
+        self.transformation
+    }
+
+    ///
+    /// Set the local to world transformation applied to the mesh.
+    /// Should be kept in sync with the transformation of the [Mesh] if the queries are used for picking.
+    ///
+    pub fn set_transformation(&mut self, transformation: Mat4) {
+        self.transformation = transformation;
+        self.inverse_transformation = transformation.invert();
+        // The normals are not used when the transformation is singular, since nothing can be hit
+        let inverse = self.inverse_transformation.unwrap_or(Mat4::identity());
+        self.normal_transformation = Mat3::from_cols(
+            inverse.x.truncate(),
+            inverse.y.truncate(),
+            inverse.z.truncate(),
+        )
+        .transpose();
+    }
+
+    ///
+    /// Returns the number of triangles in the hierarchy.
+    ///
+    pub fn triangle_count(&self) -> usize {
+        self.triangles.len()
+    }
+
+    ///
+    /// Returns the [AxisAlignedBoundingBox] of the mesh in the global coordinate system.
+    ///
+    pub fn aabb(&self) -> AxisAlignedBoundingBox {
+        if self.nodes.is_empty() {
+            return AxisAlignedBoundingBox::EMPTY;
+        }
+        let mut aabb =
+            AxisAlignedBoundingBox::new_with_positions(&[self.nodes[0].min, self.nodes[0].max]);
+        aabb.transform(&self.transformation);
+        aabb
+    }
+
+    ///
+    /// Finds the closest intersection between the mesh and a ray starting at the given position in the given direction.
+    /// Returns ```None``` if the mesh was not hit before the given maximum distance.
+    ///
+    pub fn ray_intersect(
+        &self,
+        position: Vec3,
+        direction: Vec3,
+        max_distance: f32,
+    ) -> Option<RayHit> {
+        let inverse_transformation = self.inverse_transformation?;
+        let direction = direction.normalize();
+        // The direction and the distance are transformed separately, so a very large maximum distance does not overflow.
+        // The ray parameter is the same in local and world space since the transformation is affine, so it is the distance in world space.
+        let local_start = transform_point(&inverse_transformation, position);
+        let local_direction = (inverse_transformation * direction.extend(0.0)).truncate();
+        let (triangle_index, t, u, v) =
+            self.closest_hit(local_start, local_direction, max_distance)?;
+
+        let hit_position = transform_point(&self.transformation, local_start + local_direction * t);
+        let [i0, i1, i2] = self.triangles[triangle_index];
+        let p0 = self.positions[i0 as usize];
+        let p1 = self.positions[i1 as usize];
+        let p2 = self.positions[i2 as usize];
+        Some(RayHit {
+            distance: t,
+            position: hit_position,
+            triangle_index,
+            barycentric: vec3(1.0 - u - v, u, v),
+            normal: (self.normal_transformation * (p1 - p0).cross(p2 - p0)).normalize(),
+        })
+    }
+
+    ///
+    /// Finds the intersection between the mesh and the line segment from `start` to `end` which is closest to `start`.
+    /// Returns ```None``` if the segment does not intersect the mesh.
+    ///
+    pub fn segment_intersect(&self, start: Vec3, end: Vec3) -> Option<RayHit> {
+        let length = (end - start).magnitude();
+        if length <= f32::EPSILON {
+            return None;
+        }
+        self.ray_intersect(start, end - start, length)
+    }
+
+    ///
+    /// Returns true if the line segment from `start` to `end` intersects the mesh.
+    /// This is faster than [MeshBvh::segment_intersect] since the search stops at the first hit.
+    ///
+    pub fn segment_intersects(&self, start: Vec3, end: Vec3) -> bool {
+        if let Some(inverse_transformation) = self.inverse_transformation {
+            let local_start = transform_point(&inverse_transformation, start);
+            let local_end = transform_point(&inverse_transformation, end);
+            self.any_hit(local_start, local_end - local_start, 1.0)
+        } else {
+            false
+        }
+    }
+
+    fn build(&mut self) {
+        self.nodes.clear();
+        if self.triangles.is_empty() {
+            return;
+        }
+        let centroids = self
+            .triangles
+            .iter()
+            .map(|t| {
+                (self.positions[t[0] as usize]
+                    + self.positions[t[1] as usize]
+                    + self.positions[t[2] as usize])
+                    / 3.0
+            })
+            .collect::<Vec<_>>();
+        self.nodes.push(BvhNode {
+            min: Vec3::zero(),
+            max: Vec3::zero(),
+            first: 0,
+            count: self.triangles.len() as u32,
+        });
+        let mut stack = vec![0];
+        while let Some(node_index) = stack.pop() {
+            self.update_bounds(node_index);
+            let node = self.nodes[node_index];
+            let first = node.first as usize;
+            let count = node.count as usize;
+            if count <= MAX_TRIANGLES_IN_LEAF {
+                continue;
+            }
+
+            // Split at the middle of the centroid bounds along the longest axis
+            let mut centroid_min = vec3(f32::MAX, f32::MAX, f32::MAX);
+            let mut centroid_max = vec3(f32::MIN, f32::MIN, f32::MIN);
+            for &t in self.triangle_order[first..first + count].iter() {
+                let c = centroids[t as usize];
+                centroid_min = vec3(
+                    centroid_min.x.min(c.x),
+                    centroid_min.y.min(c.y),
+                    centroid_min.z.min(c.z),
+                );
+                centroid_max = vec3(
+                    centroid_max.x.max(c.x),
+                    centroid_max.y.max(c.y),
+                    centroid_max.z.max(c.z),
+                );
+            }
+            let extent = centroid_max - centroid_min;
+            let axis = if extent.x >= extent.y && extent.x >= extent.z {
+                0
+            } else if extent.y >= extent.z {
+                1
+            } else {
+                2
+            };
+            let split = centroid_min[axis] + 0.5 * extent[axis];
+            let order = &mut self.triangle_order[first..first + count];
+            let mut left_count = 0;
+            for i in 0..count {
+                if centroids[order[i] as usize][axis] < split {
+                    order.swap(i, left_count);
+                    left_count += 1;
+                }
+            }
+            // All centroids are on one side, so split in the middle of the sorted list instead
+            if left_count == 0 || left_count == count {
+                order.sort_by(|a, b| {
+                    centroids[*a as usize][axis]
+                        .partial_cmp(&centroids[*b as usize][axis])
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                });
+                left_count = count / 2;
+            }
+
+            let left_index = self.nodes.len();
+            self.nodes.push(BvhNode {
+                min: Vec3::zero(),
+                max: Vec3::zero(),
+                first: first as u32,
+                count: left_count as u32,
+            });
+            self.nodes.push(BvhNode {
+                min: Vec3::zero(),
+                max: Vec3::zero(),
+                first: (first + left_count) as u32,
+                count: (count - left_count) as u32,
+            });
+            self.nodes[node_index].first = left_index as u32;
+            self.nodes[node_index].count = 0;
+            stack.push(left_index);
+            stack.push(left_index + 1);
+        }
+    }
+
+    fn update_bounds(&mut self, node_index: usize) {
+        let node = self.nodes[node_index];
+        let mut min = vec3(f32::MAX, f32::MAX, f32::MAX);
+        let mut max = vec3(f32::MIN, f32::MIN, f32::MIN);
+        for &t in
+            self.triangle_order[node.first as usize..(node.first + node.count) as usize].iter()
+        {
+            for &i in self.triangles[t as usize].iter() {
+                let p = self.positions[i as usize];
+                min = vec3(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z));
+                max = vec3(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z));
+            }
+        }
+        self.nodes[node_index].min = min;
+        self.nodes[node_index].max = max;
+    }
+
+    // Returns the triangle index and the ray parameter and barycentric coordinates of the closest hit.
+    fn closest_hit(
+        &self,
+        origin: Vec3,
+        direction: Vec3,
+        max_t: f32,
+    ) -> Option<(usize, f32, f32, f32)> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+        let inv_direction = vec3(1.0 / direction.x, 1.0 / direction.y, 1.0 / direction.z);
+        let mut closest: Option<(usize, f32, f32, f32)> = None;
+        let mut closest_t = max_t;
+        let mut stack = vec![0usize];
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+            if intersect_aabb(node.min, node.max, origin, inv_direction, closest_t).is_none() {
+                continue;
+            }
+            if node.count > 0 {
+                for &t in self.triangle_order
+                    [node.first as usize..(node.first + node.count) as usize]
+                    .iter()
+                {
+                    if let Some((hit_t, u, v)) =
+                        self.intersect_triangle(t as usize, origin, direction, closest_t)
+                    {
+                        closest_t = hit_t;
+                        closest = Some((t as usize, hit_t, u, v));
+                    }
+                }
+            } else {
+                // Visit the nearest child first
+                let left = node.first as usize;
+                let right = left + 1;
+                let t_left = intersect_aabb(
+                    self.nodes[left].min,
+                    self.nodes[left].max,
+                    origin,
+                    inv_direction,
+                    closest_t,
+                );
+                let t_right = intersect_aabb(
+                    self.nodes[right].min,
+                    self.nodes[right].max,
+                    origin,
+                    inv_direction,
+                    closest_t,
+                );
+                match (t_left, t_right) {
+                    (Some(tl), Some(tr)) => {
+                        if tl < tr {
+                            stack.push(right);
+                            stack.push(left);
+                        } else {
+                            stack.push(left);
+                            stack.push(right);
+                        }
+                    }
+                    (Some(_), None) => stack.push(left),
+                    (None, Some(_)) => stack.push(right),
+                    (None, None) => {}
+                }
+            }
+        }
+        closest
+    }
+
+    fn any_hit(&self, origin: Vec3, direction: Vec3, max_t: f32) -> bool {
+        if self.nodes.is_empty() {
+            return false;
+        }
+        let inv_direction = vec3(1.0 / direction.x, 1.0 / direction.y, 1.0 / direction.z);
+        let mut stack = vec![0usize];
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+            if intersect_aabb(node.min, node.max, origin, inv_direction, max_t).is_none() {
+                continue;
+            }
+            if node.count > 0 {
+                for &t in self.triangle_order
+                    [node.first as usize..(node.first + node.count) as usize]
+                    .iter()
+                {
+                    if self
+                        .intersect_triangle(t as usize, origin, direction, max_t)
+                        .is_some()
+                    {
+                        return true;
+                    }
+                }
+            } else {
+                stack.push(node.first as usize);
+                stack.push(node.first as usize + 1);
+            }
+        }
+        false
+    }
+
+    // Möller–Trumbore ray-triangle intersection.
+    fn intersect_triangle(
+        &self,
+        triangle_index: usize,
+        origin: Vec3,
+        direction: Vec3,
+        max_t: f32,
+    ) -> Option<(f32, f32, f32)> {
+        let [i0, i1, i2] = self.triangles[triangle_index];
+        let p0 = self.positions[i0 as usize];
+        let e1 = self.positions[i1 as usize] - p0;
+        let e2 = self.positions[i2 as usize] - p0;
+        let p = direction.cross(e2);
+        let det = e1.dot(p);
+        if det.abs() < f32::EPSILON * e1.magnitude2().max(e2.magnitude2()) {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+        let s = origin - p0;
+        let u = s.dot(p) * inv_det;
+        if u < 0.0 || u > 1.0 {
+            return None;
+        }
+        let q = s.cross(e1);
+        let v = direction.dot(q) * inv_det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let t = e2.dot(q) * inv_det;
+        if t < 0.0 || t > max_t {
+            return None;
+        }
+        Some((t, u, v))
+    }
+}
+
+// Slab test, returns the ray parameter where the ray enters the box.
+fn intersect_aabb(
+    min: Vec3,
+    max: Vec3,
+    origin: Vec3,
+    inv_direction: Vec3,
+    max_t: f32,
+) -> Option<f32> {
+    let mut t_min = 0.0f32;
+    let mut t_max = max_t;
+    for axis in 0..3 {
+        let t0 = (min[axis] - origin[axis]) * inv_direction[axis];
+        let t1 = (max[axis] - origin[axis]) * inv_direction[axis];
+        let (t0, t1) = if t0 <= t1 { (t0, t1) } else { (t1, t0) };
+        // NaN appears when the ray lies in the plane of the slab, in which case the slab is ignored
+        if !t0.is_nan() {
+            t_min = t_min.max(t0);
+        }
+        if !t1.is_nan() {
+            t_max = t_max.min(t1);
+        }
+        if t_min > t_max {
+            return None;
+        }
+    }
+    Some(t_min)
+}
+
+fn transform_point(transformation: &Mat4, point: Vec3) -> Vec3 {
+    let p = transformation * point.extend(1.0);
+    p.truncate() / p.w
+}
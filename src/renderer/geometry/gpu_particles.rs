@@ -0,0 +1,626 @@
+use crate::core::*;
+use crate::renderer::*;
+use std::collections::HashMap;
+
+// The number of samples of each curve sent to the shader.
+const CURVE_SAMPLE_COUNT: usize = 16;
+// The number of random positions on the surface of a mesh used for emitting particles.
+const SURFACE_SAMPLE_COUNT: usize = 1024;
+
+///
+/// The shape from which a [ParticleEmitter] emits particles, defined in the local coordinate system of the emitter.
+///
+#[derive(Clone, Debug)]
+pub enum ParticleEmitterShape {
+    /// Particles are emitted from the origin in random directions.
+    Point,
+    /// Particles are emitted from random positions inside a sphere centered at the origin, moving away from the center.
+    Sphere {
+        /// The radius of the sphere.
+        radius: f32,
+    },
+    /// Particles are emitted from random positions on a disc in the xz-plane centered at the origin, moving in random directions within a cone around the y-axis.
+    Cone {
+        /// The radius of the disc.
+        radius: f32,
+        /// The angle in radians between the y-axis and the side of the cone.
+        angle: f32,
+    },
+    /// Particles are emitted from random positions on the surface of the mesh, moving in the direction of the surface normal.
+    MeshSurface(CpuMesh),
+}
+
+///
+/// Emits particles into a [GpuParticleSystem] with a given rate from a given shape.
+/// The lifetime and speed of each particle is chosen randomly within the given ranges.
+///
+#[derive(Clone, Debug)]
+pub struct ParticleEmitter {
+    /// The shape from which the particles are emitted.
+    pub shape: ParticleEmitterShape,
+    /// The local to world transformation of the emitter.
+    pub transformation: Mat4,
+    /// The number of particles emitted per second.
+    pub rate: f32,
+    /// The minimum lifetime of a particle in seconds.
+    pub min_lifetime: f32,
+    /// The maximum lifetime of a particle in seconds.
+    pub max_lifetime: f32,
+    /// The minimum speed of a particle when emitted.
+    pub min_speed: f32,
+    /// The maximum speed of a particle when emitted.
+    pub max_speed: f32,
+}
+
+impl ParticleEmitter {
+    ///
+    /// Creates a new emitter with the given shape which emits 100 particles per second with a lifetime between 1 and 2 seconds and a speed between 1 and 2.
+    ///
+    pub fn new(shape: ParticleEmitterShape) -> Self {
+        Self {
+            shape,
+            transformation: Mat4::identity(),
+            rate: 100.0,
+            min_lifetime: 1.0,
+            max_lifetime: 2.0,
+            min_speed: 1.0,
+            max_speed: 2.0,
+        }
+    }
+}
+
+///
+/// A curve which defines a value over the life of a particle, for example the size or the color.
+/// The curve is defined by a list of keys, each consisting of a time in the range `[0..1]` relative to the lifetime of the particle and a value.
+/// The value between two keys is linearly interpolated and the value before the first key and after the last key is constant.
+///
+#[derive(Clone, Debug)]
+pub struct ParticleCurve<T: Copy> {
+    keys: Vec<(f32, T)>,
+}
+
+impl<T: Copy> ParticleCurve<T> {
+    ///
+    /// Creates a new curve from the given keys. The keys do not need to be sorted by time.
+    ///
+    /// # Panic
+    /// Will panic if no keys are given.
+    ///
+    pub fn new(keys: &[(f32, T)]) -> Self {
+        if keys.is_empty() {
+            panic!("a particle curve needs at least one key");
+        }
+        let mut keys = keys.to_vec();
+        keys.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+        Self { keys }
+    }
+
+    ///
+    /// Creates a new curve with the same value over the entire life.
+    ///
+    pub fn constant(value: T) -> Self {
+        Self {
+            keys: vec![(0.0, value)],
+        }
+    }
+
+    ///
+    /// Returns the keys of this curve sorted by time.
+    ///
+    pub fn keys(&self) -> &[(f32, T)] {
+        &self.keys
+    }
+
+    fn sample_with(&self, time: f32, lerp: impl Fn(T, T, f32) -> T) -> T {
+        let next = self.keys.iter().position(|(t, _)| *t > time);
+        match next {
+            Some(0) => self.keys[0].1,
+            Some(i) => {
+                let (t0, v0) = self.keys[i - 1];
+                let (t1, v1) = self.keys[i];
+                lerp(v0, v1, (time - t0) / (t1 - t0))
+            }
+            None => self.keys[self.keys.len() - 1].1,
+        }
+    }
+
+    fn samples<S>(&self, sample: impl Fn(&Self, f32) -> S) -> Vec<S> {
+        (0..CURVE_SAMPLE_COUNT)
+            .map(|i| sample(self, i as f32 / (CURVE_SAMPLE_COUNT - 1) as f32))
+            .collect()
+    }
+}
+
+impl ParticleCurve<f32> {
+    ///
+    /// Returns the value at the given time in the range `[0..1]` relative to the lifetime of the particle.
+    ///
+    pub fn sample(&self, time: f32) -> f32 {
+        self.sample_with(time, |a, b, t| a + (b - a) * t)
+    }
+}
+
+impl ParticleCurve<Color> {
+    ///
+    /// Returns the value at the given time in the range `[0..1]` relative to the lifetime of the particle.
+    ///
+    pub fn sample(&self, time: f32) -> Color {
+        self.sample_with(time, |a, b, t| {
+            let lerp = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+            Color::new(
+                lerp(a.r, b.r),
+                lerp(a.g, b.g),
+                lerp(a.b, b.b),
+                lerp(a.a, b.a),
+            )
+        })
+    }
+}
+
+struct EmitterState {
+    emitter: ParticleEmitter,
+    surface: Option<Texture2D>,
+    accumulated: f32,
+    burst: u32,
+}
+
+///
+/// A particle system where the particles are simulated on the GPU, which makes it possible to simulate a large number of particles,
+/// for example to create smoke, fire or sparks.
+/// In contrast to [ParticleSystem], each particle has an age and a lifetime, after which the particle dies and can be replaced by a new particle.
+///
+/// The particles are emitted by one or more [ParticleEmitter]s and are afterwards affected by [GpuParticleSystem::acceleration] and [GpuParticleSystem::drag].
+/// The size, color and opacity of each particle changes over its life as defined by [ParticleCurve]s.
+/// Each particle is rendered as the mesh given at construction, scaled by the size of the particle,
+/// and the state of all particles is kept on the GPU and updated using transform feedback (see [Program::draw_arrays_transform_feedback]).
+///
+/// The particles are only simulated when calling [GpuParticleSystem::update], which should be called every frame.
+///
+pub struct GpuParticleSystem {
+    context: Context,
+    vertex_buffers: HashMap<String, VertexBuffer>,
+    index_buffer: Option<ElementBuffer>,
+    update_program: Program,
+    positions: [VertexBuffer; 2],
+    velocities: [VertexBuffer; 2],
+    current: usize,
+    capacity: u32,
+    next_index: u32,
+    seed: u32,
+    emitters: Vec<EmitterState>,
+    empty_surface: Texture2D,
+    /// The acceleration applied to all particles defined in the world coordinate system. Default is gravity.
+    pub acceleration: Vec3,
+    /// The drag applied to all particles, ie. how fast the particles slow down. Default is no drag.
+    pub drag: f32,
+    /// The size of each particle over its life. Default is a size of 1.
+    pub size_over_life: ParticleCurve<f32>,
+    /// The color of each particle over its life. Default is white.
+    pub color_over_life: ParticleCurve<Color>,
+    /// The opacity of each particle over its life, which is multiplied with the alpha value of [GpuParticleSystem::color_over_life]. Default is fully opaque.
+    pub opacity_over_life: ParticleCurve<f32>,
+    /// Whether or not the x and y axes of the particle mesh are aligned with the screen, which is typically used with a quad to render smoke or fire. Default is false.
+    pub billboard: bool,
+}
+
+impl GpuParticleSystem {
+    ///
+    /// Creates a new particle system with room for the given number of particles alive at the same time, where each particle is rendered as the given mesh.
+    /// When more particles are emitted than the capacity, the oldest particles are replaced.
+    ///
+    pub fn new(context: &Context, capacity: u32, cpu_mesh: &CpuMesh) -> Self {
+        #[cfg(debug_assertions)]
+        cpu_mesh.validate().expect("invalid cpu mesh");
+
+        let capacity = capacity.max(1);
+        let dead = vec![vec4(0.0, 0.0, 0.0, 0.0); capacity as usize];
+        let update_program = Program::from_source_with_transform_feedback(
+            context,
+            include_str!("shaders/gpu_particles_update.vert"),
+            "layout (location = 0) out vec4 outColor;\nvoid main() { outColor = vec4(0.0); }",
+            &["new_position", "new_velocity"],
+        )
+        .expect("Failed compiling shader");
+        Self {
+            context: context.clone(),
+            index_buffer: super::index_buffer_from_mesh(context, cpu_mesh),
+            vertex_buffers: super::vertex_buffers_from_mesh(context, cpu_mesh),
+            update_program,
+            positions: [
+                VertexBuffer::new_with_data(context, &dead),
+                VertexBuffer::new_with_data(context, &dead),
+            ],
+            velocities: [
+                VertexBuffer::new_with_data(context, &dead),
+                VertexBuffer::new_with_data(context, &dead),
+            ],
+            current: 0,
+            capacity,
+            next_index: 0,
+            seed: 0,
+            emitters: Vec::new(),
+            empty_surface: Texture2D::new_empty::<[f32; 4]>(
+                context,
+                1,
+                2,
+                Interpolation::Nearest,
+                Interpolation::Nearest,
+                None,
+                Wrapping::ClampToEdge,
+                Wrapping::ClampToEdge,
+            ),
+            acceleration: vec3(0.0, -9.82, 0.0),
+            drag: 0.0,
+            size_over_life: ParticleCurve::constant(1.0),
+            color_over_life: ParticleCurve::constant(Color::WHITE),
+            opacity_over_life: ParticleCurve::constant(1.0),
+            billboard: false,
+        }
+    }
+
+    ///
+    /// Returns the maximum number of particles alive at the same time.
+    ///
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    ///
+    /// Adds the given emitter and returns the index of the emitter.
+    ///
+    pub fn add_emitter(&mut self, emitter: ParticleEmitter) -> usize {
+        self.emitters.push(EmitterState {
+            surface: surface_texture(&self.context, &emitter.shape),
+            emitter,
+            accumulated: 0.0,
+            burst: 0,
+        });
+        self.emitters.len() - 1
+    }
+
+    ///
+    /// Returns the emitter with the given index.
+    ///
+    pub fn emitter(&self, index: usize) -> &ParticleEmitter {
+        &self.emitters[index].emitter
+    }
+
+    ///
+    /// Replaces the emitter with the given index.
+    ///
+    pub fn set_emitter(&mut self, index: usize, emitter: ParticleEmitter) {
+        self.emitters[index].surface = surface_texture(&self.context, &emitter.shape);
+        self.emitters[index].emitter = emitter;
+    }
+
+    ///
+    /// Set the local to world transformation of the emitter with the given index, for example to make the emitter follow an object.
+    ///
+    pub fn set_emitter_transformation(&mut self, index: usize, transformation: Mat4) {
+        self.emitters[index].emitter.transformation = transformation;
+    }
+
+    ///
+    /// Removes the emitter with the given index. The particles already emitted by the emitter stay alive for the rest of their life.
+    /// The index of the emitters after the removed emitter is decreased by one.
+    ///
+    pub fn remove_emitter(&mut self, index: usize) {
+        self.emitters.remove(index);
+    }
+
+    ///
+    /// Returns the number of emitters.
+    ///
+    pub fn emitter_count(&self) -> usize {
+        self.emitters.len()
+    }
+
+    ///
+    /// Emits the given number of particles from the emitter with the given index the next time [GpuParticleSystem::update] is called,
+    /// in addition to the particles emitted continuously. This is for example useful for sparks or explosions.
+    ///
+    pub fn emit(&mut self, index: usize, count: u32) {
+        self.emitters[index].burst += count;
+    }
+
+    ///
+    /// Kills all particles.
+    ///
+    pub fn clear(&mut self) {
+        let dead = vec![vec4(0.0, 0.0, 0.0, 0.0); self.capacity as usize];
+        self.positions[self.current].fill(&dead);
+        self.velocities[self.current].fill(&dead);
+        for state in self.emitters.iter_mut() {
+            state.accumulated = 0.0;
+            state.burst = 0;
+        }
+    }
+
+    ///
+    /// Moves the simulation forward by the given time in seconds, ie. emits new particles and updates the position, velocity and age of the existing particles.
+    /// Should be called once every frame.
+    ///
+    pub fn update(&mut self, delta_time: f32) {
+        let delta_time = delta_time.max(0.0);
+        // The first pass moves the existing particles and each pass emits the particles of one emitter
+        for pass in 0..self.emitters.len().max(1) {
+            let spawn_count = if let Some(state) = self.emitters.get_mut(pass) {
+                state.accumulated += state.emitter.rate.max(0.0) * delta_time;
+                let count = state.accumulated.floor();
+                state.accumulated -= count;
+                let count = (count as u32 + state.burst).min(self.capacity);
+                state.burst = 0;
+                count
+            } else {
+                0
+            };
+            self.seed = self.seed.wrapping_add(1);
+            self.simulate(pass, if pass == 0 { delta_time } else { 0.0 }, spawn_count);
+            self.next_index = (self.next_index + spawn_count) % self.capacity;
+        }
+    }
+
+    fn simulate(&mut self, emitter_index: usize, delta_time: f32, spawn_count: u32) {
+        let program = &self.update_program;
+        program.use_uniform("deltaTime", delta_time);
+        program.use_uniform("acceleration", self.acceleration);
+        program.use_uniform("drag", self.drag);
+        program.use_uniform("seed", self.seed as i32);
+        program.use_uniform("particleCount", self.capacity as i32);
+        program.use_uniform("spawnStart", self.next_index as i32);
+        program.use_uniform("spawnCount", spawn_count as i32);
+
+        let (shape, radius, angle) = if let Some(state) = self.emitters.get(emitter_index) {
+            let emitter = &state.emitter;
+            program.use_uniform("emitterTransformation", emitter.transformation);
+            program.use_uniform(
+                "lifetimeRange",
+                vec2(emitter.min_lifetime, emitter.max_lifetime),
+            );
+            program.use_uniform("speedRange", vec2(emitter.min_speed, emitter.max_speed));
+            program.use_texture(
+                "emitterSurface",
+                state.surface.as_ref().unwrap_or(&self.empty_surface),
+            );
+            match emitter.shape {
+                ParticleEmitterShape::Point => (0, 0.0, 0.0),
+                ParticleEmitterShape::Sphere { radius } => (1, radius, 0.0),
+                ParticleEmitterShape::Cone { radius, angle } => (2, radius, angle),
+                ParticleEmitterShape::MeshSurface(_) => (3, 0.0, 0.0),
+            }
+        } else {
+            program.use_uniform("emitterTransformation", Mat4::identity());
+            program.use_uniform("lifetimeRange", vec2(0.0, 0.0));
+            program.use_uniform("speedRange", vec2(0.0, 0.0));
+            program.use_texture("emitterSurface", &self.empty_surface);
+            (0, 0.0, 0.0)
+        };
+        program.use_uniform("emitterShape", shape as i32);
+        program.use_uniform("emitterRadius", radius);
+        program.use_uniform("emitterAngle", angle);
+
+        let current = self.current;
+        let next = 1 - current;
+        let (positions, velocities) = (&mut self.positions, &mut self.velocities);
+        let [input_position, output_position] = pair(positions, current);
+        let [input_velocity, output_velocity] = pair(velocities, current);
+        program.use_vertex_attribute("particle_position", input_position);
+        program.use_vertex_attribute("particle_velocity", input_velocity);
+        program
+            .draw_arrays_transform_feedback(self.capacity, &mut [output_position, output_velocity]);
+        self.current = next;
+    }
+
+    fn vertex_shader_source(
+        &self,
+        fragment_shader_source: &str,
+        material_vertex_source: Option<String>,
+    ) -> String {
+        let use_positions = fragment_shader_source.find("in vec3 pos;").is_some();
+        let use_normals = fragment_shader_source.find("in vec3 nor;").is_some()
+            || (material_vertex_source.is_some() && self.vertex_buffers.contains_key("normal"));
+        let use_tangents = fragment_shader_source.find("in vec3 tang;").is_some();
+        let use_uvs = fragment_shader_source.find("in vec2 uvs;").is_some();
+        let use_colors = fragment_shader_source.find("in vec4 col;").is_some();
+        format!(
+            "#define GPU_PARTICLES\n{}{}{}{}{}{}{}{}{}{}",
+            if self.billboard {
+                "#define GPU_PARTICLES_BILLBOARD\n"
+            } else {
+                ""
+            },
+            if use_positions {
+                "#define USE_POSITIONS\n"
+            } else {
+                ""
+            },
+            if use_normals {
+                "#define USE_NORMALS\n"
+            } else {
+                ""
+            },
+            if use_tangents {
+                if fragment_shader_source.find("in vec3 bitang;").is_none() {
+                    panic!("if the fragment shader defined 'in vec3 tang' it also needs to define 'in vec3 bitang'");
+                }
+                "#define USE_TANGENTS\n"
+            } else {
+                ""
+            },
+            if use_uvs { "#define USE_UVS\n" } else { "" },
+            if use_colors {
+                if self.vertex_buffers.contains_key("color") {
+                    "#define USE_COLORS\n#define USE_VERTEX_COLORS\n"
+                } else {
+                    "#define USE_COLORS\n"
+                }
+            } else {
+                ""
+            },
+            include_str!("../../core/shared.frag"),
+            include_str!("shaders/gpu_particles.vert"),
+            material_vertex_source
+                .map(|source| format!("#define USE_MATERIAL_VERTEX\n{}\n", source))
+                .unwrap_or_default(),
+            include_str!("shaders/mesh.vert"),
+        )
+    }
+}
+
+impl Geometry for GpuParticleSystem {
+    fn aabb(&self) -> AxisAlignedBoundingBox {
+        AxisAlignedBoundingBox::INFINITE
+    }
+
+    fn render_with_material(
+        &self,
+        material: &dyn Material,
+        camera: &Camera,
+        lights: &[&dyn Light],
+    ) {
+        let fragment_shader_source = material.fragment_shader_source(true, lights);
+        self.context.program(
+            &self.vertex_shader_source(&fragment_shader_source, material.vertex_shader_source()),
+            &fragment_shader_source,
+            |program| {
+                material.use_uniforms(program, camera, lights);
+                program.use_uniform("viewProjection", camera.projection() * camera.view());
+                program.use_uniform("modelMatrix", Mat4::identity());
+                program.use_uniform_if_required("textureTransform", Mat3::identity());
+                program.use_uniform_if_required("normalMatrix", Mat4::identity());
+                program.use_uniform_array(
+                    "particleSizes",
+                    &self.size_over_life.samples(|curve, t| curve.sample(t)),
+                );
+                if program.requires_uniform("particleColors") {
+                    program.use_uniform_array(
+                        "particleColors",
+                        &self.color_over_life.samples(|curve, t| {
+                            let color = curve.sample(t);
+                            vec4(
+                                color.r as f32 / 255.0,
+                                color.g as f32 / 255.0,
+                                color.b as f32 / 255.0,
+                                color.a as f32 / 255.0 * self.opacity_over_life.sample(t),
+                            )
+                        }),
+                    );
+                }
+                if self.billboard {
+                    let view = camera.view();
+                    program.use_uniform("particleCameraRight", vec3(view.x.x, view.y.x, view.z.x));
+                    program.use_uniform("particleCameraUp", vec3(view.x.y, view.y.y, view.z.y));
+                }
+
+                for attribute_name in ["position", "normal", "tangent", "color", "uv_coordinates"] {
+                    if program.requires_attribute(attribute_name) {
+                        program.use_vertex_attribute(
+                            attribute_name,
+                            self.vertex_buffers
+                                .get(attribute_name).expect(&format!("the render call requires the {} vertex buffer which is missing on the given geometry", attribute_name))
+                        );
+                    }
+                }
+                program.use_vertex_buffer_as_instance_attribute(
+                    "particle_position",
+                    &self.positions[self.current],
+                );
+                program.use_vertex_buffer_as_instance_attribute(
+                    "particle_velocity",
+                    &self.velocities[self.current],
+                );
+
+                if let Some(ref index_buffer) = self.index_buffer {
+                    program.draw_elements_instanced(
+                        material.render_states(),
+                        camera.viewport(),
+                        index_buffer,
+                        self.capacity,
+                    )
+                } else {
+                    program.draw_arrays_instanced(
+                        material.render_states(),
+                        camera.viewport(),
+                        self.vertex_buffers.get("position").unwrap().vertex_count() as u32,
+                        self.capacity,
+                    )
+                }
+            },
+        ).expect("Failed compiling shader")
+    }
+}
+
+// Returns the buffer with the given index followed by the other buffer.
+fn pair(buffers: &mut [VertexBuffer; 2], index: usize) -> [&mut VertexBuffer; 2] {
+    let [first, second] = buffers;
+    if index == 0 {
+        [first, second]
+    } else {
+        [second, first]
+    }
+}
+
+// Generates a texture with random positions on the surface of the mesh in the first row and the corresponding normals in the second row.
+fn surface_texture(context: &Context, shape: &ParticleEmitterShape) -> Option<Texture2D> {
+    let cpu_mesh = match shape {
+        ParticleEmitterShape::MeshSurface(cpu_mesh) => cpu_mesh,
+        _ => return None,
+    };
+    let positions = cpu_mesh.positions.to_f32();
+    let indices = cpu_mesh
+        .indices
+        .to_u32()
+        .unwrap_or_else(|| (0..positions.len() as u32).collect());
+    let mut triangles = Vec::new();
+    let mut total_area = 0.0;
+    for triangle in indices.chunks_exact(3) {
+        let [p0, p1, p2] = [0, 1, 2].map(|i| positions[triangle[i] as usize]);
+        let cross = (p1 - p0).cross(p2 - p0);
+        let area = 0.5 * cross.magnitude();
+        if area > 0.0 {
+            total_area += area;
+            triangles.push((total_area, [p0, p1, p2], cross.normalize()));
+        }
+    }
+    if triangles.is_empty() {
+        return None;
+    }
+
+    // A simple deterministic random generator (xorshift) is enough for distributing the samples
+    let mut state = 0x9e3779b9u32;
+    let mut random = || {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        state as f32 / u32::MAX as f32
+    };
+    let mut sample_positions = Vec::with_capacity(SURFACE_SAMPLE_COUNT);
+    let mut sample_normals = Vec::with_capacity(SURFACE_SAMPLE_COUNT);
+    for i in 0..SURFACE_SAMPLE_COUNT {
+        // Stratified sampling of the triangles weighted by their area
+        let target = (i as f32 + random()) / SURFACE_SAMPLE_COUNT as f32 * total_area;
+        let t = triangles
+            .partition_point(|(cumulative_area, _, _)| *cumulative_area < target)
+            .min(triangles.len() - 1);
+        let (_, [p0, p1, p2], normal) = triangles[t];
+        let (r0, r1) = (random().sqrt(), random());
+        let p = p0 * (1.0 - r0) + p1 * (r0 * (1.0 - r1)) + p2 * (r0 * r1);
+        sample_positions.push([p.x, p.y, p.z, 1.0]);
+        sample_normals.push([normal.x, normal.y, normal.z, 0.0]);
+    }
+    sample_positions.extend(sample_normals);
+    Some(Texture2D::new(
+        context,
+        &CpuTexture {
+            data: TextureData::RgbaF32(sample_positions),
+            width: SURFACE_SAMPLE_COUNT as u32,
+            height: 2,
+            min_filter: Interpolation::Nearest,
+            mag_filter: Interpolation::Nearest,
+            mip_map_filter: None,
+            wrap_s: Wrapping::ClampToEdge,
+            wrap_t: Wrapping::ClampToEdge,
+            ..Default::default()
+        },
+    ))
+}
@@ -1,21 +1,26 @@
 use crate::core::*;
 use crate::renderer::*;
 use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
 
 ///
 /// Similar to [Mesh], except it is possible to render many instances of the same mesh efficiently.
 ///
 pub struct InstancedMesh {
     context: Context,
-    vertex_buffers: HashMap<String, VertexBuffer>,
-    instance_buffers: HashMap<String, InstanceBuffer>,
-    index_buffer: Option<ElementBuffer>,
+    vertex_buffers: Arc<HashMap<String, VertexBuffer>>,
+    instance_buffers: RwLock<HashMap<String, InstanceBuffer>>,
+    index_buffer: Option<Arc<ElementBuffer>>,
+    custom_vertex_buffers: HashMap<String, Arc<VertexBuffer>>,
     aabb_local: AxisAlignedBoundingBox,
     aabb: AxisAlignedBoundingBox,
     transformation: Mat4,
     instance_transforms: Vec<Mat4>,
     instance_count: u32,
     texture_transform: Mat3,
+    instances: Instances,
+    instance_order: RwLock<Vec<u32>>,
+    depth_sorting: bool,
 }
 
 impl InstancedMesh {
@@ -25,18 +30,56 @@ impl InstancedMesh {
     /// The model is rendered in as many instances as there are attributes in [Instances] given as input.
     ///
     pub fn new(context: &Context, instances: &Instances, cpu_mesh: &CpuMesh) -> Self {
-        let aabb = cpu_mesh.compute_aabb();
+        Self::new_with_buffers(
+            context,
+            instances,
+            Arc::new(super::vertex_buffers_from_mesh(context, cpu_mesh)),
+            super::index_buffer_from_mesh(context, cpu_mesh).map(Arc::new),
+            HashMap::new(),
+            cpu_mesh.compute_aabb(),
+        )
+    }
+
+    ///
+    /// Creates a new instanced 3D mesh which shares the GPU buffers, including custom attributes, with the given [Mesh], so no data is transfered to the GPU except the instance attributes.
+    /// The transformation and texture transform of the mesh is not used.
+    /// The model is rendered in as many instances as there are attributes in [Instances] given as input.
+    ///
+    pub fn from_mesh(context: &Context, instances: &Instances, mesh: &Mesh) -> Self {
+        let (vertex_buffers, index_buffer, custom_vertex_buffers, aabb) = mesh.buffers();
+        Self::new_with_buffers(
+            context,
+            instances,
+            vertex_buffers,
+            index_buffer,
+            custom_vertex_buffers,
+            aabb,
+        )
+    }
+
+    fn new_with_buffers(
+        context: &Context,
+        instances: &Instances,
+        vertex_buffers: Arc<HashMap<String, VertexBuffer>>,
+        index_buffer: Option<Arc<ElementBuffer>>,
+        custom_vertex_buffers: HashMap<String, Arc<VertexBuffer>>,
+        aabb: AxisAlignedBoundingBox,
+    ) -> Self {
         let mut instanced_mesh = Self {
             context: context.clone(),
-            index_buffer: super::index_buffer_from_mesh(context, cpu_mesh),
-            vertex_buffers: super::vertex_buffers_from_mesh(context, cpu_mesh),
-            instance_buffers: HashMap::new(),
+            index_buffer,
+            vertex_buffers,
+            custom_vertex_buffers,
+            instance_buffers: RwLock::new(HashMap::new()),
             aabb,
             aabb_local: aabb.clone(),
             transformation: Mat4::identity(),
             instance_count: 0,
             instance_transforms: Vec::new(),
             texture_transform: Mat3::identity(),
+            instances: Instances::default(),
+            instance_order: RwLock::new(Vec::new()),
+            depth_sorting: false,
         };
         instanced_mesh.set_instances(instances);
         instanced_mesh
@@ -73,6 +116,39 @@ impl InstancedMesh {
         self.texture_transform = texture_transform;
     }
 
+    ///
+    /// Set a custom attribute with the given name and a value for each vertex of the mesh, which is the same for all instances.
+    /// The attribute is available in the fragment shader of a material by adding `in float name;`, `in vec2 name;`, `in vec3 name;` or `in vec4 name;`,
+    /// depending on the number of components of the data type, optionally prefixed with `flat` to disable interpolation.
+    /// The values are converted to floating point, so integers larger than 2^24 are not represented exactly.
+    /// A custom attribute of the instances with the same name (see [Instances::custom_attributes]) takes precedence over this attribute.
+    ///
+    /// # Panic
+    /// Will panic if the number of values is not the same as the number of vertices.
+    ///
+    pub fn set_custom_attribute<T: BufferDataType>(&mut self, name: &str, data: &[T]) {
+        let vertex_count = self.vertex_buffers.get("position").unwrap().vertex_count();
+        if data.len() as u32 != vertex_count {
+            panic!(
+                "the custom attribute {} has {} values, but the mesh has {} vertices",
+                name,
+                data.len(),
+                vertex_count
+            );
+        }
+        self.custom_vertex_buffers.insert(
+            name.to_string(),
+            Arc::new(VertexBuffer::new_with_data(&self.context, data)),
+        );
+    }
+
+    ///
+    /// Removes the custom attribute with the given name, see [InstancedMesh::set_custom_attribute].
+    ///
+    pub fn remove_custom_attribute(&mut self, name: &str) {
+        self.custom_vertex_buffers.remove(name);
+    }
+
     /// Returns the number of instances that is rendered.
     pub fn instance_count(&self) -> u32 {
         self.instance_count
@@ -86,6 +162,28 @@ impl InstancedMesh {
         self.update_aabb();
     }
 
+    ///
+    /// Returns whether the instances are sorted back to front before rendering with a transparent material, see [InstancedMesh::set_depth_sorting].
+    ///
+    pub fn depth_sorting(&self) -> bool {
+        self.depth_sorting
+    }
+
+    ///
+    /// Set whether the instances should be sorted back to front, based on the distance from the camera to the center of each instance, before rendering with a [MaterialType::Transparent] material.
+    /// This is needed for overlapping transparent instances to blend correctly. Only the part of the instance buffers where the order changed is updated,
+    /// so the cost is small when the camera moves a little between frames. Disabled by default.
+    ///
+    pub fn set_depth_sorting(&mut self, depth_sorting: bool) {
+        if self.depth_sorting && !depth_sorting {
+            // Restore the original order
+            let order = (0..self.instance_transforms.len() as u32).collect::<Vec<_>>();
+            self.fill_instance_buffers(&mut self.instance_buffers.write().unwrap(), 0, &order);
+            self.instance_order = RwLock::new(order);
+        }
+        self.depth_sorting = depth_sorting;
+    }
+
     ///
     /// Update the instances.
     ///
@@ -93,7 +191,7 @@ impl InstancedMesh {
         #[cfg(debug_assertions)]
         instances.validate().expect("invalid instances");
         self.instance_count = instances.count();
-        self.instance_buffers.clear();
+        self.instances = instances.clone();
         self.instance_transforms = (0..self.instance_count as usize)
             .map(|i| {
                 Mat4::from_translation(instances.translations[i])
@@ -110,16 +208,40 @@ impl InstancedMesh {
             })
             .collect::<Vec<_>>();
 
+        let order = (0..self.instance_count).collect::<Vec<_>>();
+        let mut instance_buffers = HashMap::new();
+        self.fill_instance_buffers(&mut instance_buffers, 0, &order);
+        self.instance_buffers = RwLock::new(instance_buffers);
+        self.instance_order = RwLock::new(order);
+        self.update_aabb();
+    }
+
+    // Fills the instance buffers, starting at the instance with index `offset`, with the attributes of the instances given by `order`.
+    // Buffers that do not exist are created, in which case `offset` must be zero and `order` must contain all instances.
+    fn fill_instance_buffers(
+        &self,
+        instance_buffers: &mut HashMap<String, InstanceBuffer>,
+        offset: u32,
+        order: &[u32],
+    ) {
+        let instances = &self.instances;
         if instances.rotations.is_none() && instances.scales.is_none() {
-            self.instance_buffers.insert(
-                "instance_translation".to_string(),
-                InstanceBuffer::new_with_data(&self.context, &instances.translations),
+            fill_instance_buffer(
+                &self.context,
+                instance_buffers,
+                "instance_translation",
+                offset,
+                &order
+                    .iter()
+                    .map(|i| instances.translations[*i as usize])
+                    .collect::<Vec<_>>(),
             );
         } else {
             let mut row1 = Vec::new();
             let mut row2 = Vec::new();
             let mut row3 = Vec::new();
-            for geometry_transform in self.instance_transforms.iter() {
+            for i in order.iter() {
+                let geometry_transform = &self.instance_transforms[*i as usize];
                 row1.push(vec4(
                     geometry_transform.x.x,
                     geometry_transform.y.x,
@@ -141,25 +263,16 @@ impl InstancedMesh {
                     geometry_transform.w.z,
                 ));
             }
-
-            self.instance_buffers.insert(
-                "row1".to_string(),
-                InstanceBuffer::new_with_data(&self.context, &row1),
-            );
-            self.instance_buffers.insert(
-                "row2".to_string(),
-                InstanceBuffer::new_with_data(&self.context, &row2),
-            );
-            self.instance_buffers.insert(
-                "row3".to_string(),
-                InstanceBuffer::new_with_data(&self.context, &row3),
-            );
+            fill_instance_buffer(&self.context, instance_buffers, "row1", offset, &row1);
+            fill_instance_buffer(&self.context, instance_buffers, "row2", offset, &row2);
+            fill_instance_buffer(&self.context, instance_buffers, "row3", offset, &row3);
         }
 
         if let Some(texture_transforms) = &instances.texture_transforms {
             let mut instance_tex_transform1 = Vec::new();
             let mut instance_tex_transform2 = Vec::new();
-            for texture_transform in texture_transforms.iter() {
+            for i in order.iter() {
+                let texture_transform = &texture_transforms[*i as usize];
                 instance_tex_transform1.push(vec3(
                     texture_transform.x.x,
                     texture_transform.y.x,
@@ -171,22 +284,114 @@ impl InstancedMesh {
                     texture_transform.z.y,
                 ));
             }
-            self.instance_buffers.insert(
-                "tex_transform_row1".to_string(),
-                InstanceBuffer::new_with_data(&self.context, &instance_tex_transform1),
+            fill_instance_buffer(
+                &self.context,
+                instance_buffers,
+                "tex_transform_row1",
+                offset,
+                &instance_tex_transform1,
             );
-            self.instance_buffers.insert(
-                "tex_transform_row2".to_string(),
-                InstanceBuffer::new_with_data(&self.context, &instance_tex_transform2),
+            fill_instance_buffer(
+                &self.context,
+                instance_buffers,
+                "tex_transform_row2",
+                offset,
+                &instance_tex_transform2,
             );
         }
         if let Some(instance_colors) = &instances.colors {
-            self.instance_buffers.insert(
-                "instance_color".to_string(),
-                InstanceBuffer::new_with_data(&self.context, &instance_colors),
+            fill_instance_buffer(
+                &self.context,
+                instance_buffers,
+                "instance_color",
+                offset,
+                &order
+                    .iter()
+                    .map(|i| instance_colors[*i as usize])
+                    .collect::<Vec<_>>(),
             );
         }
-        self.update_aabb();
+        for (name, attribute) in instances.custom_attributes.iter() {
+            let name = format!("custom_instance_{}", name);
+            match attribute {
+                InstanceAttribute::Float(values) => {
+                    fill_instance_buffer(
+                        &self.context,
+                        instance_buffers,
+                        &name,
+                        offset,
+                        &reorder(values, order),
+                    );
+                }
+                InstanceAttribute::Vec2(values) => {
+                    fill_instance_buffer(
+                        &self.context,
+                        instance_buffers,
+                        &name,
+                        offset,
+                        &reorder(values, order),
+                    );
+                }
+                InstanceAttribute::Vec3(values) => {
+                    fill_instance_buffer(
+                        &self.context,
+                        instance_buffers,
+                        &name,
+                        offset,
+                        &reorder(values, order),
+                    );
+                }
+                InstanceAttribute::Vec4(values) => {
+                    fill_instance_buffer(
+                        &self.context,
+                        instance_buffers,
+                        &name,
+                        offset,
+                        &reorder(values, order),
+                    );
+                }
+            }
+        }
+    }
+
+    // Sorts the instances back to front with respect to the given position and updates the part of the instance buffers where the order changed.
+    // Instances that are not rendered (see set_instance_count) are placed last.
+    fn sort_instances(&self, position: Vec3) {
+        let distances = (0..self.instance_transforms.len())
+            .map(|i| {
+                if i < self.instance_count as usize {
+                    position.distance2(self.instance_center(i))
+                } else {
+                    f32::NEG_INFINITY
+                }
+            })
+            .collect::<Vec<_>>();
+        let mut order = self.instance_order.write().unwrap();
+        let mut new_order = order.clone();
+        new_order.sort_by(|a, b| {
+            distances[*b as usize]
+                .partial_cmp(&distances[*a as usize])
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        let first = (0..order.len()).find(|i| order[*i] != new_order[*i]);
+        let last = (0..order.len()).rev().find(|i| order[*i] != new_order[*i]);
+        if let (Some(first), Some(last)) = (first, last) {
+            self.fill_instance_buffers(
+                &mut self.instance_buffers.write().unwrap(),
+                first as u32,
+                &new_order[first..=last],
+            );
+            *order = new_order;
+        }
+    }
+
+    fn instance_center(&self, index: usize) -> Vec3 {
+        let center = self.aabb_local.center().extend(1.0);
+        if self.instances.rotations.is_none() && self.instances.scales.is_none() {
+            (self.transformation * center).truncate() + self.instances.translations[index]
+        } else {
+            (self.transformation * self.instance_transforms[index] * center).truncate()
+        }
     }
 
     fn update_aabb(&mut self) {
@@ -199,15 +404,21 @@ impl InstancedMesh {
         self.aabb = aabb;
     }
 
-    fn vertex_shader_source(&self, fragment_shader_source: &str) -> String {
+    fn vertex_shader_source(
+        &self,
+        fragment_shader_source: &str,
+        material_vertex_source: Option<String>,
+    ) -> String {
+        let instance_buffers = self.instance_buffers.read().unwrap();
         let use_positions = fragment_shader_source.find("in vec3 pos;").is_some();
-        let use_normals = fragment_shader_source.find("in vec3 nor;").is_some();
+        let use_normals = fragment_shader_source.find("in vec3 nor;").is_some()
+            || (material_vertex_source.is_some() && self.vertex_buffers.contains_key("normal"));
         let use_tangents = fragment_shader_source.find("in vec3 tang;").is_some();
         let use_uvs = fragment_shader_source.find("in vec2 uvs;").is_some();
         let use_colors = fragment_shader_source.find("in vec4 col;").is_some();
         format!(
-            "{}{}{}{}{}{}{}{}{}",
-            if self.instance_buffers.contains_key("instance_translation") {
+            "{}{}{}{}{}{}{}{}{}{}{}",
+            if instance_buffers.contains_key("instance_translation") {
                 "#define USE_INSTANCE_TRANSLATIONS\n"
             } else {
                 "#define USE_INSTANCE_TRANSFORMS\n"
@@ -232,11 +443,11 @@ impl InstancedMesh {
             },
             if use_uvs { "#define USE_UVS\n" } else { "" },
             if use_colors {
-                if self.instance_buffers.contains_key("instance_color")
+                if instance_buffers.contains_key("instance_color")
                     && self.vertex_buffers.contains_key("color")
                 {
                     "#define USE_COLORS\n#define USE_VERTEX_COLORS\n#define USE_INSTANCE_COLORS\n"
-                } else if self.instance_buffers.contains_key("instance_color") {
+                } else if instance_buffers.contains_key("instance_color") {
                     "#define USE_COLORS\n#define USE_INSTANCE_COLORS\n"
                 } else {
                     "#define USE_COLORS\n#define USE_VERTEX_COLORS\n"
@@ -244,17 +455,58 @@ impl InstancedMesh {
             } else {
                 ""
             },
-            if self.instance_buffers.contains_key("tex_transform_row1") {
+            if instance_buffers.contains_key("tex_transform_row1") {
                 "#define USE_INSTANCE_TEXTURE_TRANSFORMATION\n"
             } else {
                 ""
             },
             include_str!("../../core/shared.frag"),
+            super::custom_attributes_source(
+                fragment_shader_source,
+                super::custom_vertex_attributes(&self.custom_vertex_buffers)
+                    .filter(|(_, name, _)| !self.instances.custom_attributes.contains_key(name))
+                    .chain(
+                        self.instances
+                            .custom_attributes
+                            .iter()
+                            .map(|(name, attribute)| {
+                                (
+                                    format!("custom_instance_{}", name),
+                                    name.clone(),
+                                    attribute.component_count(),
+                                )
+                            }),
+                    ),
+            ),
+            material_vertex_source
+                .map(|source| format!("#define USE_MATERIAL_VERTEX\n{}\n", source))
+                .unwrap_or_default(),
             include_str!("shaders/mesh.vert"),
         )
     }
 }
 
+fn reorder<T: Copy>(values: &[T], order: &[u32]) -> Vec<T> {
+    order.iter().map(|i| values[*i as usize]).collect()
+}
+
+fn fill_instance_buffer<T: BufferDataType>(
+    context: &Context,
+    instance_buffers: &mut HashMap<String, InstanceBuffer>,
+    name: &str,
+    offset: u32,
+    data: &[T],
+) {
+    if let Some(buffer) = instance_buffers.get_mut(name) {
+        buffer.fill_subset(offset, data);
+    } else {
+        instance_buffers.insert(
+            name.to_string(),
+            InstanceBuffer::new_with_data(context, data),
+        );
+    }
+}
+
 impl Geometry for InstancedMesh {
     fn aabb(&self) -> AxisAlignedBoundingBox {
         self.aabb
@@ -266,13 +518,20 @@ impl Geometry for InstancedMesh {
         camera: &Camera,
         lights: &[&dyn Light],
     ) {
+        if self.depth_sorting && material.material_type() == MaterialType::Transparent {
+            self.sort_instances(camera.position());
+        }
         let fragment_shader_source = material.fragment_shader_source(
             self.vertex_buffers.contains_key("color")
-                || self.instance_buffers.contains_key("instance_color"),
+                || self
+                    .instance_buffers
+                    .read()
+                    .unwrap()
+                    .contains_key("instance_color"),
             lights,
         );
         self.context.program(
-            &self.vertex_shader_source(&fragment_shader_source),
+            &self.vertex_shader_source(&fragment_shader_source, material.vertex_shader_source()),
             &fragment_shader_source,
             |program| {
                 material.use_uniforms(program, camera, lights);
@@ -293,7 +552,9 @@ impl Geometry for InstancedMesh {
                         );
                     }
                 }
+                super::use_custom_attributes(program, &self.custom_vertex_buffers);
 
+                let instance_buffers = self.instance_buffers.read().unwrap();
                 for attribute_name in [
                     "instance_translation",
                     "row1",
@@ -306,11 +567,20 @@ impl Geometry for InstancedMesh {
                     if program.requires_attribute(attribute_name) {
                         program.use_instance_attribute(
                             attribute_name,
-                            self.instance_buffers
+                            instance_buffers
                             .get(attribute_name).expect(&format!("the render call requires the {} instance buffer which is missing on the given geometry", attribute_name))
                         );
                     }
                 }
+                for name in self.instances.custom_attributes.keys() {
+                    let attribute_name = format!("custom_instance_{}", name);
+                    if program.requires_attribute(&attribute_name) {
+                        program.use_instance_attribute(
+                            &attribute_name,
+                            instance_buffers.get(&attribute_name).unwrap(),
+                        );
+                    }
+                }
 
                 if let Some(ref index_buffer) = self.index_buffer {
                     program.draw_elements_instanced(
@@ -352,6 +622,52 @@ pub struct Instances {
     pub texture_transforms: Option<Vec<Mat3>>,
     /// Colors multiplied onto the base color of each instance.
     pub colors: Option<Vec<Color>>,
+    /// Custom attributes of each instance, for example a health value or an index, accessed by name in the fragment shader of a material, see [InstanceAttribute].
+    /// If a custom attribute of the mesh (see [InstancedMesh::set_custom_attribute]) has the same name, the instance attribute is used.
+    pub custom_attributes: HashMap<String, InstanceAttribute>,
+}
+
+///
+/// The values of a custom attribute for each instance, see [Instances::custom_attributes].
+/// The attribute is available in the fragment shader of a material by adding `in float name;`, `in vec2 name;`, `in vec3 name;` or `in vec4 name;`, depending on the variant,
+/// where `name` is the name of the attribute. Use `flat in` to avoid interpolation, for example when the values are indices.
+///
+#[derive(Clone, Debug)]
+pub enum InstanceAttribute {
+    /// One scalar value for each instance.
+    Float(Vec<f32>),
+    /// One two-dimensional vector for each instance.
+    Vec2(Vec<Vec2>),
+    /// One three-dimensional vector for each instance.
+    Vec3(Vec<Vec3>),
+    /// One four-dimensional vector for each instance.
+    Vec4(Vec<Vec4>),
+}
+
+impl InstanceAttribute {
+    /// Returns the number of values, which should be the same as the number of instances.
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Float(values) => values.len(),
+            Self::Vec2(values) => values.len(),
+            Self::Vec3(values) => values.len(),
+            Self::Vec4(values) => values.len(),
+        }
+    }
+
+    /// Returns true if there are no values.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn component_count(&self) -> u32 {
+        match self {
+            Self::Float(_) => 1,
+            Self::Vec2(_) => 2,
+            Self::Vec3(_) => 3,
+            Self::Vec4(_) => 4,
+        }
+    }
 }
 
 impl Instances {
@@ -380,6 +696,9 @@ impl Instances {
         buffer_check(self.rotations.as_ref().map(|b| b.len()), "rotations")?;
         buffer_check(self.scales.as_ref().map(|b| b.len()), "scales")?;
         buffer_check(self.colors.as_ref().map(|b| b.len()), "colors")?;
+        for (name, attribute) in self.custom_attributes.iter() {
+            buffer_check(Some(attribute.len()), name)?;
+        }
         buffer_check(Some(self.translations.len()), "translations")?;
 
         Ok(())
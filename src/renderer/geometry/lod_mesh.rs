@@ -0,0 +1,355 @@
+use crate::core::*;
+use crate::renderer::*;
+
+///
+/// Defines how the level of a [LodMesh] is chosen.
+///
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum LodMetric {
+    /// The threshold of each level is the maximum distance from the camera to the center of the mesh at which the level is used.
+    Distance,
+    /// The threshold of each level is the minimum height of the bounding sphere of the mesh on screen, relative to the height of the viewport, at which the level is used.
+    ScreenSize,
+}
+
+///
+/// A level-of-detail [Geometry] which renders one of several [Mesh]es depending on the distance to the camera or the size on screen (see [LodMetric]).
+/// The levels are ordered from most to least detailed, and the level used is the first level for which the threshold is satisfied.
+/// If no threshold is satisfied, the imposter is used if set (see [LodMesh::set_imposter]), otherwise the last level is used.
+/// The imposter only replaces the last level when rendering with the material it was created with, for other materials, for example in the depth and shadow passes,
+/// the last level is used instead.
+///
+/// Optionally, two levels are blended over a range before the threshold to avoid popping (see [LodMesh::set_cross_fade]).
+/// This is done by rendering each of the two levels into complementary sets of pixels defined by a dither pattern.
+///
+pub struct LodMesh {
+    levels: Vec<(Mesh, f32)>,
+    metric: LodMetric,
+    cross_fade: Option<f32>,
+    imposter: Option<(Imposters, Vec3, String)>,
+    transformation: Mat4,
+}
+
+impl LodMesh {
+    ///
+    /// Creates a new level-of-detail mesh from the given list of [CpuMesh]es and thresholds, ordered from most to least detailed.
+    ///
+    pub fn new(context: &Context, levels: &[(CpuMesh, f32)], metric: LodMetric) -> Self {
+        Self::from_meshes(
+            levels
+                .iter()
+                .map(|(cpu_mesh, threshold)| (Mesh::new(context, cpu_mesh), *threshold))
+                .collect(),
+            metric,
+        )
+    }
+
+    ///
+    /// Creates a new level-of-detail mesh where the first level is the given [CpuMesh] and the remaining levels are generated from it using [simplify_mesh].
+    /// The threshold of the first level is given by `threshold` and the target and threshold of each of the remaining levels are given by `levels`.
+    ///
+    pub fn new_simplified(
+        context: &Context,
+        cpu_mesh: &CpuMesh,
+        threshold: f32,
+        levels: &[(SimplificationTarget, f32)],
+        metric: LodMetric,
+    ) -> Self {
+        let mut meshes = vec![(Mesh::new(context, cpu_mesh), threshold)];
+        for (target, threshold) in levels {
+            meshes.push((
+                Mesh::new(context, &simplify_mesh(cpu_mesh, *target)),
+                *threshold,
+            ));
+        }
+        Self::from_meshes(meshes, metric)
+    }
+
+    ///
+    /// Creates a new level-of-detail mesh from the given list of [Mesh]es and thresholds, ordered from most to least detailed.
+    /// The transformation of the first mesh is used for all levels.
+    ///
+    /// # Panic
+    /// Will panic if no levels are given.
+    ///
+    pub fn from_meshes(levels: Vec<(Mesh, f32)>, metric: LodMetric) -> Self {
+        let transformation = levels
+            .first()
+            .expect("a level-of-detail mesh needs at least one level")
+            .0
+            .transformation();
+        let mut lod_mesh = Self {
+            levels,
+            metric,
+            cross_fade: None,
+            imposter: None,
+            transformation,
+        };
+        lod_mesh.set_transformation(transformation);
+        lod_mesh
+    }
+
+    ///
+    /// Returns the local to world transformation applied to all levels.
+    ///
+    pub fn transformation(&self) -> Mat4 {
+        self.transformation
+    }
+
+    ///
+    /// Set the local to world transformation applied to all levels.
+    /// Only the translation is applied to the imposter, so the imposter needs to be updated if the rotation or scale changes.
+    ///
+    pub fn set_transformation(&mut self, transformation: Mat4) {
+        self.transformation = transformation;
+        for (mesh, _) in self.levels.iter_mut() {
+            mesh.set_transformation(transformation);
+        }
+        if let Some((ref mut imposter, origin, _)) = self.imposter {
+            imposter.set_positions(&[transformation.w.truncate() - origin]);
+        }
+    }
+
+    ///
+    /// Returns the number of mesh levels, not including the imposter.
+    ///
+    pub fn level_count(&self) -> usize {
+        self.levels.len()
+    }
+
+    ///
+    /// Returns the threshold of each level.
+    ///
+    pub fn thresholds(&self) -> Vec<f32> {
+        self.levels
+            .iter()
+            .map(|(_, threshold)| *threshold)
+            .collect()
+    }
+
+    ///
+    /// Set the threshold of each level. Any thresholds beyond the number of levels are ignored.
+    ///
+    pub fn set_thresholds(&mut self, thresholds: &[f32]) {
+        for ((_, threshold), new_threshold) in self.levels.iter_mut().zip(thresholds) {
+            *threshold = *new_threshold;
+        }
+    }
+
+    ///
+    /// Returns the relative range in which two levels are blended, see [LodMesh::set_cross_fade].
+    ///
+    pub fn cross_fade(&self) -> Option<f32> {
+        self.cross_fade
+    }
+
+    ///
+    /// Set the range in which two levels are blended relative to the threshold, for example `Some(0.1)` blends a level into the next
+    /// when the distance is between 90% and 100% of the threshold. Use `None` to switch level abruptly, which is the default.
+    ///
+    pub fn set_cross_fade(&mut self, cross_fade: Option<f32>) {
+        self.cross_fade = cross_fade;
+    }
+
+    ///
+    /// Set the imposter used beyond the threshold of the last level by rendering the most detailed level with the given material and lights into an [Imposters].
+    /// The imposter is only used when rendering with a material with the same shader as the given material, since it only contains the colours of that material.
+    /// For other materials, for example when picking, in the depth and shadow passes and with [RenderTarget::render_with_oit], the last level is used instead.
+    ///
+    pub fn set_imposter(
+        &mut self,
+        context: &Context,
+        material: &dyn Material,
+        lights: &[&dyn Light],
+        max_texture_size: u32,
+    ) {
+        let imposter = Imposters::new(
+            context,
+            &[vec3(0.0, 0.0, 0.0)],
+            &Gm::new(&self.levels[0].0, material),
+            lights,
+            max_texture_size,
+        );
+        self.imposter = Some((
+            imposter,
+            self.transformation.w.truncate(),
+            material.fragment_shader_source(false, &[]),
+        ));
+    }
+
+    ///
+    /// Removes the imposter, if any.
+    ///
+    pub fn remove_imposter(&mut self) {
+        self.imposter = None;
+    }
+
+    ///
+    /// Returns the index of the level used when rendering with the given camera, where an index equal to the number of levels is the imposter,
+    /// together with the amount in the range `[0..1]` to blend into the next level.
+    ///
+    pub fn level(&self, camera: &Camera) -> (usize, f32) {
+        let aabb = self.aabb();
+        if aabb.is_empty() {
+            return (0, 0.0);
+        }
+        let center = aabb.center();
+        let distance = camera.position().distance(center);
+        // Both metrics are converted to a value which increases with the distance
+        let (value, to_value): (f32, fn(f32) -> f32) = match self.metric {
+            LodMetric::Distance => (distance, |t| t),
+            LodMetric::ScreenSize => {
+                let radius = 0.5 * aabb.size().magnitude();
+                let view_position = camera.view() * center.extend(1.0);
+                let w = (camera.projection() * view_position).w.abs().max(0.0001);
+                let screen_size = radius * camera.projection().y.y / w;
+                (1.0 / screen_size.max(0.0001), |t| 1.0 / t.max(0.0001))
+            }
+        };
+        let final_level = if self.imposter.is_some() {
+            self.levels.len()
+        } else {
+            self.levels.len() - 1
+        };
+        let level = self
+            .levels
+            .iter()
+            .position(|(_, threshold)| value < to_value(*threshold))
+            .unwrap_or(self.levels.len())
+            .min(final_level);
+        let mut fade = 0.0;
+        if let Some(range) = self.cross_fade {
+            if level < final_level && range > 0.0 {
+                let threshold = to_value(self.levels[level].1);
+                let start = threshold * (1.0 - range);
+                if value > start {
+                    fade = ((value - start) / (threshold - start)).clamp(0.0, 1.0);
+                }
+            }
+        }
+        (level, fade)
+    }
+
+    fn render_level(
+        &self,
+        level: usize,
+        material: &dyn Material,
+        camera: &Camera,
+        lights: &[&dyn Light],
+    ) {
+        if let Some((mesh, _)) = self.levels.get(level) {
+            mesh.render_with_material(material, camera, lights);
+        } else if let Some((ref imposter, _, _)) = self.imposter {
+            imposter.render_with_material(material, camera, lights);
+        }
+    }
+
+    // The imposter only contains the colours of the material it was created with, so it is not used with other materials.
+    fn uses_imposter(&self, material: &dyn Material) -> bool {
+        self.imposter.as_ref().map_or(false, |(_, _, source)| {
+            *source == material.fragment_shader_source(false, &[])
+        })
+    }
+}
+
+impl Geometry for LodMesh {
+    fn aabb(&self) -> AxisAlignedBoundingBox {
+        let mut aabb = AxisAlignedBoundingBox::EMPTY;
+        for (mesh, _) in self.levels.iter() {
+            aabb.expand_with_aabb(&mesh.aabb());
+        }
+        aabb
+    }
+
+    fn render_with_material(
+        &self,
+        material: &dyn Material,
+        camera: &Camera,
+        lights: &[&dyn Light],
+    ) {
+        let (mut level, mut fade) = self.level(camera);
+        let use_imposter = self.uses_imposter(material);
+        if !use_imposter && level + 1 >= self.levels.len() {
+            level = self.levels.len() - 1;
+            fade = 0.0;
+        }
+        if fade > 0.0 {
+            let imposter_material = self
+                .imposter
+                .as_ref()
+                .filter(|_| use_imposter)
+                .map(|(imposter, _, _)| imposter.material());
+            let material_of = |level: usize| {
+                if level < self.levels.len() {
+                    material
+                } else {
+                    imposter_material.unwrap()
+                }
+            };
+            self.render_level(
+                level,
+                &CrossFadeMaterial {
+                    material: material_of(level),
+                    fade: 1.0 - fade,
+                    invert: false,
+                },
+                camera,
+                lights,
+            );
+            self.render_level(
+                level + 1,
+                &CrossFadeMaterial {
+                    material: material_of(level + 1),
+                    fade: 1.0 - fade,
+                    invert: true,
+                },
+                camera,
+                lights,
+            );
+        } else if level < self.levels.len() {
+            self.render_level(level, material, camera, lights);
+        } else if let Some((ref imposter, _, _)) = self.imposter {
+            self.render_level(level, imposter.material(), camera, lights);
+        }
+    }
+}
+
+struct CrossFadeMaterial<'a> {
+    material: &'a dyn Material,
+    fade: f32,
+    invert: bool,
+}
+
+impl Material for CrossFadeMaterial<'_> {
+    fn fragment_shader_source(&self, use_vertex_colors: bool, lights: &[&dyn Light]) -> String {
+        format!(
+            "{}{}",
+            self.material
+                .fragment_shader_source(use_vertex_colors, lights)
+                .replacen("void main()", "void lod_main()", 1),
+            include_str!("shaders/lod_cross_fade.frag")
+        )
+    }
+
+    fn use_uniforms(&self, program: &Program, camera: &Camera, lights: &[&dyn Light]) {
+        self.material.use_uniforms(program, camera, lights);
+        program.use_uniform("lodFade", self.fade);
+        program.use_uniform("lodFadeInvert", if self.invert { 1i32 } else { 0i32 });
+    }
+
+    fn render_states(&self) -> RenderStates {
+        self.material.render_states()
+    }
+
+    fn material_type(&self) -> MaterialType {
+        self.material.material_type()
+    }
+
+    fn sort_key(&self) -> u64 {
+        self.material.sort_key()
+    }
+
+    fn vertex_shader_source(&self) -> Option<String> {
+        self.material.vertex_shader_source()
+    }
+}
@@ -1,13 +1,18 @@
 use crate::core::*;
 use crate::renderer::*;
 use std::collections::HashMap;
+use std::sync::Arc;
 
 ///
 /// A triangle mesh [Geometry].
+/// Cloning a mesh is cheap since the clone shares the GPU buffers with the original mesh, only the transformations are copied.
+/// Objects with meshes that share GPU buffers can be rendered in a single draw call, see [batch_meshes] and [MeshBatching].
 ///
+#[derive(Clone)]
 pub struct Mesh {
-    vertex_buffers: HashMap<String, VertexBuffer>,
-    index_buffer: Option<ElementBuffer>,
+    vertex_buffers: Arc<HashMap<String, VertexBuffer>>,
+    index_buffer: Option<Arc<ElementBuffer>>,
+    custom_vertex_buffers: HashMap<String, Arc<VertexBuffer>>,
     context: Context,
     aabb: AxisAlignedBoundingBox,
     aabb_local: AxisAlignedBoundingBox,
@@ -24,8 +29,30 @@ impl Mesh {
         let aabb = cpu_mesh.compute_aabb();
         Self {
             context: context.clone(),
-            index_buffer: super::index_buffer_from_mesh(context, cpu_mesh),
-            vertex_buffers: super::vertex_buffers_from_mesh(context, cpu_mesh),
+            index_buffer: super::index_buffer_from_mesh(context, cpu_mesh).map(Arc::new),
+            vertex_buffers: Arc::new(super::vertex_buffers_from_mesh(context, cpu_mesh)),
+            custom_vertex_buffers: HashMap::new(),
+            aabb,
+            aabb_local: aabb.clone(),
+            transformation: Mat4::identity(),
+            texture_transform: Mat3::identity(),
+        }
+    }
+
+    ///
+    /// Creates a new triangle mesh from the given [CpuMesh] where all vertex data is interleaved in a single buffer on the GPU, see [VertexBuffer::new_interleaved].
+    /// To save memory, the normals and tangents are stored as normalized 8 bit integers, which is precise enough for most meshes,
+    /// while the positions and uv coordinates are kept as 32 bit floats.
+    ///
+    pub fn new_interleaved(context: &Context, cpu_mesh: &CpuMesh) -> Self {
+        let aabb = cpu_mesh.compute_aabb();
+        Self {
+            context: context.clone(),
+            index_buffer: super::index_buffer_from_mesh(context, cpu_mesh).map(Arc::new),
+            vertex_buffers: Arc::new(super::interleaved_vertex_buffers_from_mesh(
+                context, cpu_mesh,
+            )),
+            custom_vertex_buffers: HashMap::new(),
             aabb,
             aabb_local: aabb.clone(),
             transformation: Mat4::identity(),
@@ -74,7 +101,7 @@ impl Mesh {
     ///
     /// Get the texture transform applied to the uv coordinates of the model.
     ///
-    pub fn texture_transform(&mut self) -> &Mat3 {
+    pub fn texture_transform(&self) -> &Mat3 {
         &self.texture_transform
     }
 
@@ -85,14 +112,87 @@ impl Mesh {
         self.texture_transform = texture_transform;
     }
 
-    fn vertex_shader_source(fragment_shader_source: &str) -> String {
+    ///
+    /// Set a custom attribute with the given name and a value for each vertex, for example a temperature or an id.
+    /// The attribute is available in the fragment shader of a material by adding `in float name;`, `in vec2 name;`, `in vec3 name;` or `in vec4 name;`,
+    /// depending on the number of components of the data type, optionally prefixed with `flat` to disable interpolation.
+    /// The values are converted to floating point, so integers larger than 2^24 are not represented exactly.
+    ///
+    /// # Panic
+    /// Will panic if the number of values is not the same as the number of vertices.
+    ///
+    pub fn set_custom_attribute<T: BufferDataType>(&mut self, name: &str, data: &[T]) {
+        let vertex_count = self.vertex_buffers.get("position").unwrap().vertex_count();
+        if data.len() as u32 != vertex_count {
+            panic!(
+                "the custom attribute {} has {} values, but the mesh has {} vertices",
+                name,
+                data.len(),
+                vertex_count
+            );
+        }
+        self.custom_vertex_buffers.insert(
+            name.to_string(),
+            Arc::new(VertexBuffer::new_with_data(&self.context, data)),
+        );
+    }
+
+    ///
+    /// Removes the custom attribute with the given name, see [Mesh::set_custom_attribute].
+    ///
+    pub fn remove_custom_attribute(&mut self, name: &str) {
+        self.custom_vertex_buffers.remove(name);
+    }
+
+    ///
+    /// Returns true if this mesh shares GPU buffers with the other mesh, ie. if one is a clone of the other and they have the same custom attributes (see [Mesh::set_custom_attribute]).
+    ///
+    pub fn shares_buffers_with(&self, other: &Mesh) -> bool {
+        self.buffers_id() == other.buffers_id()
+    }
+
+    pub(super) fn buffers(
+        &self,
+    ) -> (
+        Arc<HashMap<String, VertexBuffer>>,
+        Option<Arc<ElementBuffer>>,
+        HashMap<String, Arc<VertexBuffer>>,
+        AxisAlignedBoundingBox,
+    ) {
+        (
+            self.vertex_buffers.clone(),
+            self.index_buffer.clone(),
+            self.custom_vertex_buffers.clone(),
+            self.aabb_local,
+        )
+    }
+
+    // Identifies the vertex buffers and custom attribute buffers, so meshes with the same id render the same geometry.
+    pub(in crate::renderer) fn buffers_id(&self) -> Vec<usize> {
+        let mut custom = self.custom_vertex_buffers.iter().collect::<Vec<_>>();
+        custom.sort_by(|(a, _), (b, _)| a.cmp(b));
+        std::iter::once(Arc::as_ptr(&self.vertex_buffers) as usize)
+            .chain(
+                custom
+                    .into_iter()
+                    .map(|(_, buffer)| Arc::as_ptr(buffer) as usize),
+            )
+            .collect()
+    }
+
+    fn vertex_shader_source(
+        &self,
+        fragment_shader_source: &str,
+        material_vertex_source: Option<String>,
+    ) -> String {
         let use_positions = fragment_shader_source.find("in vec3 pos;").is_some();
-        let use_normals = fragment_shader_source.find("in vec3 nor;").is_some();
+        let use_normals = fragment_shader_source.find("in vec3 nor;").is_some()
+            || (material_vertex_source.is_some() && self.vertex_buffers.contains_key("normal"));
         let use_tangents = fragment_shader_source.find("in vec3 tang;").is_some();
         let use_uvs = fragment_shader_source.find("in vec2 uvs;").is_some();
         let use_colors = fragment_shader_source.find("in vec4 col;").is_some();
         format!(
-            "{}{}{}{}{}{}{}",
+            "{}{}{}{}{}{}{}{}{}",
             if use_positions {
                 "#define USE_POSITIONS\n"
             } else {
@@ -118,6 +218,13 @@ impl Mesh {
                 ""
             },
             include_str!("../../core/shared.frag"),
+            super::custom_attributes_source(
+                fragment_shader_source,
+                super::custom_vertex_attributes(&self.custom_vertex_buffers),
+            ),
+            material_vertex_source
+                .map(|source| format!("#define USE_MATERIAL_VERTEX\n{}\n", source))
+                .unwrap_or_default(),
             include_str!("shaders/mesh.vert"),
         )
     }
@@ -137,7 +244,7 @@ impl Geometry for Mesh {
         let fragment_shader_source =
             material.fragment_shader_source(self.vertex_buffers.contains_key("color"), lights);
         self.context.program(
-            &Self::vertex_shader_source(&fragment_shader_source),
+            &self.vertex_shader_source(&fragment_shader_source, material.vertex_shader_source()),
             &fragment_shader_source,
             |program| {
                 material.use_uniforms(program, camera, lights);
@@ -159,6 +266,8 @@ impl Geometry for Mesh {
                     }
                 }
 
+                super::use_custom_attributes(program, &self.custom_vertex_buffers);
+
                 if let Some(ref index_buffer) = self.index_buffer {
                     program.draw_elements(material.render_states(), camera.viewport(), index_buffer)
                 } else {
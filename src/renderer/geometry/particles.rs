@@ -182,14 +182,19 @@ impl ParticleSystem {
         }
     }
 
-    fn vertex_shader_source(&self, fragment_shader_source: &str) -> String {
+    fn vertex_shader_source(
+        &self,
+        fragment_shader_source: &str,
+        material_vertex_source: Option<String>,
+    ) -> String {
         let use_positions = fragment_shader_source.find("in vec3 pos;").is_some();
-        let use_normals = fragment_shader_source.find("in vec3 nor;").is_some();
+        let use_normals = fragment_shader_source.find("in vec3 nor;").is_some()
+            || (material_vertex_source.is_some() && self.vertex_buffers.contains_key("normal"));
         let use_tangents = fragment_shader_source.find("in vec3 tang;").is_some();
         let use_uvs = fragment_shader_source.find("in vec2 uvs;").is_some();
         let use_colors = fragment_shader_source.find("in vec4 col;").is_some();
         format!(
-            "#define PARTICLES\n{}{}{}{}{}{}{}{}",
+            "#define PARTICLES\n{}{}{}{}{}{}{}{}{}",
             if use_positions {
                 "#define USE_POSITIONS\n"
             } else {
@@ -228,6 +233,9 @@ impl ParticleSystem {
                 ""
             },
             include_str!("../../core/shared.frag"),
+            material_vertex_source
+                .map(|source| format!("#define USE_MATERIAL_VERTEX\n{}\n", source))
+                .unwrap_or_default(),
             include_str!("shaders/mesh.vert"),
         )
     }
@@ -250,7 +258,7 @@ impl Geometry for ParticleSystem {
             lights,
         );
         self.context.program(
-            &self.vertex_shader_source(&fragment_shader_source),
+            &self.vertex_shader_source(&fragment_shader_source, material.vertex_shader_source()),
             &fragment_shader_source,
             |program| {
                 material.use_uniforms(program, camera, lights);
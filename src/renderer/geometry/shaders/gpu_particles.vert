@@ -0,0 +1,42 @@
+in vec4 particle_position;
+in vec4 particle_velocity;
+uniform float particleSizes[16];
+uniform vec4 particleColors[16];
+#ifdef GPU_PARTICLES_BILLBOARD
+uniform vec3 particleCameraRight;
+uniform vec3 particleCameraUp;
+#endif
+
+// Returns the age of the particle relative to its lifetime in the range [0..1], or a negative value if the particle is dead
+float gpu_particle_life()
+{
+    return particle_position.w < particle_velocity.w ? particle_position.w / particle_velocity.w : -1.0;
+}
+
+float gpu_particle_size()
+{
+    float life = gpu_particle_life();
+    if (life < 0.0) {
+        return 0.0;
+    }
+    float x = life * 15.0;
+    int i = min(int(x), 15);
+    return mix(particleSizes[i], particleSizes[min(i + 1, 15)], x - float(i));
+}
+
+vec4 gpu_particle_color()
+{
+    float x = clamp(gpu_particle_life(), 0.0, 1.0) * 15.0;
+    int i = min(int(x), 15);
+    return mix(particleColors[i], particleColors[min(i + 1, 15)], x - float(i));
+}
+
+vec3 gpu_particle_position(vec3 localPosition)
+{
+    float size = gpu_particle_size();
+#ifdef GPU_PARTICLES_BILLBOARD
+    return particle_position.xyz + size * (localPosition.x * particleCameraRight + localPosition.y * particleCameraUp);
+#else
+    return particle_position.xyz + size * localPosition;
+#endif
+}
@@ -0,0 +1,107 @@
+uniform float deltaTime;
+uniform vec3 acceleration;
+uniform float drag;
+uniform int seed;
+uniform int particleCount;
+uniform int spawnStart;
+uniform int spawnCount;
+uniform mat4 emitterTransformation;
+uniform int emitterShape;
+uniform float emitterRadius;
+uniform float emitterAngle;
+uniform vec2 lifetimeRange;
+uniform vec2 speedRange;
+uniform sampler2D emitterSurface;
+
+in vec4 particle_position;
+in vec4 particle_velocity;
+
+out vec4 new_position;
+out vec4 new_velocity;
+
+uint rngState;
+
+uint hash(uint x)
+{
+    x ^= x >> 16;
+    x *= 0x7feb352du;
+    x ^= x >> 15;
+    x *= 0x846ca68bu;
+    x ^= x >> 16;
+    return x;
+}
+
+float random()
+{
+    rngState = hash(rngState);
+    return float(rngState >> 8) / 16777216.0;
+}
+
+vec3 random_direction()
+{
+    float z = 2.0 * random() - 1.0;
+    float phi = 6.28318530718 * random();
+    float r = sqrt(max(1.0 - z * z, 0.0));
+    return vec3(r * cos(phi), r * sin(phi), z);
+}
+
+void spawn(out vec3 position, out vec3 velocity, out float lifetime)
+{
+    vec3 direction;
+    if (emitterShape == 1) {
+        // Sphere
+        direction = random_direction();
+        position = direction * emitterRadius * pow(random(), 1.0 / 3.0);
+    } else if (emitterShape == 2) {
+        // Cone
+        float r = emitterRadius * sqrt(random());
+        float phi = 6.28318530718 * random();
+        position = vec3(r * cos(phi), 0.0, r * sin(phi));
+        float cosTheta = mix(1.0, cos(emitterAngle), random());
+        float sinTheta = sqrt(max(1.0 - cosTheta * cosTheta, 0.0));
+        phi = 6.28318530718 * random();
+        direction = vec3(sinTheta * cos(phi), cosTheta, sinTheta * sin(phi));
+    } else if (emitterShape == 3) {
+        // Mesh surface, the first row contains positions and the second row the corresponding normals
+        int sampleCount = textureSize(emitterSurface, 0).x;
+        int i = min(int(random() * float(sampleCount)), sampleCount - 1);
+        position = texelFetch(emitterSurface, ivec2(i, 0), 0).xyz;
+        direction = texelFetch(emitterSurface, ivec2(i, 1), 0).xyz;
+    } else {
+        // Point
+        position = vec3(0.0);
+        direction = random_direction();
+    }
+    vec4 p = emitterTransformation * vec4(position, 1.0);
+    position = p.xyz / p.w;
+    vec3 d = mat3(emitterTransformation) * direction;
+    direction = length(d) > 0.0 ? normalize(d) : vec3(0.0, 1.0, 0.0);
+    velocity = direction * mix(speedRange.x, speedRange.y, random());
+    lifetime = mix(lifetimeRange.x, lifetimeRange.y, random());
+}
+
+void main()
+{
+    vec3 position = particle_position.xyz;
+    float age = particle_position.w;
+    vec3 velocity = particle_velocity.xyz;
+    float lifetime = particle_velocity.w;
+
+    if (age < lifetime) {
+        velocity += acceleration * deltaTime;
+        velocity *= exp(-drag * deltaTime);
+        position += velocity * deltaTime;
+        age += deltaTime;
+    }
+
+    // The particles are spawned in a ring buffer, so the oldest particles are replaced first
+    int index = (gl_VertexID - spawnStart + particleCount) % particleCount;
+    if (index < spawnCount) {
+        rngState = hash(uint(gl_VertexID) ^ hash(uint(seed)));
+        spawn(position, velocity, lifetime);
+        age = 0.0;
+    }
+
+    new_position = vec4(position, age);
+    new_velocity = vec4(velocity, lifetime);
+}
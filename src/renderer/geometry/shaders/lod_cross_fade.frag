@@ -0,0 +1,17 @@
+
+uniform float lodFade;
+uniform int lodFadeInvert;
+
+const float lodDitherPattern[16] = float[16](0.0, 8.0, 2.0, 10.0, 12.0, 4.0, 14.0, 6.0, 3.0, 11.0, 1.0, 9.0, 15.0, 7.0, 13.0, 5.0);
+
+void main()
+{
+    // Keep a fraction of the fragments given by lodFade using an ordered dither pattern, or the remaining fragments if inverted
+    int x = int(mod(gl_FragCoord.x, 4.0));
+    int y = int(mod(gl_FragCoord.y, 4.0));
+    bool keep = (lodDitherPattern[4 * y + x] + 0.5) / 16.0 < lodFade;
+    if (keep == (lodFadeInvert == 1)) {
+        discard;
+    }
+    lod_main();
+}
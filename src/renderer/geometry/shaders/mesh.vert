@@ -77,14 +77,12 @@ void main()
 #ifdef PARTICLES
     worldPosition.xyz += start_position + start_velocity * time + 0.5 * acceleration * time * time;
 #endif
+#ifdef GPU_PARTICLES
+    worldPosition.xyz = gpu_particle_position(worldPosition.xyz);
+#endif
 #ifdef USE_INSTANCE_TRANSLATIONS 
     worldPosition.xyz += instance_translation;
 #endif
-    gl_Position = viewProjection * worldPosition;
-
-#ifdef USE_POSITIONS
-    pos = worldPosition.xyz;
-#endif
 
     // *** NORMAL ***
 #ifdef USE_NORMALS 
@@ -93,7 +91,24 @@ void main()
 #else
     mat3 normalMat = mat3(normalMatrix);
 #endif
-    nor = normalize(normalMat * normal);
+    vec3 worldNormal = normalize(normalMat * normal);
+#else
+    vec3 worldNormal = vec3(0.0);
+#endif
+
+    // *** MATERIAL ***
+#ifdef USE_MATERIAL_VERTEX
+    material_vertex(worldPosition.xyz, worldNormal);
+#endif
+
+    gl_Position = viewProjection * worldPosition;
+
+#ifdef USE_POSITIONS
+    pos = worldPosition.xyz;
+#endif
+
+#ifdef USE_NORMALS 
+    nor = worldNormal;
 
 #ifdef USE_TANGENTS 
     tang = normalize(normalMat * tangent.xyz);
@@ -124,5 +139,13 @@ void main()
 #ifdef USE_INSTANCE_COLORS
     col *= instance_color / 255.0;
 #endif
+#ifdef GPU_PARTICLES
+    col *= gpu_particle_color();
+#endif
+#endif
+
+    // *** CUSTOM ***
+#ifdef USE_CUSTOM_ATTRIBUTES
+    custom_attributes();
 #endif
 }
\ No newline at end of file
@@ -9,6 +9,7 @@ in vec3 position;
 in vec2 uv_coordinate;
 
 out vec2 uvs;
+out vec3 pos;
 
 void main()
 {
@@ -32,5 +33,11 @@ void main()
                 y, 0.0,
                 z, 0.0,
                 center.x, center.y, center.z, 1.0);
-    gl_Position = viewProjection * instanced_transform * transformation * vec4(position, 1.);
+    vec4 worldPosition = instanced_transform * transformation * vec4(position, 1.);
+#ifdef USE_MATERIAL_VERTEX
+    vec3 worldNormal = z;
+    material_vertex(worldPosition.xyz, worldNormal);
+#endif
+    pos = worldPosition.xyz / worldPosition.w;
+    gl_Position = viewProjection * worldPosition;
 }
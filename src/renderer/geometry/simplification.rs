@@ -0,0 +1,505 @@
+use crate::renderer::*;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+// The weight of the planes which keep boundaries and attribute seams in place, relative to the planes of the triangles.
+const BOUNDARY_WEIGHT: f64 = 10.0;
+
+// The minimum cosine of the angle between the normal of a triangle before and after a collapse, smaller values fold the surface over itself.
+const FOLD_OVER_THRESHOLD: f32 = 0.2;
+
+///
+/// Defines when to stop simplifying a mesh, see [simplify_mesh].
+///
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SimplificationTarget {
+    /// Simplify until the mesh has at most the given number of triangles or until no more triangles can be removed without breaking seams or boundaries.
+    TriangleCount(usize),
+    /// Simplify as much as possible as long as the error stays below the given value.
+    /// The error is approximately the distance between the original and the simplified surface in the same units as the positions of the mesh.
+    Error(f32),
+}
+
+///
+/// Reduces the number of triangles in the given [CpuMesh] until the given target is reached, by collapsing the edge with the smallest quadric error one at a time.
+/// The vertices of the simplified mesh are a subset of the original vertices, so all attributes, ie. normals, tangents, uv coordinates and colors, are kept as they are.
+///
+/// Vertices with the same position but different attributes, for example at uv seams or sharp edges in the normals, are collapsed together so the seams are not broken,
+/// and boundaries of the mesh are only collapsed along the boundary. Collapses that would change the topology of the mesh or fold a triangle over are not allowed.
+///
+/// The result is for example useful as the levels of a [LodMesh], see [LodMesh::new_simplified].
+///
+pub fn simplify_mesh(cpu_mesh: &CpuMesh, target: SimplificationTarget) -> CpuMesh {
+    let positions = cpu_mesh.positions.to_f32();
+    let indices = cpu_mesh
+        .indices
+        .to_u32()
+        .unwrap_or_else(|| (0..positions.len() as u32).collect());
+    let (target_triangle_count, max_error) = match target {
+        SimplificationTarget::TriangleCount(count) => (count, f32::INFINITY),
+        SimplificationTarget::Error(error) => (0, error),
+    };
+
+    // Weld the vertices with equal positions, so that seams in the attributes can be detected
+    let mut remap = vec![0u32; positions.len()];
+    let mut welded = HashMap::new();
+    for (i, p) in positions.iter().enumerate() {
+        let key = [
+            (p.x + 0.0).to_bits(),
+            (p.y + 0.0).to_bits(),
+            (p.z + 0.0).to_bits(),
+        ];
+        remap[i] = *welded.entry(key).or_insert(i as u32);
+    }
+
+    let triangle_count = indices.len() / 3;
+    let mut mesh = Connectivity {
+        indices,
+        remap,
+        removed: vec![false; triangle_count],
+        vertex_triangles: vec![Vec::new(); positions.len()],
+    };
+    let mut live_triangle_count = 0;
+    let mut quadrics = vec![Quadric::default(); positions.len()];
+    for t in 0..triangle_count as u32 {
+        let [a, b, c] = mesh.welded_triangle(t);
+        if a == b || b == c || c == a {
+            mesh.removed[t as usize] = true;
+            continue;
+        }
+        live_triangle_count += 1;
+        for v in [a, b, c] {
+            mesh.vertex_triangles[v as usize].push(t);
+        }
+        let [p0, p1, p2] = [a, b, c].map(|v| positions[v as usize]);
+        let normal = (p1 - p0).cross(p2 - p0);
+        let area = 0.5 * normal.magnitude();
+        if area > 0.0 {
+            let quadric = Quadric::from_plane(normal.normalize(), p0, area as f64);
+            for v in [a, b, c] {
+                quadrics[v as usize].add(&quadric);
+            }
+        }
+    }
+    add_boundary_quadrics(
+        &positions,
+        &mesh.indices,
+        &mesh.remap,
+        &mesh.removed,
+        &mut quadrics,
+    );
+
+    // The candidates are invalidated by increasing the version of their vertices instead of being removed from the queue
+    let mut versions = vec![0u32; positions.len()];
+    let mut queue = BinaryHeap::new();
+    let push_candidate = |queue: &mut BinaryHeap<Candidate>,
+                          mesh: &Connectivity,
+                          quadrics: &[Quadric],
+                          versions: &[u32],
+                          from: u32,
+                          to: u32| {
+        let cost = match mesh.class(from) {
+            None => return,
+            Some(true) if mesh.edge_triangle_count(from, to) != 1 => return,
+            _ => quadrics[from as usize].error(positions[to as usize]),
+        };
+        if cost <= max_error {
+            queue.push(Candidate {
+                cost,
+                from,
+                to,
+                versions: [versions[from as usize], versions[to as usize]],
+            });
+        }
+    };
+    for v in 0..positions.len() as u32 {
+        for n in mesh.neighbours(v) {
+            push_candidate(&mut queue, &mesh, &quadrics, &versions, v, n);
+        }
+    }
+
+    while live_triangle_count > target_triangle_count {
+        let Candidate {
+            from,
+            to,
+            versions: candidate_versions,
+            ..
+        } = match queue.pop() {
+            Some(candidate) => candidate,
+            None => break,
+        };
+        if candidate_versions != [versions[from as usize], versions[to as usize]]
+            || !mesh.satisfies_link_condition(from, to)
+        {
+            continue;
+        }
+        if let Some(removed_count) = mesh.collapse(from, to, &positions) {
+            let quadric = quadrics[from as usize];
+            quadrics[to as usize].add(&quadric);
+            live_triangle_count -= removed_count;
+
+            // Only the costs of the edges around the vertices next to the collapsed edge are changed
+            versions[from as usize] += 1;
+            let mut affected = mesh.neighbours(to);
+            affected.push(to);
+            for v in affected.iter() {
+                versions[*v as usize] += 1;
+            }
+            for v in affected {
+                for n in mesh.neighbours(v) {
+                    push_candidate(&mut queue, &mesh, &quadrics, &versions, v, n);
+                    push_candidate(&mut queue, &mesh, &quadrics, &versions, n, v);
+                }
+            }
+        }
+    }
+
+    // Remove the triangles and vertices that are no longer used
+    let mut new_indices = Vec::with_capacity(3 * live_triangle_count);
+    let mut vertex_map = HashMap::new();
+    let mut used_vertices = Vec::new();
+    for t in (0..triangle_count).filter(|t| !mesh.removed[*t]) {
+        for i in triangle(&mesh.indices, t) {
+            let index = *vertex_map.entry(i).or_insert_with(|| {
+                used_vertices.push(i as usize);
+                used_vertices.len() as u32 - 1
+            });
+            new_indices.push(index);
+        }
+    }
+    let mut result = cpu_mesh.clone();
+    result.positions = match &cpu_mesh.positions {
+        Positions::F32(positions) => Positions::F32(select(positions, &used_vertices)),
+        Positions::F64(positions) => Positions::F64(select(positions, &used_vertices)),
+    };
+    result.indices = Indices::U32(new_indices);
+    result.normals = cpu_mesh
+        .normals
+        .as_ref()
+        .map(|normals| select(normals, &used_vertices));
+    result.tangents = cpu_mesh
+        .tangents
+        .as_ref()
+        .map(|tangents| select(tangents, &used_vertices));
+    result.uvs = cpu_mesh.uvs.as_ref().map(|uvs| select(uvs, &used_vertices));
+    result.colors = cpu_mesh
+        .colors
+        .as_ref()
+        .map(|colors| select(colors, &used_vertices));
+    result
+}
+
+fn triangle(indices: &[u32], t: usize) -> [u32; 3] {
+    [indices[3 * t], indices[3 * t + 1], indices[3 * t + 2]]
+}
+
+fn select<T: Copy>(values: &[T], used_vertices: &[usize]) -> Vec<T> {
+    used_vertices.iter().map(|i| values[*i]).collect()
+}
+
+///
+/// Adds planes perpendicular to the triangles along boundaries and attribute seams, so that these are preserved.
+///
+fn add_boundary_quadrics(
+    positions: &[Vec3],
+    indices: &[u32],
+    remap: &[u32],
+    removed: &[bool],
+    quadrics: &mut [Quadric],
+) {
+    let mut edges = HashSet::new();
+    let mut welded_edges = HashSet::new();
+    for t in (0..removed.len()).filter(|t| !removed[*t]) {
+        let corners = triangle(indices, t);
+        for k in 0..3 {
+            let (a, b) = (corners[k], corners[(k + 1) % 3]);
+            edges.insert((a, b));
+            welded_edges.insert((remap[a as usize], remap[b as usize]));
+        }
+    }
+    for t in (0..removed.len()).filter(|t| !removed[*t]) {
+        let corners = triangle(indices, t);
+        let [p0, p1, p2] = corners.map(|i| positions[i as usize]);
+        let normal = (p1 - p0).cross(p2 - p0);
+        if normal.magnitude2() == 0.0 {
+            continue;
+        }
+        for k in 0..3 {
+            let (a, b) = (corners[k], corners[(k + 1) % 3]);
+            let (wa, wb) = (remap[a as usize], remap[b as usize]);
+            if welded_edges.contains(&(wb, wa)) && edges.contains(&(b, a)) {
+                continue;
+            }
+            let edge = positions[b as usize] - positions[a as usize];
+            let plane_normal = edge.cross(normal);
+            if plane_normal.magnitude2() > 0.0 {
+                let quadric = Quadric::from_plane(
+                    plane_normal.normalize(),
+                    positions[a as usize],
+                    BOUNDARY_WEIGHT * edge.magnitude2() as f64,
+                );
+                quadrics[wa as usize].add(&quadric);
+                quadrics[wb as usize].add(&quadric);
+            }
+        }
+    }
+}
+
+///
+/// A collapse of the welded vertex `from` into the welded vertex `to` in the priority queue, where the cheapest collapse is popped first.
+///
+struct Candidate {
+    cost: f32,
+    from: u32,
+    to: u32,
+    versions: [u32; 2],
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .cost
+            .partial_cmp(&self.cost)
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+///
+/// The triangles around each welded vertex, which are updated with each collapse.
+///
+struct Connectivity {
+    indices: Vec<u32>,
+    remap: Vec<u32>,
+    removed: Vec<bool>,
+    vertex_triangles: Vec<Vec<u32>>,
+}
+
+impl Connectivity {
+    fn welded_triangle(&self, t: u32) -> [u32; 3] {
+        triangle(&self.indices, t as usize).map(|i| self.remap[i as usize])
+    }
+
+    fn triangles(&self, v: u32) -> impl Iterator<Item = u32> + '_ {
+        self.vertex_triangles[v as usize]
+            .iter()
+            .copied()
+            .filter(move |t| !self.removed[*t as usize])
+    }
+
+    fn neighbours(&self, v: u32) -> Vec<u32> {
+        let mut neighbours = Vec::new();
+        for t in self.triangles(v) {
+            for w in self.welded_triangle(t) {
+                if w != v && !neighbours.contains(&w) {
+                    neighbours.push(w);
+                }
+            }
+        }
+        neighbours
+    }
+
+    fn edge_triangle_count(&self, a: u32, b: u32) -> usize {
+        self.triangles(a)
+            .filter(|t| self.welded_triangle(*t).contains(&b))
+            .count()
+    }
+
+    ///
+    /// Returns `Some(false)` if the welded vertex is in the interior of the mesh, `Some(true)` if it is on a boundary and can be collapsed along the boundary
+    /// and `None` if it cannot be collapsed, for example if it is next to a non-manifold edge.
+    ///
+    fn class(&self, v: u32) -> Option<bool> {
+        let mut edges = HashMap::new();
+        for t in self.triangles(v) {
+            let corners = self.welded_triangle(t);
+            for k in 0..3 {
+                let edge = (corners[k], corners[(k + 1) % 3]);
+                if edge.0 == v || edge.1 == v {
+                    *edges.entry(edge).or_insert(0) += 1;
+                }
+            }
+        }
+        let mut boundary_count = 0;
+        for (&(a, b), &count) in edges.iter() {
+            if count > 1 {
+                // Non-manifold edge or inconsistent winding order
+                return None;
+            }
+            if !edges.contains_key(&(b, a)) {
+                boundary_count += 1;
+            }
+        }
+        match boundary_count {
+            0 => Some(false),
+            2 => Some(true),
+            _ => None,
+        }
+    }
+
+    ///
+    /// Returns true if the vertices connected to both `a` and `b` are exactly the vertices opposite to the edge in the triangles along the edge,
+    /// otherwise collapsing the edge would change the topology of the mesh, for example by pinching a tube or creating duplicate triangles.
+    ///
+    fn satisfies_link_condition(&self, a: u32, b: u32) -> bool {
+        let neighbours_b = self.neighbours(b);
+        let common_count = self
+            .neighbours(a)
+            .iter()
+            .filter(|v| neighbours_b.contains(v))
+            .count();
+        common_count == self.edge_triangle_count(a, b)
+    }
+
+    ///
+    /// Moves the welded vertex `from` to the welded vertex `to` and removes the triangles that become degenerate.
+    /// Each vertex at `from` is replaced by the vertex at `to` on the same side of any attribute seam.
+    /// Returns the number of removed triangles or `None` if the collapse is not allowed.
+    ///
+    fn collapse(&mut self, from: u32, to: u32, positions: &[Vec3]) -> Option<usize> {
+        let triangles = self.triangles(from).collect::<Vec<_>>();
+
+        // Find the vertex at `to` which replaces each vertex at `from` using the triangles along the collapsed edge
+        let mut vertex_map: Vec<(u32, u32)> = Vec::new();
+        for t in triangles
+            .iter()
+            .copied()
+            .filter(|t| self.welded_triangle(*t).contains(&to))
+        {
+            let corners = triangle(&self.indices, t as usize);
+            let welded = self.welded_triangle(t);
+            let source = corners[welded.iter().position(|v| *v == from)?];
+            let target = corners[welded.iter().position(|v| *v == to)?];
+            match vertex_map.iter().find(|(s, _)| *s == source) {
+                Some((_, existing)) if *existing != target => return None,
+                Some(_) => {}
+                None => vertex_map.push((source, target)),
+            }
+        }
+        if vertex_map.is_empty() {
+            return None;
+        }
+
+        // Check that all vertices at `from` can be replaced and that no triangles are folded over
+        let new_position = positions[to as usize];
+        for t in triangles
+            .iter()
+            .copied()
+            .filter(|t| !self.welded_triangle(*t).contains(&to))
+        {
+            let corners = triangle(&self.indices, t as usize);
+            let mut new_positions = corners.map(|i| positions[i as usize]);
+            for (k, i) in corners.iter().enumerate() {
+                if self.remap[*i as usize] == from {
+                    if !vertex_map.iter().any(|(source, _)| source == i) {
+                        return None;
+                    }
+                    new_positions[k] = new_position;
+                }
+            }
+            let [p0, p1, p2] = corners.map(|i| positions[i as usize]);
+            let [q0, q1, q2] = new_positions;
+            let normal = (p1 - p0).cross(p2 - p0);
+            let new_normal = (q1 - q0).cross(q2 - q0);
+            if new_normal.magnitude2() == 0.0 {
+                return None;
+            }
+            if normal.magnitude2() > 0.0
+                && normal.normalize().dot(new_normal.normalize()) < FOLD_OVER_THRESHOLD
+            {
+                return None;
+            }
+        }
+
+        let mut removed_count = 0;
+        for t in triangles {
+            if self.welded_triangle(t).contains(&to) {
+                self.removed[t as usize] = true;
+                removed_count += 1;
+            } else {
+                for index in self.indices[3 * t as usize..3 * t as usize + 3].iter_mut() {
+                    if let Some((_, target)) = vertex_map.iter().find(|(s, _)| s == index) {
+                        *index = *target;
+                    }
+                }
+                self.vertex_triangles[to as usize].push(t);
+            }
+        }
+        self.vertex_triangles[from as usize].clear();
+        Some(removed_count)
+    }
+}
+
+///
+/// A symmetric 4x4 matrix which evaluates the sum of the weighted squared distances from a point to a set of planes.
+///
+#[derive(Clone, Copy, Default)]
+struct Quadric {
+    a: [f64; 10],
+    weight: f64,
+}
+
+impl Quadric {
+    fn from_plane(normal: Vec3, point: Vec3, weight: f64) -> Self {
+        let (a, b, c) = (normal.x as f64, normal.y as f64, normal.z as f64);
+        let d = -normal.dot(point) as f64;
+        Self {
+            a: [
+                a * a,
+                a * b,
+                a * c,
+                a * d,
+                b * b,
+                b * c,
+                b * d,
+                c * c,
+                c * d,
+                d * d,
+            ]
+            .map(|v| v * weight),
+            weight,
+        }
+    }
+
+    fn add(&mut self, other: &Self) {
+        for (a, b) in self.a.iter_mut().zip(other.a) {
+            *a += b;
+        }
+        self.weight += other.weight;
+    }
+
+    ///
+    /// Returns the weighted root mean square distance from the given point to the planes.
+    ///
+    fn error(&self, point: Vec3) -> f32 {
+        if self.weight <= 0.0 {
+            return 0.0;
+        }
+        let (x, y, z) = (point.x as f64, point.y as f64, point.z as f64);
+        let q = &self.a;
+        let e = q[0] * x * x
+            + 2.0 * q[1] * x * y
+            + 2.0 * q[2] * x * z
+            + 2.0 * q[3] * x
+            + q[4] * y * y
+            + 2.0 * q[5] * y * z
+            + 2.0 * q[6] * y
+            + q[7] * z * z
+            + 2.0 * q[8] * z
+            + q[9];
+        (e.max(0.0) / self.weight).sqrt() as f32
+    }
+}
@@ -1,5 +1,6 @@
 use crate::core::*;
 use crate::renderer::*;
+use std::sync::RwLock;
 
 ///
 /// A set of sprites, ie. a set of quads that orients itself towards the camera.
@@ -12,9 +13,12 @@ pub struct Sprites {
     context: Context,
     position_buffer: VertexBuffer,
     uv_buffer: VertexBuffer,
-    center_buffer: InstanceBuffer,
+    center_buffer: RwLock<InstanceBuffer>,
     transformation: Mat4,
     direction: Option<Vec3>,
+    centers: Vec<Vec3>,
+    center_order: RwLock<Vec<u32>>,
+    depth_sorting: bool,
 }
 
 impl Sprites {
@@ -49,9 +53,12 @@ impl Sprites {
             context: context.clone(),
             position_buffer,
             uv_buffer,
-            center_buffer: InstanceBuffer::new_with_data(context, centers),
+            center_buffer: RwLock::new(InstanceBuffer::new_with_data(context, centers)),
             transformation: Mat4::identity(),
             direction,
+            centers: centers.to_vec(),
+            center_order: RwLock::new((0..centers.len() as u32).collect()),
+            depth_sorting: false,
         }
     }
 
@@ -80,7 +87,58 @@ impl Sprites {
     /// Set the centers of the sprites. The centers also determines the number of sprites.
     ///
     pub fn set_centers(&mut self, centers: &[Vec3]) {
-        self.center_buffer.fill(centers);
+        self.center_buffer.get_mut().unwrap().fill(centers);
+        self.centers = centers.to_vec();
+        self.center_order = RwLock::new((0..centers.len() as u32).collect());
+    }
+
+    ///
+    /// Returns whether the sprites are sorted back to front before rendering with a transparent material, see [Sprites::set_depth_sorting].
+    ///
+    pub fn depth_sorting(&self) -> bool {
+        self.depth_sorting
+    }
+
+    ///
+    /// Set whether the sprites should be sorted back to front, based on the distance from the camera to the center of each sprite, before rendering with a [MaterialType::Transparent] material.
+    /// This is needed for overlapping transparent sprites to blend correctly. Only the part of the center buffer where the order changed is updated,
+    /// so the cost is small when the camera moves a little between frames. Disabled by default.
+    ///
+    pub fn set_depth_sorting(&mut self, depth_sorting: bool) {
+        if self.depth_sorting && !depth_sorting {
+            // Restore the original order
+            self.center_buffer.get_mut().unwrap().fill(&self.centers);
+            self.center_order = RwLock::new((0..self.centers.len() as u32).collect());
+        }
+        self.depth_sorting = depth_sorting;
+    }
+
+    // Sorts the sprites back to front with respect to the given world space position and updates the part of the center buffer where the order changed.
+    fn sort_centers(&self, position: Vec3) {
+        let distances = self
+            .centers
+            .iter()
+            .map(|c| position.distance2(*c))
+            .collect::<Vec<_>>();
+        let mut order = self.center_order.write().unwrap();
+        let mut new_order = order.clone();
+        new_order.sort_by(|a, b| {
+            distances[*b as usize]
+                .partial_cmp(&distances[*a as usize])
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        let first = (0..order.len()).find(|i| order[*i] != new_order[*i]);
+        let last = (0..order.len()).rev().find(|i| order[*i] != new_order[*i]);
+        if let (Some(first), Some(last)) = (first, last) {
+            self.center_buffer.write().unwrap().fill_subset(
+                first as u32,
+                &new_order[first..=last]
+                    .iter()
+                    .map(|i| self.centers[*i as usize])
+                    .collect::<Vec<_>>(),
+            );
+            *order = new_order;
+        }
     }
 }
 
@@ -91,10 +149,20 @@ impl Geometry for Sprites {
         camera: &Camera,
         lights: &[&dyn Light],
     ) {
+        if self.depth_sorting && material.material_type() == MaterialType::Transparent {
+            self.sort_centers(camera.position());
+        }
         let fragment_shader_source = material.fragment_shader_source(false, lights);
         self.context
             .program(
-                &include_str!("shaders/sprites.vert"),
+                &format!(
+                    "{}{}",
+                    material
+                        .vertex_shader_source()
+                        .map(|source| format!("#define USE_MATERIAL_VERTEX\n{}\n", source))
+                        .unwrap_or_default(),
+                    include_str!("shaders/sprites.vert")
+                ),
                 &fragment_shader_source,
                 |program| {
                     material.use_uniforms(program, camera, lights);
@@ -103,13 +171,14 @@ impl Geometry for Sprites {
                     program.use_uniform("transformation", self.transformation);
                     program.use_vertex_attribute("position", &self.position_buffer);
                     program.use_vertex_attribute("uv_coordinate", &self.uv_buffer);
-                    program.use_instance_attribute("center", &self.center_buffer);
+                    let center_buffer = self.center_buffer.read().unwrap();
+                    program.use_instance_attribute("center", &center_buffer);
                     program.use_uniform("direction", self.direction.unwrap_or(vec3(0.0, 0.0, 0.0)));
                     program.draw_arrays_instanced(
                         material.render_states(),
                         camera.viewport(),
                         6,
-                        self.center_buffer.instance_count(),
+                        center_buffer.instance_count(),
                     )
                 },
             )
@@ -56,16 +56,49 @@ impl DirectionalLight {
         texture_size: u32,
         geometries: impl IntoIterator<Item = impl Geometry> + Clone,
     ) {
-        let up = compute_up_direction(self.direction);
-
-        let viewport = Viewport::new_at_origo(texture_size, texture_size);
         let mut aabb = AxisAlignedBoundingBox::EMPTY;
         for geometry in geometries.clone() {
             aabb.expand_with_aabb(&geometry.aabb());
         }
+        self.render_shadow_map(texture_size, aabb, |shadow_camera, depth_material| {
+            for geometry in geometries
+                .into_iter()
+                .filter(|g| shadow_camera.in_frustum(&g.aabb()))
+            {
+                geometry.render_with_material(depth_material, shadow_camera, &[]);
+            }
+        });
+    }
+
+    ///
+    /// Same as [DirectionalLight::generate_shadow_map], except that the given [ObjectBvh] is used to find the geometries inside the shadow frustum
+    /// instead of testing each geometry. The id of each geometry in the hierarchy must be its index in the given list of geometries.
+    ///
+    pub fn generate_shadow_map_with_bvh(
+        &mut self,
+        texture_size: u32,
+        bvh: &ObjectBvh,
+        geometries: &[impl Geometry],
+    ) {
+        self.render_shadow_map(texture_size, bvh.aabb(), |shadow_camera, depth_material| {
+            for geometry in bvh.visible_objects(shadow_camera, geometries) {
+                geometry.render_with_material(depth_material, shadow_camera, &[]);
+            }
+        });
+    }
+
+    fn render_shadow_map(
+        &mut self,
+        texture_size: u32,
+        aabb: AxisAlignedBoundingBox,
+        render: impl FnOnce(&Camera, &DepthMaterial),
+    ) {
         if aabb.is_empty() {
             return;
         }
+        let up = compute_up_direction(self.direction);
+
+        let viewport = Viewport::new_at_origo(texture_size, texture_size);
         let target = aabb.center();
         let position = target - aabb.max().distance(aabb.min()) * self.direction;
         let z_far = aabb.distance_max(&position);
@@ -98,14 +131,7 @@ impl DirectionalLight {
         shadow_texture
             .as_depth_target()
             .clear(ClearState::default())
-            .write(|| {
-                for geometry in geometries
-                    .into_iter()
-                    .filter(|g| shadow_camera.in_frustum(&g.aabb()))
-                {
-                    geometry.render_with_material(&depth_material, &shadow_camera, &[]);
-                }
-            });
+            .write(|| render(&shadow_camera, &depth_material));
         self.shadow_texture = Some(shadow_texture);
         self.shadow_matrix = shadow_matrix(&shadow_camera);
     }
@@ -67,12 +67,6 @@ impl SpotLight {
         texture_size: u32,
         geometries: impl IntoIterator<Item = impl Geometry> + Clone,
     ) {
-        let position = self.position;
-        let direction = self.direction;
-        let up = compute_up_direction(self.direction);
-
-        let viewport = Viewport::new_at_origo(texture_size, texture_size);
-
         let mut z_far = 0.0f32;
         let mut z_near = f32::MAX;
         for geometry in geometries.clone() {
@@ -82,7 +76,59 @@ impl SpotLight {
                 z_near = z_near.min(aabb.distance(&self.position));
             }
         }
+        self.render_shadow_map(
+            texture_size,
+            z_near,
+            z_far,
+            |shadow_camera, depth_material| {
+                for geometry in geometries
+                    .into_iter()
+                    .filter(|g| shadow_camera.in_frustum(&g.aabb()))
+                {
+                    geometry.render_with_material(depth_material, shadow_camera, &[]);
+                }
+            },
+        );
+    }
 
+    ///
+    /// Same as [SpotLight::generate_shadow_map], except that the given [ObjectBvh] is used to find the geometries inside the shadow frustum
+    /// instead of testing each geometry. The id of each geometry in the hierarchy must be its index in the given list of geometries.
+    ///
+    pub fn generate_shadow_map_with_bvh(
+        &mut self,
+        texture_size: u32,
+        bvh: &ObjectBvh,
+        geometries: &[impl Geometry],
+    ) {
+        let aabb = bvh.aabb();
+        if aabb.is_empty() {
+            return;
+        }
+        self.render_shadow_map(
+            texture_size,
+            aabb.distance(&self.position),
+            aabb.distance_max(&self.position),
+            |shadow_camera, depth_material| {
+                for geometry in bvh.visible_objects(shadow_camera, geometries) {
+                    geometry.render_with_material(depth_material, shadow_camera, &[]);
+                }
+            },
+        );
+    }
+
+    fn render_shadow_map(
+        &mut self,
+        texture_size: u32,
+        z_near: f32,
+        z_far: f32,
+        render: impl FnOnce(&Camera, &DepthMaterial),
+    ) {
+        let position = self.position;
+        let direction = self.direction;
+        let up = compute_up_direction(self.direction);
+
+        let viewport = Viewport::new_at_origo(texture_size, texture_size);
         let shadow_camera = Camera::new_perspective(
             viewport,
             position,
@@ -112,14 +158,7 @@ impl SpotLight {
         shadow_texture
             .as_depth_target()
             .clear(ClearState::default())
-            .write(|| {
-                for geometry in geometries
-                    .into_iter()
-                    .filter(|g| shadow_camera.in_frustum(&g.aabb()))
-                {
-                    geometry.render_with_material(&depth_material, &shadow_camera, &[]);
-                }
-            });
+            .write(|| render(&shadow_camera, &depth_material));
         self.shadow_texture = Some(shadow_texture);
     }
 
@@ -55,11 +55,19 @@ mod isosurface_material;
 #[doc(inline)]
 pub use isosurface_material::*;
 
+mod oit_material;
+#[doc(inline)]
+pub use oit_material::*;
+
+mod clip_cap_material;
+#[doc(inline)]
+pub use clip_cap_material::*;
+
 ///
 /// Defines the material type which is needed to render the objects in the correct order.
 /// For example, transparent objects need to be rendered back to front, whereas opaque objects need to be rendered front to back.
 ///
-#[derive(Clone, Copy, PartialEq, PartialOrd, Ord, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, PartialOrd, Ord, Eq, Hash, Debug)]
 pub enum MaterialType {
     /// Forward opaque
     Opaque,
@@ -103,6 +111,32 @@ pub trait Material {
     /// Returns the type of material.
     ///
     fn material_type(&self) -> MaterialType;
+
+    ///
+    /// Returns a key used to group objects with similar materials together before rendering, see [RenderQueue].
+    /// Materials that use the same textures should return the same key to minimise the number of texture bindings.
+    /// The default implementation returns zero.
+    ///
+    fn sort_key(&self) -> u64 {
+        0
+    }
+
+    ///
+    /// Returns an optional snippet which is inserted into the vertex shader of the geometry, for example to animate foliage in the wind or to displace the vertices using a height texture.
+    /// The snippet must define the function `void material_vertex(inout vec3 position, inout vec3 normal)`
+    /// which is called with the position and normal of each vertex in world space before the position is projected to the screen.
+    /// The normal is zero if the geometry does not have normals.
+    /// Any uniforms declared in the snippet should be sent in [Material::use_uniforms].
+    /// The snippet is used by [Mesh], [InstancedMesh], [ParticleSystem] and [Sprites], where the normal of a sprite is the direction towards the camera.
+    /// When an object is rendered with another material, for example the [DepthMaterial] in the shadow passes and the depth pass of [RenderTarget::render_with_oit],
+    /// the snippet of the material of the object is used instead, see [Gm]. In that case [Material::use_uniforms] of the material of the object is also called
+    /// with the program of the other material, which does not have the other uniforms of this material, so a material with a snippet should send its uniforms with [Program::use_uniform_if_required]
+    /// or check [Program::requires_uniform] before sending them.
+    /// The default implementation returns `None`, ie. the vertices are not changed.
+    ///
+    fn vertex_shader_source(&self) -> Option<String> {
+        None
+    }
 }
 
 ///
@@ -138,6 +172,12 @@ impl<T: Material + ?Sized> Material for &T {
     fn material_type(&self) -> MaterialType {
         (*self).material_type()
     }
+    fn sort_key(&self) -> u64 {
+        (*self).sort_key()
+    }
+    fn vertex_shader_source(&self) -> Option<String> {
+        (*self).vertex_shader_source()
+    }
 }
 
 impl<T: Material + ?Sized> Material for &mut T {
@@ -153,6 +193,12 @@ impl<T: Material + ?Sized> Material for &mut T {
     fn material_type(&self) -> MaterialType {
         (**self).material_type()
     }
+    fn sort_key(&self) -> u64 {
+        (**self).sort_key()
+    }
+    fn vertex_shader_source(&self) -> Option<String> {
+        (**self).vertex_shader_source()
+    }
 }
 
 impl<T: Material> Material for Box<T> {
@@ -169,6 +215,12 @@ impl<T: Material> Material for Box<T> {
     fn material_type(&self) -> MaterialType {
         self.as_ref().material_type()
     }
+    fn sort_key(&self) -> u64 {
+        self.as_ref().sort_key()
+    }
+    fn vertex_shader_source(&self) -> Option<String> {
+        self.as_ref().vertex_shader_source()
+    }
 }
 
 impl<T: Material> Material for std::rc::Rc<T> {
@@ -185,6 +237,12 @@ impl<T: Material> Material for std::rc::Rc<T> {
     fn material_type(&self) -> MaterialType {
         self.as_ref().material_type()
     }
+    fn sort_key(&self) -> u64 {
+        self.as_ref().sort_key()
+    }
+    fn vertex_shader_source(&self) -> Option<String> {
+        self.as_ref().vertex_shader_source()
+    }
 }
 
 impl<T: Material> Material for std::sync::Arc<T> {
@@ -201,6 +259,12 @@ impl<T: Material> Material for std::sync::Arc<T> {
     fn material_type(&self) -> MaterialType {
         self.as_ref().material_type()
     }
+    fn sort_key(&self) -> u64 {
+        self.as_ref().sort_key()
+    }
+    fn vertex_shader_source(&self) -> Option<String> {
+        self.as_ref().vertex_shader_source()
+    }
 }
 
 impl<T: Material> Material for std::cell::RefCell<T> {
@@ -217,6 +281,12 @@ impl<T: Material> Material for std::cell::RefCell<T> {
     fn material_type(&self) -> MaterialType {
         self.borrow().material_type()
     }
+    fn sort_key(&self) -> u64 {
+        self.borrow().sort_key()
+    }
+    fn vertex_shader_source(&self) -> Option<String> {
+        self.borrow().vertex_shader_source()
+    }
 }
 
 impl<T: Material> Material for std::sync::RwLock<T> {
@@ -234,6 +304,24 @@ impl<T: Material> Material for std::sync::RwLock<T> {
     fn material_type(&self) -> MaterialType {
         self.read().unwrap().material_type()
     }
+    fn sort_key(&self) -> u64 {
+        self.read().unwrap().sort_key()
+    }
+    fn vertex_shader_source(&self) -> Option<String> {
+        self.read().unwrap().vertex_shader_source()
+    }
+}
+
+fn texture_key(textures: &[&Option<std::sync::Arc<Texture2D>>]) -> u64 {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    for texture in textures {
+        texture
+            .as_ref()
+            .map(|t| std::sync::Arc::as_ptr(t) as usize)
+            .hash(&mut hasher);
+    }
+    hasher.finish()
 }
 
 fn is_transparent(cpu_material: &CpuMaterial) -> bool {
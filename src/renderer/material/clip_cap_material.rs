@@ -0,0 +1,99 @@
+use crate::core::*;
+use crate::renderer::*;
+
+///
+/// A material that fills the holes which appear where a closed [Geometry] is cut by clip planes, see [RenderTarget::render_clip_caps].
+/// The cut surface is rendered in a single color at the depth of the clip plane, which makes the geometry look solid instead of hollow.
+///
+/// When used as an ordinary material inside [RenderTarget::render_with_clip_planes], the cap is computed from the back faces of the geometry that are visible through the cut,
+/// so the geometry must be closed and have consistent winding order. Only the parts of a clip plane facing the camera are capped, ie. when the camera is on the clipped side of the plane.
+///
+#[derive(Clone)]
+pub struct ClipCapMaterial {
+    /// The color of the cut surface. Assumed to be in linear color space.
+    pub color: Color,
+    /// Render states. Defaults to culling front faces, which is required for the cap to be computed correctly.
+    pub render_states: RenderStates,
+}
+
+impl Default for ClipCapMaterial {
+    fn default() -> Self {
+        Self {
+            color: Color::WHITE,
+            render_states: RenderStates {
+                cull: Cull::Front,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+impl ClipCapMaterial {
+    // Fills the pixels marked in the stencil buffer with the intersection between the view ray and the clip plane with the given index,
+    // see RenderTarget::render_clip_caps.
+    pub(crate) fn render_stencil_cap(
+        &self,
+        context: &Context,
+        camera: &Camera,
+        plane_index: usize,
+    ) {
+        let view_projection = camera.projection() * camera.view();
+        if let Some(view_projection_inverse) = view_projection.invert() {
+            context
+                .effect(
+                    &format!(
+                        "#define USE_CLIP_PLANES\n{}{}{}",
+                        include_str!("../../core/shared.frag"),
+                        include_str!("shaders/clip_planes.frag"),
+                        include_str!("shaders/clip_cap_stencil.frag")
+                    ),
+                    |effect| {
+                        effect.use_uniform("surfaceColor", self.color);
+                        effect.use_uniform("viewProjection", view_projection);
+                        effect.use_uniform("viewProjectionInverse", view_projection_inverse);
+                        effect.use_uniform("capPlane", plane_index as i32);
+                        effect.apply(
+                            RenderStates {
+                                cull: Cull::None,
+                                ..self.render_states
+                            },
+                            camera.viewport(),
+                        );
+                    },
+                )
+                .expect("Failed compiling shader");
+        }
+    }
+}
+
+impl FromCpuMaterial for ClipCapMaterial {
+    fn from_cpu_material(_context: &Context, cpu_material: &CpuMaterial) -> Self {
+        Self {
+            color: cpu_material.albedo,
+            ..Default::default()
+        }
+    }
+}
+
+impl Material for ClipCapMaterial {
+    fn fragment_shader_source(&self, _use_vertex_colors: bool, _lights: &[&dyn Light]) -> String {
+        format!(
+            "{}{}{}",
+            include_str!("../../core/shared.frag"),
+            include_str!("shaders/clip_planes.frag"),
+            include_str!("shaders/clip_cap_material.frag")
+        )
+    }
+    fn use_uniforms(&self, program: &Program, camera: &Camera, _lights: &[&dyn Light]) {
+        // The uniforms are only used when rendering with clip planes
+        program.use_uniform_if_required("surfaceColor", self.color);
+        program.use_uniform_if_required("cameraPosition", camera.position());
+        program.use_uniform_if_required("viewProjection", camera.projection() * camera.view());
+    }
+    fn render_states(&self) -> RenderStates {
+        self.render_states
+    }
+    fn material_type(&self) -> MaterialType {
+        MaterialType::Opaque
+    }
+}
@@ -93,6 +93,7 @@ impl Material for ColorMaterial {
             shader.push_str("#define USE_VERTEX_COLORS\nin vec4 col;\n");
         }
         shader.push_str(include_str!("../../core/shared.frag"));
+        shader.push_str(include_str!("shaders/clip_planes.frag"));
         shader.push_str(include_str!("shaders/color_material.frag"));
         shader
     }
@@ -112,4 +113,7 @@ impl Material for ColorMaterial {
             MaterialType::Opaque
         }
     }
+    fn sort_key(&self) -> u64 {
+        super::texture_key(&[&self.texture])
+    }
 }
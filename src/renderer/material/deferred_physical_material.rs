@@ -228,6 +228,7 @@ impl Material for DeferredPhysicalMaterial {
         if use_vertex_colors {
             output.push_str("#define USE_VERTEX_COLORS\nin vec4 col;\n");
         }
+        output.push_str(include_str!("shaders/clip_planes.frag"));
         output.push_str(include_str!("shaders/deferred_physical_material.frag"));
         output
     }
@@ -265,6 +266,16 @@ impl Material for DeferredPhysicalMaterial {
     fn material_type(&self) -> MaterialType {
         MaterialType::Deferred
     }
+
+    fn sort_key(&self) -> u64 {
+        super::texture_key(&[
+            &self.albedo_texture,
+            &self.metallic_roughness_texture,
+            &self.occlusion_texture,
+            &self.normal_texture,
+            &self.emissive_texture,
+        ])
+    }
 }
 
 impl Default for DeferredPhysicalMaterial {
@@ -23,7 +23,11 @@ impl FromCpuMaterial for DepthMaterial {
 
 impl Material for DepthMaterial {
     fn fragment_shader_source(&self, _use_vertex_colors: bool, _lights: &[&dyn Light]) -> String {
-        include_str!("shaders/depth_material.frag").to_string()
+        format!(
+            "{}{}",
+            include_str!("shaders/clip_planes.frag"),
+            include_str!("shaders/depth_material.frag")
+        )
     }
     fn use_uniforms(&self, program: &Program, camera: &Camera, _lights: &[&dyn Light]) {
         program.use_uniform("minDistance", &self.min_distance.unwrap_or(camera.z_near()));
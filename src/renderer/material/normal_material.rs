@@ -58,6 +58,7 @@ impl Material for NormalMaterial {
         if self.normal_texture.is_some() {
             shader.push_str("#define USE_TEXTURE\nin vec2 uvs;\nin vec3 tang;\nin vec3 bitang;\n");
         }
+        shader.push_str(include_str!("shaders/clip_planes.frag"));
         shader.push_str(include_str!("shaders/normal_material.frag"));
         shader
     }
@@ -73,6 +74,9 @@ impl Material for NormalMaterial {
     fn material_type(&self) -> MaterialType {
         MaterialType::Opaque
     }
+    fn sort_key(&self) -> u64 {
+        super::texture_key(&[&self.normal_texture])
+    }
 }
 
 impl Default for NormalMaterial {
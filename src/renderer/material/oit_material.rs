@@ -0,0 +1,164 @@
+use crate::core::*;
+use crate::renderer::*;
+
+///
+/// Wraps a transparent [Material] such that it can be rendered using weighted blended order-independent transparency, see [RenderTarget::render_with_oit].
+/// The wrapped material is rendered with the same uniforms, but the output color of the fragment shader is written as a weighted sum
+/// into an accumulation and a revealage target instead of being blended directly with the render target.
+/// Only needed when implementing [Object::render_with_oit] for a custom object.
+///
+/// The fragment shader of the wrapped material is required to have one color output defined as `layout (location = 0) out vec4 <name>;`
+/// and a `void main()` function, which is the case for all materials in this crate, see [OitMaterial::supports].
+/// If it does not, the wrapped material is rendered as it is.
+///
+pub struct OitMaterial<'a> {
+    material: &'a dyn Material,
+}
+
+impl<'a> OitMaterial<'a> {
+    ///
+    /// Creates a new order-independent transparency material from the given transparent material.
+    ///
+    pub fn new(material: &'a dyn Material) -> Self {
+        Self { material }
+    }
+
+    ///
+    /// Returns whether the given material can be rendered with order-independent transparency, ie. whether the fragment shader has the required color output.
+    /// Used to implement [Object::supports_oit].
+    ///
+    pub fn supports(material: &dyn Material, lights: &[&dyn Light]) -> bool {
+        output_declaration(&material.fragment_shader_source(false, lights)).is_some()
+    }
+
+    // Returns whether the accumulation texture can be rendered to with blending on this device.
+    // Blending is enabled for all color attachments at once, so both layers of the accumulation texture must support blending.
+    pub(in crate::renderer) fn is_available(context: &Context) -> bool {
+        let extensions = context.supported_extensions();
+        if cfg!(target_arch = "wasm32") {
+            extensions.contains("EXT_color_buffer_float") && extensions.contains("EXT_float_blend")
+        } else if context.version().is_embedded {
+            extensions.contains("GL_EXT_color_buffer_half_float")
+                || extensions.contains("GL_EXT_color_buffer_float")
+        } else {
+            true
+        }
+    }
+
+    ///
+    /// The blending used when rendering into the accumulation and revealage targets.
+    /// The red, green and blue channels are summed, while the alpha channel is multiplied by one minus the alpha value of the fragment.
+    ///
+    pub const BLEND: Blend = Blend::Enabled {
+        source_rgb_multiplier: BlendMultiplierType::One,
+        source_alpha_multiplier: BlendMultiplierType::Zero,
+        destination_rgb_multiplier: BlendMultiplierType::One,
+        destination_alpha_multiplier: BlendMultiplierType::OneMinusSrcAlpha,
+        rgb_equation: BlendEquationType::Add,
+        alpha_equation: BlendEquationType::Add,
+    };
+
+    ///
+    /// Creates the accumulation and revealage texture needed for rendering with this material. The first layer contains the accumulated color and the revealage,
+    /// the second layer contains the accumulated weights. Should be cleared to `(0.0, 0.0, 0.0, 1.0)` before rendering.
+    /// Rendering into this texture requires support for rendering to half float textures which on web requires the `EXT_color_buffer_float` extension.
+    ///
+    pub(in crate::renderer) fn new_accumulation_texture(
+        context: &Context,
+        width: u32,
+        height: u32,
+    ) -> Texture2DArray {
+        Texture2DArray::new_empty::<[f16; 4]>(
+            context,
+            width,
+            height,
+            2,
+            Interpolation::Nearest,
+            Interpolation::Nearest,
+            None,
+            Wrapping::ClampToEdge,
+            Wrapping::ClampToEdge,
+        )
+    }
+
+    ///
+    /// Composites the accumulated transparent objects on top of the current render target.
+    /// Must be called in the callback given as input to a [RenderTarget], [ColorTarget] or [DepthTarget] write method.
+    ///
+    pub(in crate::renderer) fn composite_pass(
+        context: &Context,
+        viewport: Viewport,
+        accumulation_texture: &Texture2DArray,
+    ) {
+        context
+            .effect(include_str!("shaders/oit_composite.frag"), |effect| {
+                effect.use_texture_array("oitTexture", accumulation_texture);
+                effect.apply(
+                    RenderStates {
+                        write_mask: WriteMask::COLOR,
+                        depth_test: DepthTest::Always,
+                        blend: Blend::TRANSPARENCY,
+                        ..Default::default()
+                    },
+                    viewport,
+                );
+            })
+            .unwrap()
+    }
+}
+
+impl Material for OitMaterial<'_> {
+    fn fragment_shader_source(&self, use_vertex_colors: bool, lights: &[&dyn Light]) -> String {
+        let source = self
+            .material
+            .fragment_shader_source(use_vertex_colors, lights);
+        let (declaration_start, name_start, name_end) = match output_declaration(&source) {
+            Some(declaration) => declaration,
+            None => return source,
+        };
+        let name = source[name_start..name_end].trim();
+        format!(
+            "{}vec4 {};{}\n#define oit_material_color {}\n{}",
+            &source[..declaration_start],
+            name,
+            source[name_end + 1..].replacen("void main()", "void oit_material_main()", 1),
+            name,
+            include_str!("shaders/oit_material.frag")
+        )
+    }
+
+    fn use_uniforms(&self, program: &Program, camera: &Camera, lights: &[&dyn Light]) {
+        self.material.use_uniforms(program, camera, lights)
+    }
+
+    fn render_states(&self) -> RenderStates {
+        let render_states = self.material.render_states();
+        RenderStates {
+            write_mask: WriteMask::COLOR,
+            blend: Self::BLEND,
+            ..render_states
+        }
+    }
+
+    fn material_type(&self) -> MaterialType {
+        MaterialType::Transparent
+    }
+
+    fn sort_key(&self) -> u64 {
+        self.material.sort_key()
+    }
+
+    fn vertex_shader_source(&self) -> Option<String> {
+        self.material.vertex_shader_source()
+    }
+}
+
+const OUTPUT_DECLARATION: &str = "layout (location = 0) out vec4 ";
+
+// Returns the start of the color output declaration and the start and end of the name of the output.
+fn output_declaration(source: &str) -> Option<(usize, usize, usize)> {
+    let declaration_start = source.find(OUTPUT_DECLARATION)?;
+    let name_start = declaration_start + OUTPUT_DECLARATION.len();
+    let name_end = name_start + source[name_start..].find(';')?;
+    Some((declaration_start, name_start, name_end))
+}
@@ -91,6 +91,7 @@ impl Material for ORMMaterial {
                 output.push_str("#define USE_OCCLUSION_TEXTURE;\n");
             }
         }
+        output.push_str(include_str!("shaders/clip_planes.frag"));
         output.push_str(include_str!("shaders/orm_material.frag"));
         output
     }
@@ -114,6 +115,10 @@ impl Material for ORMMaterial {
     fn material_type(&self) -> MaterialType {
         MaterialType::Opaque
     }
+
+    fn sort_key(&self) -> u64 {
+        super::texture_key(&[&self.metallic_roughness_texture, &self.occlusion_texture])
+    }
 }
 
 impl Default for ORMMaterial {
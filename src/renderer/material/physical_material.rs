@@ -166,6 +166,7 @@ impl Material for PhysicalMaterial {
         if use_vertex_colors {
             output.push_str("#define USE_VERTEX_COLORS\nin vec4 col;\n");
         }
+        output.push_str(include_str!("shaders/clip_planes.frag"));
         output.push_str(include_str!("shaders/physical_material.frag"));
         output
     }
@@ -219,6 +220,15 @@ impl Material for PhysicalMaterial {
             MaterialType::Opaque
         }
     }
+    fn sort_key(&self) -> u64 {
+        super::texture_key(&[
+            &self.albedo_texture,
+            &self.metallic_roughness_texture,
+            &self.occlusion_texture,
+            &self.normal_texture,
+            &self.emissive_texture,
+        ])
+    }
 }
 
 impl Default for PhysicalMaterial {
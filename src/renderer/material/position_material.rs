@@ -19,7 +19,11 @@ impl FromCpuMaterial for PositionMaterial {
 
 impl Material for PositionMaterial {
     fn fragment_shader_source(&self, _use_vertex_colors: bool, _lights: &[&dyn Light]) -> String {
-        include_str!("shaders/position_material.frag").to_string()
+        format!(
+            "{}{}",
+            include_str!("shaders/clip_planes.frag"),
+            include_str!("shaders/position_material.frag")
+        )
     }
     fn use_uniforms(&self, _program: &Program, _camera: &Camera, _lights: &[&dyn Light]) {}
     fn render_states(&self) -> RenderStates {
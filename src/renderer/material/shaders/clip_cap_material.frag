@@ -0,0 +1,42 @@
+uniform vec4 surfaceColor;
+uniform vec3 cameraPosition;
+uniform mat4 viewProjection;
+
+#ifdef USE_CLIP_PLANES
+in vec3 pos;
+#endif
+
+layout (location = 0) out vec4 outColor;
+
+void main()
+{
+#ifdef USE_CLIP_PLANES
+    if(is_clipped(pos)) {
+        discard;
+    }
+
+    // Find where the ray from the camera to this back face enters the region kept by the clip planes
+    vec3 direction = pos - cameraPosition;
+    float t = -1.0;
+    for (int i = 0; i < 8; i++) {
+        if (i >= clipPlaneCount) {
+            break;
+        }
+        float cameraDistance = dot(clipPlanes[i].xyz, cameraPosition) + clipPlanes[i].w;
+        float denominator = dot(clipPlanes[i].xyz, direction);
+        if (cameraDistance < 0.0 && denominator > 0.0) {
+            t = max(t, -cameraDistance / denominator);
+        }
+    }
+    if (t < 0.0) {
+        discard;
+    }
+
+    vec4 capPosition = viewProjection * vec4(cameraPosition + t * direction, 1.0);
+    gl_FragDepth = 0.5 * capPosition.z / capPosition.w + 0.5;
+    outColor = vec4(srgb_from_rgb(surfaceColor.rgb), surfaceColor.a);
+#else
+    // Nothing is cut away without clip planes, so there is no hole to fill
+    discard;
+#endif
+}
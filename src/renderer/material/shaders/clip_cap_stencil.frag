@@ -0,0 +1,41 @@
+uniform vec4 surfaceColor;
+uniform mat4 viewProjection;
+uniform mat4 viewProjectionInverse;
+uniform int capPlane;
+
+in vec2 uv;
+
+layout (location = 0) out vec4 outColor;
+
+void main()
+{
+    // Intersect the view ray through this pixel with the clip plane
+    vec4 near = viewProjectionInverse * vec4(2.0 * uv - 1.0, -1.0, 1.0);
+    vec4 far = viewProjectionInverse * vec4(2.0 * uv - 1.0, 1.0, 1.0);
+    vec3 origin = near.xyz / near.w;
+    vec3 direction = far.xyz / far.w - origin;
+    vec4 plane = clipPlanes[capPlane];
+    float denominator = dot(plane.xyz, direction);
+    if (abs(denominator) < 0.000001) {
+        discard;
+    }
+    float t = -(dot(plane.xyz, origin) + plane.w) / denominator;
+    if (t < 0.0 || t > 1.0) {
+        discard;
+    }
+    vec3 position = origin + t * direction;
+
+    // The cap is cut by the other clip planes
+    for (int i = 0; i < 8; i++) {
+        if (i >= clipPlaneCount) {
+            break;
+        }
+        if (i != capPlane && dot(clipPlanes[i].xyz, position) + clipPlanes[i].w < 0.0) {
+            discard;
+        }
+    }
+
+    vec4 capPosition = viewProjection * vec4(position, 1.0);
+    gl_FragDepth = 0.5 * capPosition.z / capPosition.w + 0.5;
+    outColor = vec4(srgb_from_rgb(surfaceColor.rgb), surfaceColor.a);
+}
@@ -0,0 +1,21 @@
+
+#ifdef USE_CLIP_PLANES
+uniform int clipPlaneCount;
+uniform vec4 clipPlanes[8];
+#endif
+
+// Returns true if the given world position is on the clipped side of one of the clip planes
+bool is_clipped(vec3 position)
+{
+#ifdef USE_CLIP_PLANES
+    for (int i = 0; i < 8; i++) {
+        if (i >= clipPlaneCount) {
+            break;
+        }
+        if (dot(clipPlanes[i].xyz, position) + clipPlanes[i].w < 0.0) {
+            return true;
+        }
+    }
+#endif
+    return false;
+}
@@ -4,10 +4,20 @@ uniform vec4 surfaceColor;
 uniform sampler2D tex;
 #endif
 
+#ifdef USE_CLIP_PLANES
+in vec3 pos;
+#endif
+
 layout (location = 0) out vec4 outColor;
 
 void main()
 {
+#ifdef USE_CLIP_PLANES
+    if(is_clipped(pos)) {
+        discard;
+    }
+#endif
+
     outColor = surfaceColor;
     
     #ifdef USE_VERTEX_COLORS
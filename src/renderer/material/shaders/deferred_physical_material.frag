@@ -35,6 +35,10 @@ layout (location = 2) out vec4 outEmissive;
 
 void main()
 {
+    if(is_clipped(pos)) {
+        discard;
+    }
+
     vec4 surface_color = albedo;
 #ifdef USE_ALBEDO_TEXTURE
     vec4 c = texture(albedoTexture, uvs);
@@ -9,6 +9,10 @@ layout (location = 0) out vec4 outColor;
 
 void main()
 {
+    if(is_clipped(pos)) {
+        discard;
+    }
+
     float dist = (distance(pos, eye) - minDistance) / (maxDistance - minDistance);
     outColor = vec4(dist, dist, dist, 1.0);
 }
\ No newline at end of file
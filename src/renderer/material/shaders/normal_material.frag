@@ -6,10 +6,19 @@ uniform sampler2D normalTexture;
 uniform float normalScale;
 #endif
 
+#ifdef USE_CLIP_PLANES
+in vec3 pos;
+#endif
+
 layout (location = 0) out vec4 outColor;
 
 void main()
 {
+#ifdef USE_CLIP_PLANES
+    if(is_clipped(pos)) {
+        discard;
+    }
+#endif
 
     vec3 normal = normalize(gl_FrontFacing ? nor : -nor);
 #ifdef USE_TEXTURE
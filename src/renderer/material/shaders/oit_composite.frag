@@ -0,0 +1,18 @@
+
+uniform sampler2DArray oitTexture;
+
+in vec2 uv;
+
+layout (location = 0) out vec4 outColor;
+
+void main()
+{
+    vec4 accumulation = texture(oitTexture, vec3(uv, 0));
+    float revealage = accumulation.a;
+    if(revealage > 0.9999)
+    {
+        discard;
+    }
+    float weight = texture(oitTexture, vec3(uv, 1)).r;
+    outColor = vec4(accumulation.rgb / max(weight, 0.00001), 1.0 - revealage);
+}
@@ -0,0 +1,16 @@
+
+layout (location = 0) out vec4 outAccumulation;
+layout (location = 1) out vec4 outWeight;
+
+void main()
+{
+    oit_material_main();
+    vec4 color = oit_material_color;
+
+    // Weight function (eq. 10) from McGuire and Bavoil, Weighted Blended Order-Independent Transparency
+    float depth = 1.0 - gl_FragCoord.z * 0.9;
+    float weight = clamp(pow(min(1.0, color.a * 10.0) + 0.01, 3.0) * 1e8 * depth * depth * depth, 1e-2, 3e3);
+
+    outAccumulation = vec4(color.rgb * color.a * weight, color.a);
+    outWeight = vec4(color.a * weight, 0.0, 0.0, color.a);
+}
@@ -11,10 +11,20 @@ uniform sampler2D occlusionTexture;
 uniform float occlusionStrength;
 #endif
 
+#ifdef USE_CLIP_PLANES
+in vec3 pos;
+#endif
+
 layout (location = 0) out vec4 outColor;
 
 void main()
 {
+#ifdef USE_CLIP_PLANES
+    if(is_clipped(pos)) {
+        discard;
+    }
+#endif
+
     float metallic_factor = metallic;
     float roughness_factor = roughness;
 #ifdef USE_METALLIC_ROUGHNESS_TEXTURE
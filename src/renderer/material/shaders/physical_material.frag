@@ -34,6 +34,10 @@ layout (location = 0) out vec4 outColor;
 
 void main()
 {
+    if(is_clipped(pos)) {
+        discard;
+    }
+
     vec4 surface_color = albedo;
 #ifdef USE_ALBEDO_TEXTURE
     vec4 c = texture(albedoTexture, uvs);
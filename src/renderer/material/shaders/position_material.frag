@@ -5,5 +5,9 @@ layout (location = 0) out vec4 outColor;
 
 void main()
 {
+    if(is_clipped(pos)) {
+        discard;
+    }
+
     outColor = vec4(pos, 1.0);
 }
\ No newline at end of file
@@ -1,9 +1,19 @@
 
 in vec2 uvs;
 
+#ifdef USE_CLIP_PLANES
+in vec3 pos;
+#endif
+
 layout (location = 0) out vec4 outColor;
 
 void main()
 {
+#ifdef USE_CLIP_PLANES
+    if(is_clipped(pos)) {
+        discard;
+    }
+#endif
+
     outColor = vec4(uvs, 0.0, 1.0);
 }
\ No newline at end of file
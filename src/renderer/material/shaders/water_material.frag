@@ -56,6 +56,10 @@ vec3 water(vec3 col, vec3 p1, vec3 p2)
 
 void main()
 {
+    if(is_clipped(pos)) {
+        discard;
+    }
+
     vec2 screen_uv = gl_FragCoord.xy/screenSize;
     
     vec3 normal = normalize(nor);
@@ -25,8 +25,9 @@ pub struct WaterMaterial<'a> {
 impl Material for WaterMaterial<'_> {
     fn fragment_shader_source(&self, _use_vertex_colors: bool, lights: &[&dyn Light]) -> String {
         format!(
-            "{}\n{}",
+            "{}\n{}{}",
             lights_shader_source(lights, self.lighting_model),
+            include_str!("shaders/clip_planes.frag"),
             include_str!("shaders/water_material.frag")
         )
     }
@@ -57,6 +57,10 @@ mod bounding_box;
 #[doc(inline)]
 pub use bounding_box::*;
 
+mod batch;
+#[doc(inline)]
+pub use batch::*;
+
 use crate::core::*;
 use crate::renderer::*;
 
@@ -98,6 +102,33 @@ pub trait Object: Geometry {
     /// Returns the type of material applied to this object.
     ///
     fn material_type(&self) -> MaterialType;
+
+    ///
+    /// Render the object into the accumulation and revealage targets used for weighted blended order-independent transparency, see [RenderTarget::render_with_oit].
+    /// This is only called for objects with a [MaterialType::Transparent] material type where [Object::supports_oit] returns true.
+    /// Objects that can be transparent should render their geometry with their material wrapped in an [OitMaterial],
+    /// the default implementation just calls [Object::render] which does not produce the correct result for transparent objects.
+    ///
+    fn render_with_oit(&self, camera: &Camera, lights: &[&dyn Light]) {
+        self.render(camera, lights)
+    }
+
+    ///
+    /// Returns whether this object can be rendered with [Object::render_with_oit], typically by checking the material with [OitMaterial::supports].
+    /// Otherwise the object is sorted back to front with the other transparent objects as when rendering without order-independent transparency.
+    /// The default implementation returns false.
+    ///
+    fn supports_oit(&self, _lights: &[&dyn Light]) -> bool {
+        false
+    }
+
+    ///
+    /// Returns the key used to sort this object relative to other objects before rendering to minimise the number of state changes, see [RenderQueue].
+    /// The default implementation returns `None`, in which case the object is only sorted by distance to the camera.
+    ///
+    fn render_key(&self, _lights: &[&dyn Light]) -> Option<RenderKey> {
+        None
+    }
 }
 
 impl<T: Object + ?Sized> Object for &T {
@@ -108,6 +139,18 @@ impl<T: Object + ?Sized> Object for &T {
     fn material_type(&self) -> MaterialType {
         (*self).material_type()
     }
+
+    fn render_with_oit(&self, camera: &Camera, lights: &[&dyn Light]) {
+        (*self).render_with_oit(camera, lights)
+    }
+
+    fn supports_oit(&self, lights: &[&dyn Light]) -> bool {
+        (*self).supports_oit(lights)
+    }
+
+    fn render_key(&self, lights: &[&dyn Light]) -> Option<RenderKey> {
+        (*self).render_key(lights)
+    }
 }
 
 impl<T: Object + ?Sized> Object for &mut T {
@@ -118,6 +161,18 @@ impl<T: Object + ?Sized> Object for &mut T {
     fn material_type(&self) -> MaterialType {
         (**self).material_type()
     }
+
+    fn render_with_oit(&self, camera: &Camera, lights: &[&dyn Light]) {
+        (**self).render_with_oit(camera, lights)
+    }
+
+    fn supports_oit(&self, lights: &[&dyn Light]) -> bool {
+        (**self).supports_oit(lights)
+    }
+
+    fn render_key(&self, lights: &[&dyn Light]) -> Option<RenderKey> {
+        (**self).render_key(lights)
+    }
 }
 
 impl<T: Object> Object for Box<T> {
@@ -128,6 +183,18 @@ impl<T: Object> Object for Box<T> {
     fn material_type(&self) -> MaterialType {
         self.as_ref().material_type()
     }
+
+    fn render_with_oit(&self, camera: &Camera, lights: &[&dyn Light]) {
+        self.as_ref().render_with_oit(camera, lights)
+    }
+
+    fn supports_oit(&self, lights: &[&dyn Light]) -> bool {
+        self.as_ref().supports_oit(lights)
+    }
+
+    fn render_key(&self, lights: &[&dyn Light]) -> Option<RenderKey> {
+        self.as_ref().render_key(lights)
+    }
 }
 
 impl<T: Object> Object for std::rc::Rc<T> {
@@ -138,6 +205,18 @@ impl<T: Object> Object for std::rc::Rc<T> {
     fn material_type(&self) -> MaterialType {
         self.as_ref().material_type()
     }
+
+    fn render_with_oit(&self, camera: &Camera, lights: &[&dyn Light]) {
+        self.as_ref().render_with_oit(camera, lights)
+    }
+
+    fn supports_oit(&self, lights: &[&dyn Light]) -> bool {
+        self.as_ref().supports_oit(lights)
+    }
+
+    fn render_key(&self, lights: &[&dyn Light]) -> Option<RenderKey> {
+        self.as_ref().render_key(lights)
+    }
 }
 
 impl<T: Object> Object for std::sync::Arc<T> {
@@ -148,6 +227,18 @@ impl<T: Object> Object for std::sync::Arc<T> {
     fn material_type(&self) -> MaterialType {
         self.as_ref().material_type()
     }
+
+    fn render_with_oit(&self, camera: &Camera, lights: &[&dyn Light]) {
+        self.as_ref().render_with_oit(camera, lights)
+    }
+
+    fn supports_oit(&self, lights: &[&dyn Light]) -> bool {
+        self.as_ref().supports_oit(lights)
+    }
+
+    fn render_key(&self, lights: &[&dyn Light]) -> Option<RenderKey> {
+        self.as_ref().render_key(lights)
+    }
 }
 
 impl<T: Object> Object for std::cell::RefCell<T> {
@@ -158,6 +249,18 @@ impl<T: Object> Object for std::cell::RefCell<T> {
     fn material_type(&self) -> MaterialType {
         self.borrow().material_type()
     }
+
+    fn render_with_oit(&self, camera: &Camera, lights: &[&dyn Light]) {
+        self.borrow().render_with_oit(camera, lights)
+    }
+
+    fn supports_oit(&self, lights: &[&dyn Light]) -> bool {
+        self.borrow().supports_oit(lights)
+    }
+
+    fn render_key(&self, lights: &[&dyn Light]) -> Option<RenderKey> {
+        self.borrow().render_key(lights)
+    }
 }
 
 impl<T: Object> Object for std::sync::RwLock<T> {
@@ -168,6 +271,18 @@ impl<T: Object> Object for std::sync::RwLock<T> {
     fn material_type(&self) -> MaterialType {
         self.read().unwrap().material_type()
     }
+
+    fn render_with_oit(&self, camera: &Camera, lights: &[&dyn Light]) {
+        self.read().unwrap().render_with_oit(camera, lights)
+    }
+
+    fn supports_oit(&self, lights: &[&dyn Light]) -> bool {
+        self.read().unwrap().supports_oit(lights)
+    }
+
+    fn render_key(&self, lights: &[&dyn Light]) -> Option<RenderKey> {
+        self.read().unwrap().render_key(lights)
+    }
 }
 
 // Object2D trait
@@ -0,0 +1,198 @@
+use crate::renderer::*;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+type BatchKey = (Vec<usize>, usize);
+
+struct Batch<'a, M: Material> {
+    mesh: &'a Mesh,
+    material: &'a Arc<M>,
+    transformations: Vec<Mat4>,
+    texture_transforms: Vec<Mat3>,
+}
+
+impl<M: Material> Batch<'_, M> {
+    fn instances(&self) -> Instances {
+        let mut translations = Vec::with_capacity(self.transformations.len());
+        let mut rotations = Vec::with_capacity(self.transformations.len());
+        let mut scales = Vec::with_capacity(self.transformations.len());
+        for transformation in self.transformations.iter() {
+            let (translation, rotation, scale) = decompose(transformation);
+            translations.push(translation);
+            rotations.push(rotation);
+            scales.push(scale);
+        }
+        Instances {
+            translations,
+            rotations: Some(rotations),
+            scales: Some(scales),
+            texture_transforms: if self
+                .texture_transforms
+                .iter()
+                .all(|t| *t == Mat3::identity())
+            {
+                None
+            } else {
+                Some(self.texture_transforms.clone())
+            },
+            ..Default::default()
+        }
+    }
+}
+
+// Groups the objects which share both geometry and material.
+fn group<'a, M: Material + 'a>(
+    objects: impl IntoIterator<Item = &'a Gm<Mesh, Arc<M>>>,
+) -> Vec<(BatchKey, Batch<'a, M>)> {
+    let mut batches: Vec<(BatchKey, Batch<'a, M>)> = Vec::new();
+    let mut batch_indices = HashMap::new();
+    for object in objects {
+        let key = (
+            object.geometry.buffers_id(),
+            Arc::as_ptr(&object.material) as *const u8 as usize,
+        );
+        let index = *batch_indices.entry(key.clone()).or_insert_with(|| {
+            batches.push((
+                key,
+                Batch {
+                    mesh: &object.geometry,
+                    material: &object.material,
+                    transformations: Vec::new(),
+                    texture_transforms: Vec::new(),
+                },
+            ));
+            batches.len() - 1
+        });
+        batches[index]
+            .1
+            .transformations
+            .push(object.geometry.transformation());
+        batches[index]
+            .1
+            .texture_transforms
+            .push(*object.geometry.texture_transform());
+    }
+    batches
+}
+
+///
+/// Combines the given objects which share both geometry and material into objects that are rendered with a single instanced draw call each.
+/// Two objects share geometry if the meshes share GPU buffers (see [Mesh::shares_buffers_with]), ie. if one mesh is a clone of the other and they have the same custom attributes.
+/// They share material if they point to the same [Arc].
+/// The transformation and texture transform of each mesh is converted to the attributes of an instance in [Instances].
+///
+/// The returned objects can be rendered instead of the given objects with the same result, except that shear in the transformations is not supported.
+/// Since the returned objects are not updated when the given objects change, this is mostly useful for static parts of a scene.
+/// Use [RenderTarget::render_with_batching] to batch the objects each time they are rendered instead.
+///
+pub fn batch_meshes<'a, M: Material + 'a>(
+    context: &Context,
+    objects: impl IntoIterator<Item = &'a Gm<Mesh, Arc<M>>>,
+) -> Vec<Gm<InstancedMesh, Arc<M>>> {
+    group(objects)
+        .into_iter()
+        .map(|(_, batch)| {
+            Gm::new(
+                InstancedMesh::from_mesh(context, &batch.instances(), batch.mesh),
+                batch.material.clone(),
+            )
+        })
+        .collect()
+}
+
+struct CachedBatch<M: Material> {
+    object: Gm<InstancedMesh, Arc<M>>,
+    transformations: Vec<Mat4>,
+    texture_transforms: Vec<Mat3>,
+}
+
+///
+/// Keeps the batched objects between frames, see [RenderTarget::render_with_batching].
+/// The objects are batched in the same way as [batch_meshes], but each time they are rendered,
+/// so changes to the transformations are applied and objects can be added and removed.
+/// The instance data of a batch is only transferred to the GPU again if the transformations of the objects in the batch have changed.
+///
+pub struct MeshBatching<M: Material> {
+    context: Context,
+    batches: RwLock<HashMap<BatchKey, CachedBatch<M>>>,
+}
+
+impl<M: Material> MeshBatching<M> {
+    ///
+    /// Creates a new batching state with no batches.
+    ///
+    pub fn new(context: &Context) -> Self {
+        Self {
+            context: context.clone(),
+            batches: RwLock::new(HashMap::new()),
+        }
+    }
+
+    ///
+    /// Batches the given objects and calls the callback with the batched objects.
+    ///
+    pub(in crate::renderer) fn batch<'a, T>(
+        &self,
+        objects: impl IntoIterator<Item = &'a Gm<Mesh, Arc<M>>>,
+        callback: impl FnOnce(&[&Gm<InstancedMesh, Arc<M>>]) -> T,
+    ) -> T
+    where
+        M: 'a,
+    {
+        let groups = group(objects);
+        let mut batches = self.batches.write().unwrap();
+        batches.retain(|key, _| groups.iter().any(|(k, _)| k == key));
+        for (key, batch) in groups.iter() {
+            if let Some(cached) = batches.get_mut(key) {
+                if cached.transformations != batch.transformations
+                    || cached.texture_transforms != batch.texture_transforms
+                {
+                    cached.object.geometry.set_instances(&batch.instances());
+                    cached.transformations = batch.transformations.clone();
+                    cached.texture_transforms = batch.texture_transforms.clone();
+                }
+            } else {
+                batches.insert(
+                    key.clone(),
+                    CachedBatch {
+                        object: Gm::new(
+                            InstancedMesh::from_mesh(&self.context, &batch.instances(), batch.mesh),
+                            batch.material.clone(),
+                        ),
+                        transformations: batch.transformations.clone(),
+                        texture_transforms: batch.texture_transforms.clone(),
+                    },
+                );
+            }
+        }
+        callback(
+            &groups
+                .iter()
+                .map(|(key, _)| &batches.get(key).unwrap().object)
+                .collect::<Vec<_>>(),
+        )
+    }
+}
+
+fn decompose(transformation: &Mat4) -> (Vec3, Quat, Vec3) {
+    let x = transformation.x.truncate();
+    let y = transformation.y.truncate();
+    let z = transformation.z.truncate();
+    let mut scale = vec3(x.magnitude(), y.magnitude(), z.magnitude());
+    if Mat3::from_cols(x, y, z).determinant() < 0.0 {
+        scale.x = -scale.x;
+    }
+    let axis = |column: Vec3, scale: f32| {
+        if scale == 0.0 {
+            column
+        } else {
+            column / scale
+        }
+    };
+    let rotation = Quat::from(Mat3::from_cols(
+        axis(x, scale.x),
+        axis(y, scale.y),
+        axis(z, scale.z),
+    ));
+    (transformation.w.truncate(), rotation, scale)
+}
@@ -4,6 +4,7 @@ use crate::renderer::*;
 /// A combination of a [Geometry] and a [Material] which implements [Object].
 /// Use this to combine any [geometry] and [material] into an object that can be used in a render function for example [RenderTarget::render].
 /// The only requirement is that the geometry provides all the per vertex information (normals, uv coordinates, etc.) that the material requires.
+/// When the object is rendered with another material, for example in a shadow pass, the vertices are still changed by the snippet of its own material, see [Material::vertex_shader_source].
 ///
 pub struct Gm<G: Geometry, M: Material> {
     /// The geometry
@@ -41,7 +42,50 @@ impl<G: Geometry, M: Material> Geometry for Gm<G, M> {
         camera: &Camera,
         lights: &[&dyn Light],
     ) {
-        self.geometry.render_with_material(material, camera, lights)
+        if material.vertex_shader_source().is_none()
+            && self.material.vertex_shader_source().is_some()
+        {
+            self.geometry.render_with_material(
+                &VertexMaterial {
+                    material,
+                    vertex_material: &self.material,
+                },
+                camera,
+                lights,
+            )
+        } else {
+            self.geometry.render_with_material(material, camera, lights)
+        }
+    }
+}
+
+// Renders with the given material, except that the vertices are changed by the snippet of the vertex material.
+struct VertexMaterial<'a> {
+    material: &'a dyn Material,
+    vertex_material: &'a dyn Material,
+}
+
+impl Material for VertexMaterial<'_> {
+    fn fragment_shader_source(&self, use_vertex_colors: bool, lights: &[&dyn Light]) -> String {
+        self.material
+            .fragment_shader_source(use_vertex_colors, lights)
+    }
+    fn use_uniforms(&self, program: &Program, camera: &Camera, lights: &[&dyn Light]) {
+        // Sends the uniforms of the snippet, the uniforms of the given material are sent afterwards in case of a name clash
+        self.vertex_material.use_uniforms(program, camera, lights);
+        self.material.use_uniforms(program, camera, lights)
+    }
+    fn render_states(&self) -> RenderStates {
+        self.material.render_states()
+    }
+    fn material_type(&self) -> MaterialType {
+        self.material.material_type()
+    }
+    fn sort_key(&self) -> u64 {
+        self.material.sort_key()
+    }
+    fn vertex_shader_source(&self) -> Option<String> {
+        self.vertex_material.vertex_shader_source()
     }
 }
 
@@ -53,6 +97,18 @@ impl<G: Geometry, M: Material> Object for Gm<G, M> {
     fn material_type(&self) -> MaterialType {
         self.material.material_type()
     }
+
+    fn render_with_oit(&self, camera: &Camera, lights: &[&dyn Light]) {
+        self.render_with_material(&OitMaterial::new(&self.material), camera, lights)
+    }
+
+    fn supports_oit(&self, lights: &[&dyn Light]) -> bool {
+        OitMaterial::supports(&self.material, lights)
+    }
+
+    fn render_key(&self, _lights: &[&dyn Light]) -> Option<RenderKey> {
+        Some(RenderKey::new::<G, M>(&self.material))
+    }
 }
 
 impl<G: Geometry + Clone, M: Material + Clone> Clone for Gm<G, M> {
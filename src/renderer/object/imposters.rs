@@ -66,6 +66,10 @@ impl Imposters {
         self.material
             .update(aabb, objects, lights, max_texture_size);
     }
+
+    pub(in crate::renderer) fn material(&self) -> &dyn Material {
+        &self.material
+    }
 }
 
 fn get_sprite_transform(aabb: AxisAlignedBoundingBox) -> Mat4 {
@@ -112,6 +116,14 @@ impl Object for Imposters {
     fn material_type(&self) -> MaterialType {
         self.material.material_type()
     }
+
+    fn render_with_oit(&self, camera: &Camera, lights: &[&dyn Light]) {
+        self.render_with_material(&OitMaterial::new(&self.material), camera, lights)
+    }
+
+    fn supports_oit(&self, lights: &[&dyn Light]) -> bool {
+        OitMaterial::supports(&self.material, lights)
+    }
 }
 
 struct ImpostersMaterial {
@@ -209,8 +221,9 @@ impl ImpostersMaterial {
 impl Material for ImpostersMaterial {
     fn fragment_shader_source(&self, _use_vertex_colors: bool, _lights: &[&dyn Light]) -> String {
         format!(
-            "{}{}",
+            "{}{}{}",
             include_str!("../../core/shared.frag"),
+            include_str!("../material/shaders/clip_planes.frag"),
             include_str!("shaders/imposter.frag")
         )
     }
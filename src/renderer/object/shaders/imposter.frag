@@ -3,11 +3,20 @@ uniform sampler2DArray tex;
 uniform mat4 view;
 uniform int no_views;
 in vec2 uvs;
+#ifdef USE_CLIP_PLANES
+in vec3 pos;
+#endif
 
 layout (location = 0) out vec4 out_color;
 
 void main()
 {
+#ifdef USE_CLIP_PLANES
+    if(is_clipped(pos)) {
+        discard;
+    }
+#endif
+
     vec3 dir = normalize(vec3(view[0][2], 0.0, view[2][2]));
     float a = acos(dir.x);
     float angle = (dir.z > 0.0 ? a : 2.0 * PI - a) / (2.0 * PI);
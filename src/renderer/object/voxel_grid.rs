@@ -73,4 +73,16 @@ impl<M: Material> Object for VoxelGrid<M> {
     fn material_type(&self) -> MaterialType {
         self.0.material_type()
     }
+
+    fn render_with_oit(&self, camera: &Camera, lights: &[&dyn Light]) {
+        self.0.render_with_oit(camera, lights)
+    }
+
+    fn supports_oit(&self, lights: &[&dyn Light]) -> bool {
+        self.0.supports_oit(lights)
+    }
+
+    fn render_key(&self, lights: &[&dyn Light]) -> Option<RenderKey> {
+        self.0.render_key(lights)
+    }
 }
@@ -0,0 +1,279 @@
+use crate::renderer::*;
+use std::collections::HashMap;
+
+///
+/// A bounding volume hierarchy over the axis aligned bounding boxes of a set of objects, used to quickly find the objects inside the frustum of a [Camera].
+/// Each object is identified by an id, typically the index of the object in a list of objects.
+///
+/// The bounding boxes are stored with a margin, so small movements of an object does not require the hierarchy to change.
+/// When an object is moved, call [ObjectBvh::update] with the new bounding box. This only changes the hierarchy if the object moved outside the margin.
+///
+#[derive(Clone, Default)]
+pub struct ObjectBvh {
+    nodes: Vec<BvhNode>,
+    free_nodes: Vec<usize>,
+    root: Option<usize>,
+    leaves: HashMap<usize, usize>,
+}
+
+#[derive(Clone)]
+struct BvhNode {
+    aabb: AxisAlignedBoundingBox,
+    parent: Option<usize>,
+    children: Option<[usize; 2]>,
+    id: usize,
+}
+
+impl ObjectBvh {
+    ///
+    /// Creates a new empty bounding volume hierarchy.
+    ///
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///
+    /// Creates a new bounding volume hierarchy containing the given geometries, where the id of each geometry is its index.
+    ///
+    pub fn from_geometries(geometries: impl IntoIterator<Item = impl Geometry>) -> Self {
+        let mut bvh = Self::new();
+        for (id, geometry) in geometries.into_iter().enumerate() {
+            bvh.insert(id, geometry.aabb());
+        }
+        bvh
+    }
+
+    ///
+    /// Returns the number of objects in this hierarchy. Objects with an empty bounding box are not included.
+    ///
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    ///
+    /// Returns true if this hierarchy contains no objects.
+    ///
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    ///
+    /// Returns a bounding box which contains all objects in this hierarchy. It is not necessarily tight because of the margin added to each object.
+    ///
+    pub fn aabb(&self) -> AxisAlignedBoundingBox {
+        self.root
+            .map(|root| self.nodes[root].aabb)
+            .unwrap_or(AxisAlignedBoundingBox::EMPTY)
+    }
+
+    ///
+    /// Inserts the object with the given id and bounding box. If an object with the same id already exists, it is updated instead.
+    /// An object with an empty bounding box is never visible and is therefore not inserted.
+    ///
+    pub fn insert(&mut self, id: usize, aabb: AxisAlignedBoundingBox) {
+        self.update(id, aabb);
+    }
+
+    ///
+    /// Updates the bounding box of the object with the given id, for example when the transformation of the object has changed.
+    /// Returns true if the hierarchy changed, ie. if the object was not already inside its bounding box with margin.
+    ///
+    pub fn update(&mut self, id: usize, aabb: AxisAlignedBoundingBox) -> bool {
+        if aabb.is_empty() {
+            return self.remove(id);
+        }
+        if let Some(leaf) = self.leaves.get(&id) {
+            if contains(&self.nodes[*leaf].aabb, &aabb) {
+                return false;
+            }
+            self.remove(id);
+        }
+        let margin = 0.1 * aabb.size();
+        let leaf = self.allocate_node(BvhNode {
+            aabb: AxisAlignedBoundingBox::new_with_positions(&[
+                aabb.min() - margin,
+                aabb.max() + margin,
+            ]),
+            parent: None,
+            children: None,
+            id,
+        });
+        self.leaves.insert(id, leaf);
+        self.insert_leaf(leaf);
+        true
+    }
+
+    ///
+    /// Removes the object with the given id. Returns true if the object was part of the hierarchy.
+    ///
+    pub fn remove(&mut self, id: usize) -> bool {
+        if let Some(leaf) = self.leaves.remove(&id) {
+            self.remove_leaf(leaf);
+            self.free_nodes.push(leaf);
+            true
+        } else {
+            false
+        }
+    }
+
+    ///
+    /// Returns the ids, in increasing order, of the objects which bounding box with margin is inside the frustum of the given camera.
+    ///
+    pub fn visible(&self, camera: &Camera) -> Vec<usize> {
+        let mut ids = Vec::new();
+        let mut stack = self.root.into_iter().collect::<Vec<_>>();
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if camera.in_frustum(&node.aabb) {
+                if let Some(children) = node.children {
+                    stack.extend(children);
+                } else {
+                    ids.push(node.id);
+                }
+            }
+        }
+        ids.sort_unstable();
+        ids
+    }
+
+    ///
+    /// Returns the objects inside the frustum of the given camera, assuming that the id of each object is its index in the given list of objects.
+    /// The result can be used directly in a render call, for example [RenderTarget::render].
+    ///
+    pub fn visible_objects<'a, T>(&self, camera: &Camera, objects: &'a [T]) -> Vec<&'a T> {
+        self.visible(camera)
+            .into_iter()
+            .filter_map(|id| objects.get(id))
+            .collect()
+    }
+
+    fn allocate_node(&mut self, node: BvhNode) -> usize {
+        if let Some(index) = self.free_nodes.pop() {
+            self.nodes[index] = node;
+            index
+        } else {
+            self.nodes.push(node);
+            self.nodes.len() - 1
+        }
+    }
+
+    fn insert_leaf(&mut self, leaf: usize) {
+        let root = if let Some(root) = self.root {
+            root
+        } else {
+            self.nodes[leaf].parent = None;
+            self.root = Some(leaf);
+            return;
+        };
+
+        // Find the best sibling by descending the hierarchy using the surface area heuristic
+        let leaf_aabb = self.nodes[leaf].aabb;
+        let mut index = root;
+        while let Some(children) = self.nodes[index].children {
+            let area = surface_area(&self.nodes[index].aabb);
+            let combined_area = surface_area(&union(&self.nodes[index].aabb, &leaf_aabb));
+            let cost = 2.0 * combined_area;
+            let inheritance_cost = 2.0 * (combined_area - area);
+            let child_cost = |child: usize| {
+                let child_aabb = &self.nodes[child].aabb;
+                let area = surface_area(&union(child_aabb, &leaf_aabb));
+                if self.nodes[child].children.is_some() {
+                    area - surface_area(child_aabb) + inheritance_cost
+                } else {
+                    area + inheritance_cost
+                }
+            };
+            let cost0 = child_cost(children[0]);
+            let cost1 = child_cost(children[1]);
+            if cost < cost0 && cost < cost1 {
+                break;
+            }
+            index = if cost0 < cost1 {
+                children[0]
+            } else {
+                children[1]
+            };
+        }
+
+        let sibling = index;
+        let old_parent = self.nodes[sibling].parent;
+        let new_parent = self.allocate_node(BvhNode {
+            aabb: union(&self.nodes[sibling].aabb, &leaf_aabb),
+            parent: old_parent,
+            children: Some([sibling, leaf]),
+            id: 0,
+        });
+        self.nodes[sibling].parent = Some(new_parent);
+        self.nodes[leaf].parent = Some(new_parent);
+        if let Some(old_parent) = old_parent {
+            self.replace_child(old_parent, sibling, new_parent);
+            self.refit(Some(old_parent));
+        } else {
+            self.root = Some(new_parent);
+        }
+    }
+
+    fn remove_leaf(&mut self, leaf: usize) {
+        if self.root == Some(leaf) {
+            self.root = None;
+            return;
+        }
+        let parent = self.nodes[leaf].parent.unwrap();
+        let children = self.nodes[parent].children.unwrap();
+        let sibling = if children[0] == leaf {
+            children[1]
+        } else {
+            children[0]
+        };
+        let grand_parent = self.nodes[parent].parent;
+        self.nodes[sibling].parent = grand_parent;
+        if let Some(grand_parent) = grand_parent {
+            self.replace_child(grand_parent, parent, sibling);
+            self.refit(Some(grand_parent));
+        } else {
+            self.root = Some(sibling);
+        }
+        self.free_nodes.push(parent);
+    }
+
+    fn replace_child(&mut self, parent: usize, old_child: usize, new_child: usize) {
+        if let Some(ref mut children) = self.nodes[parent].children {
+            if children[0] == old_child {
+                children[0] = new_child;
+            } else {
+                children[1] = new_child;
+            }
+        }
+    }
+
+    fn refit(&mut self, mut index: Option<usize>) {
+        while let Some(i) = index {
+            let children = self.nodes[i].children.unwrap();
+            self.nodes[i].aabb =
+                union(&self.nodes[children[0]].aabb, &self.nodes[children[1]].aabb);
+            index = self.nodes[i].parent;
+        }
+    }
+}
+
+fn union(a: &AxisAlignedBoundingBox, b: &AxisAlignedBoundingBox) -> AxisAlignedBoundingBox {
+    let mut aabb = *a;
+    aabb.expand_with_aabb(b);
+    aabb
+}
+
+fn surface_area(aabb: &AxisAlignedBoundingBox) -> f32 {
+    let size = aabb.size();
+    2.0 * (size.x * size.y + size.y * size.z + size.z * size.x)
+}
+
+fn contains(outer: &AxisAlignedBoundingBox, inner: &AxisAlignedBoundingBox) -> bool {
+    let (outer_min, outer_max) = (outer.min(), outer.max());
+    let (inner_min, inner_max) = (inner.min(), inner.max());
+    outer_min.x <= inner_min.x
+        && outer_min.y <= inner_min.y
+        && outer_min.z <= inner_min.z
+        && outer_max.x >= inner_max.x
+        && outer_max.y >= inner_max.y
+        && outer_max.z >= inner_max.z
+}
@@ -0,0 +1,141 @@
+use crate::renderer::*;
+use std::sync::RwLock;
+
+struct OcclusionState {
+    visible: bool,
+    query: Option<OcclusionQuery>,
+}
+
+///
+/// Keeps track of which objects are hidden behind other objects, see [RenderTarget::render_with_occlusion_culling].
+/// For each object, the bounding box is rendered after the objects using an [OcclusionQuery], which tests whether any part of the bounding box is visible.
+/// The result of the query is available a frame or more later, at which point it is used to skip rendering the object if it was hidden.
+/// Objects are assumed to be visible until the first result is available, so an object might be rendered although it is hidden, but not the other way around.
+///
+/// The objects are identified by their position in the list of objects given to the render call, so the same objects must be given in the same order every frame.
+///
+pub struct OcclusionCulling {
+    context: Context,
+    position_buffer: VertexBuffer,
+    states: RwLock<Vec<OcclusionState>>,
+    // Queries which are not running, kept to avoid creating and deleting a query each time a result is read.
+    free_queries: RwLock<Vec<OcclusionQuery>>,
+}
+
+impl OcclusionCulling {
+    ///
+    /// Creates a new occlusion culling state with no objects.
+    ///
+    pub fn new(context: &Context) -> Self {
+        let cube = CpuMesh::cube();
+        let positions = cube.positions.to_f32();
+        let positions = if let Some(indices) = cube.indices.to_u32() {
+            indices.iter().map(|i| positions[*i as usize]).collect()
+        } else {
+            positions
+        };
+        Self {
+            context: context.clone(),
+            position_buffer: VertexBuffer::new_with_data(context, &positions),
+            states: RwLock::new(Vec::new()),
+            free_queries: RwLock::new(Vec::new()),
+        }
+    }
+
+    ///
+    /// Returns whether each of the given number of objects should be rendered, based on the latest available query results.
+    ///
+    pub(in crate::renderer) fn visibility(&self, object_count: usize) -> Vec<bool> {
+        let mut states = self.states.write().unwrap();
+        let mut free_queries = self.free_queries.write().unwrap();
+        if states.len() > object_count {
+            free_queries.extend(states.drain(object_count..).filter_map(|state| state.query));
+        }
+        while states.len() < object_count {
+            states.push(OcclusionState {
+                visible: true,
+                query: None,
+            });
+        }
+        states
+            .iter_mut()
+            .map(|state| {
+                if let Some(visible) = state.query.as_ref().and_then(|query| query.result()) {
+                    state.visible = visible;
+                    free_queries.push(state.query.take().unwrap());
+                }
+                state.visible
+            })
+            .collect()
+    }
+
+    ///
+    /// Renders the given bounding boxes with occlusion queries, testing against the depth of the objects already rendered.
+    /// Must be called in the callback given as input to a [RenderTarget] write method.
+    ///
+    pub(in crate::renderer) fn render_queries(
+        &self,
+        camera: &Camera,
+        aabbs: &[AxisAlignedBoundingBox],
+    ) {
+        let render_states = RenderStates {
+            write_mask: WriteMask::NONE,
+            depth_test: DepthTest::LessOrEqual,
+            cull: Cull::None,
+            ..Default::default()
+        };
+        let mut states = self.states.write().unwrap();
+        let mut free_queries = self.free_queries.write().unwrap();
+        self.context
+            .program(
+                include_str!("shaders/occlusion_proxy.vert"),
+                include_str!("shaders/occlusion_proxy.frag"),
+                |program| {
+                    for (state, aabb) in states.iter_mut().zip(aabbs) {
+                        if state.query.is_some() {
+                            continue;
+                        }
+                        // Objects outside the frustum are assumed visible, so they do not pop in when they enter the frustum again
+                        if !camera.in_frustum(aabb) {
+                            state.visible = true;
+                            continue;
+                        }
+                        // The near plane might cut away the whole box when the camera is inside or close to it
+                        let margin = vec3(1.0, 1.0, 1.0) * camera.z_near();
+                        let (min, max) = (aabb.min() - margin, aabb.max() + margin);
+                        let position = camera.position();
+                        if position.x >= min.x
+                            && position.y >= min.y
+                            && position.z >= min.z
+                            && position.x <= max.x
+                            && position.y <= max.y
+                            && position.z <= max.z
+                        {
+                            state.visible = true;
+                            continue;
+                        }
+                        let query = free_queries
+                            .pop()
+                            .map(Ok)
+                            .unwrap_or_else(|| OcclusionQuery::new(&self.context));
+                        if let Ok(query) = query {
+                            program
+                                .use_uniform("viewProjection", camera.projection() * camera.view());
+                            program.use_uniform("aabbMin", aabb.min());
+                            program.use_uniform("aabbMax", aabb.max());
+                            program.use_vertex_attribute("position", &self.position_buffer);
+                            query.run(|| {
+                                program.draw_arrays(
+                                    render_states,
+                                    camera.viewport(),
+                                    self.position_buffer.vertex_count() as u32,
+                                )
+                            });
+                            state.query = Some(query);
+                        }
+                    }
+                },
+            )
+            .expect("Failed compiling shader");
+    }
+}
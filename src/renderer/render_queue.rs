@@ -0,0 +1,145 @@
+use crate::renderer::*;
+use std::hash::{Hash, Hasher};
+
+///
+/// Identifies the shader program and the material used for rendering an object, see [Object::render_key].
+/// Objects with equal keys can be rendered after each other without switching program or rebinding textures.
+///
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RenderKey {
+    /// A key identifying the shader program, for example a hash of the shader sources.
+    pub program: u64,
+    /// A key identifying the material, see [Material::sort_key].
+    pub material: u64,
+}
+
+impl RenderKey {
+    ///
+    /// Creates the render key for an object with a geometry of type `G` rendered with the given material of type `M`.
+    /// The program key is computed from the types of the geometry and material and the [MaterialType] without generating any shader source,
+    /// so it is cheap to compute every frame. Note that materials of the same type that generate different shader programs get the same program key
+    /// and are therefore only grouped by [Material::sort_key].
+    ///
+    pub fn new<G: Geometry + ?Sized, M: Material + ?Sized>(material: &M) -> Self {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        std::any::type_name::<G>().hash(&mut hasher);
+        std::any::type_name::<M>().hash(&mut hasher);
+        material.material_type().hash(&mut hasher);
+        Self {
+            program: hasher.finish(),
+            material: material.sort_key(),
+        }
+    }
+}
+
+///
+/// A list of objects sorted in the order they should be rendered.
+/// Opaque and deferred objects are rendered first, grouped by shader program, then by material (see [RenderKey]) and finally sorted from front to back.
+/// Transparent objects are rendered last, sorted from back to front, since the result depends on the order.
+///
+pub struct RenderQueue<T: Object> {
+    objects: Vec<T>,
+    keys: Vec<Option<RenderKey>>,
+}
+
+impl<T: Object> RenderQueue<T> {
+    ///
+    /// Sorts the given objects in the order they should be rendered using the given camera and lights.
+    ///
+    pub fn new(
+        camera: &Camera,
+        objects: impl IntoIterator<Item = T>,
+        lights: &[&dyn Light],
+    ) -> Self {
+        let mut entries = objects
+            .into_iter()
+            .map(|object| {
+                let is_transparent = object.material_type() == MaterialType::Transparent;
+                let distance = camera.position().distance2(object.aabb().center());
+                let key = object.render_key(lights);
+                (is_transparent, key, distance, object)
+            })
+            .collect::<Vec<_>>();
+        entries.sort_by(
+            |(transparent0, key0, distance0, _), (transparent1, key1, distance1, _)| {
+                transparent0.cmp(transparent1).then_with(|| {
+                    if *transparent0 {
+                        distance1
+                            .partial_cmp(distance0)
+                            .unwrap_or(std::cmp::Ordering::Equal)
+                    } else {
+                        key0.cmp(key1).then_with(|| {
+                            distance0
+                                .partial_cmp(distance1)
+                                .unwrap_or(std::cmp::Ordering::Equal)
+                        })
+                    }
+                })
+            },
+        );
+        let (keys, objects) = entries
+            .into_iter()
+            .map(|(_, key, _, object)| (key, object))
+            .unzip();
+        Self { objects, keys }
+    }
+
+    ///
+    /// Returns the objects in the order they should be rendered.
+    ///
+    pub fn objects(&self) -> &[T] {
+        &self.objects
+    }
+
+    ///
+    /// Returns the number of times the shader program or the material changes when rendering the objects in order.
+    /// Objects without a [RenderKey] are counted as a change.
+    ///
+    pub fn state_changes(&self) -> usize {
+        let mut previous = None;
+        let mut changes = 0;
+        for key in self.keys.iter() {
+            if key.is_none() || *key != previous {
+                changes += 1;
+            }
+            previous = *key;
+        }
+        changes
+    }
+
+    ///
+    /// Returns the number of times the shader program changes when rendering the objects in order.
+    /// Objects without a [RenderKey] are counted as a change.
+    ///
+    pub fn program_changes(&self) -> usize {
+        let mut previous = None;
+        let mut changes = 0;
+        for key in self.keys.iter() {
+            let program = key.map(|k| k.program);
+            if program.is_none() || program != previous {
+                changes += 1;
+            }
+            previous = program;
+        }
+        changes
+    }
+
+    ///
+    /// Renders the objects in order using the given camera and lights.
+    /// Must be called in the callback given as input to a [RenderTarget], [ColorTarget] or [DepthTarget] write method.
+    ///
+    pub fn render(&self, camera: &Camera, lights: &[&dyn Light]) {
+        for object in self.objects.iter() {
+            object.render(camera, lights);
+        }
+    }
+}
+
+impl<'a, T: Object> IntoIterator for &'a RenderQueue<T> {
+    type Item = &'a T;
+    type IntoIter = std::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.objects.iter()
+    }
+}
@@ -0,0 +1,11 @@
+uniform mat4 viewProjection;
+uniform vec3 aabbMin;
+uniform vec3 aabbMax;
+
+in vec3 position;
+
+void main()
+{
+    vec3 worldPosition = aabbMin + (0.5 * position + 0.5) * (aabbMax - aabbMin);
+    gl_Position = viewProjection * vec4(worldPosition, 1.0);
+}
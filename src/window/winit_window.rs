@@ -57,6 +57,7 @@ impl Window {
                 "webgl2",
                 &wasm_bindgen::JsValue::from_serde(&serde_json::json!({
                     "antialias": window_settings.multisamples > 0,
+                    "stencil": true,
                 }))
                 .unwrap(),
             )